@@ -1,4 +1,6 @@
 use std::{
+    hash::{Hash, Hasher},
+    mem,
     fmt::{
         Debug,
         Display,
@@ -8,6 +10,8 @@ use std::{
     f64,
     rc::Rc,
     cell::RefCell,
+    cmp::Ordering,
+    convert::TryFrom,
 };
 
 use crate::common::{
@@ -15,10 +19,33 @@ use crate::common::{
     closure::Closure,
 };
 
+use crate::core::ffi::FFIFunction;
+use crate::vm::trace::Trace;
+
+#[cfg(feature = "bignum")]
+use num_bigint::{BigInt, Sign};
+#[cfg(feature = "bignum")]
+use num_traits::ToPrimitive;
+
+#[cfg(feature = "cycle-check")]
+use std::collections::HashSet;
+
+#[cfg(feature = "serde")]
+use serde::{Serialize, Serializer};
+
 /// Built-in Passerine datatypes.
+///
+/// Equality is structural: tuples and labels are equal if their contents are,
+/// recursing into nested and heaped values.
+/// Contained reals follow IEEE 754, so a tuple containing a NaN
+/// is never equal to anything, including itself.
 #[derive(Clone, PartialEq)]
 pub enum Data {
     /// Data on the heap.
+    /// Heaped values can form reference cycles, e.g. a recursive closure
+    /// captures the variable holding itself, which `Rc` never frees.
+    /// The `cycle-check` feature warns when such a value is dropped,
+    /// see `Data::is_cyclic`.
     Heaped(Rc<RefCell<Data>>),
     /// Uninitialized data.
     NotInit,
@@ -26,17 +53,28 @@ pub enum Data {
     // Passerine Data (Atomic)
     /// Real Numbers, represented as double-precision floating points.
     Real(f64),
-    // TODO: arbitrary precision integers.
     /// Integers, currently 64-bit.
     Integer(i64),
+    /// Arbitrary-precision integers, used when an integer doesn't fit in 64 bits.
+    /// Integers that fit in 64 bits are always `Data::Integer`,
+    /// see `Data::big_integer`.
+    #[cfg(feature = "bignum")]
+    BigInt(BigInt),
     /// A boolean, like true or false.
     Boolean(bool),
     /// A UTF-8 encoded string.
-    String(String),
+    /// Strings are immutable, so clones share one allocation,
+    /// see `Interner` for sharing identical strings.
+    /// Strings sharing an allocation are equal without comparing their bytes,
+    /// as `Rc` checks for the same allocation first when its contents are `Eq`.
+    String(Rc<str>),
     /// Represents a function, ie.e some bytecode without a context.
     Lambda(Box<Lambda>),
     /// Some bytecode with a context that can be run.
     Closure(Box<Closure>),
+    /// A Rust function that can be called like a closure,
+    /// usually registered as a global in the `Prelude`.
+    NativeFunction(FFIFunction),
 
     // TODO: rework how labels and tags work
     /// `Kind` is the base component of an unconstructed label
@@ -56,7 +94,413 @@ pub enum Data {
     // // I mean, it's overkill for small things
     // // yet if people have very big records, yk.
     // Record(Vec<(Local, Data)>),
-    // ArbInt(ArbInt),
+}
+
+impl Data {
+    /// Creates an integer from a `BigInt`,
+    /// demoting it to a `Data::Integer` if it fits in 64 bits.
+    #[cfg(feature = "bignum")]
+    pub fn big_integer(n: BigInt) -> Data {
+        match i64::try_from(&n) {
+            Ok(small) => Data::Integer(small),
+            Err(_)    => Data::BigInt(n),
+        }
+    }
+}
+
+#[cfg(feature = "cycle-check")]
+impl Data {
+    /// Whether a heaped value reachable from this one can reach itself,
+    /// through tuples, labels, heaped values, and the captures of closures.
+    /// Such a cycle is never freed, as nothing outside it refers to it.
+    /// Cells that are mutably borrowed are skipped.
+    pub fn is_cyclic(&self) -> bool {
+        let mut path = vec![];
+        let mut done = HashSet::new();
+        return self.reaches_cycle(&mut path, &mut done);
+    }
+
+    fn reaches_cycle(
+        &self,
+        path: &mut Vec<*const RefCell<Data>>,
+        done: &mut HashSet<*const RefCell<Data>>,
+    ) -> bool {
+        let cell_reaches = |cell: &Rc<RefCell<Data>>, path: &mut Vec<_>, done: &mut HashSet<_>| {
+            let pointer = Rc::as_ptr(cell);
+            if path.contains(&pointer) { return true; }
+            if !done.insert(pointer) { return false; }
+
+            path.push(pointer);
+            let cyclic = match cell.try_borrow() {
+                Ok(data) => data.reaches_cycle(path, done),
+                Err(_)   => false,
+            };
+            path.pop();
+            cyclic
+        };
+
+        match self {
+            Data::Heaped(cell)     => cell_reaches(cell, path, done),
+            Data::Label(_, data)   => data.reaches_cycle(path, done),
+            Data::Tuple(items)     => items.iter().any(|item| item.reaches_cycle(path, done)),
+            Data::Closure(closure) => closure.captures.iter().any(|cell| cell_reaches(cell, path, done)),
+            _ => false,
+        }
+    }
+}
+
+impl Data {
+    /// Compares two values, like the comparison operators (e.g. `<`) do.
+    /// Unlike `partial_cmp`, integers and reals are compared
+    /// by promoting the integer to a real.
+    /// Returns `None` if either is NaN, following IEEE 754,
+    /// and an error if the values can't be ordered, e.g. a real and a string.
+    pub fn compare(&self, other: &Data) -> std::result::Result<Option<Ordering>, String> {
+        let ordering = match (self, other) {
+            (Data::Integer(l), Data::Real(r)) => (*l as f64).partial_cmp(r),
+            (Data::Real(l), Data::Integer(r)) => l.partial_cmp(&(*r as f64)),
+            #[cfg(feature = "bignum")]
+            (Data::Integer(l), Data::BigInt(r)) => BigInt::from(*l).partial_cmp(r),
+            #[cfg(feature = "bignum")]
+            (Data::BigInt(l), Data::Integer(r)) => l.partial_cmp(&BigInt::from(*r)),
+            #[cfg(feature = "bignum")]
+            (Data::BigInt(_), Data::BigInt(_)) => self.partial_cmp(other),
+            (Data::Real(_),    Data::Real(_))
+            | (Data::Integer(_), Data::Integer(_))
+            | (Data::String(_),  Data::String(_)) => self.partial_cmp(other),
+            _ => return Err("Comparison between unsupported datatypes".to_string()),
+        };
+
+        return Ok(ordering);
+    }
+
+    /// Compares two reals, treating them as equal
+    /// if they are within `epsilon` of each other.
+    /// NaN is never approximately equal to anything, itself included,
+    /// and an infinity is only approximately equal to an infinity of the same sign.
+    /// Values that aren't both reals are compared exactly.
+    pub fn approx_eq(&self, other: &Data, epsilon: f64) -> bool {
+        match (self, other) {
+            (Data::Real(l), Data::Real(r)) => l == r
+                || (l.is_finite() && r.is_finite() && (l - r).abs() <= epsilon),
+            _ => self == other,
+        }
+    }
+
+    /// Returns the value of a boolean.
+    /// Only booleans are truthy or falsy -
+    /// there's no implicit conversion from, say, `0` or `()`,
+    /// so any other value is an error.
+    /// The traceback has no spans; the VM adds them with `Trace::add_context`.
+    pub fn as_bool(&self) -> std::result::Result<bool, Trace> {
+        match self {
+            Data::Boolean(b) => Ok(*b),
+            other => Err(Trace::error(
+                "Type",
+                &format!("Expected a boolean, found {}", other),
+                vec![],
+            )),
+        }
+    }
+
+    /// Logically negates a boolean, see `Data::as_bool`.
+    #[allow(clippy::should_implement_trait)]
+    pub fn not(self) -> std::result::Result<Data, Trace> {
+        Ok(Data::Boolean(!self.as_bool()?))
+    }
+
+    /// Converts a number to an integer.
+    /// Reals that aren't integral are truncated towards zero, like `Data::truncate`,
+    /// rather than being an error; use `floor`, `ceil`, or `round` to round otherwise.
+    /// Integers are returned as they are.
+    /// NaN, the infinities, and reals out of the range of an integer are errors,
+    /// as is anything that isn't a number.
+    pub fn to_int(&self) -> std::result::Result<Data, Trace> {
+        self.truncate()
+    }
+
+    /// Converts a number to a real.
+    /// Integers too large to be represented exactly are rounded to the nearest real.
+    pub fn to_real(&self) -> std::result::Result<Data, Trace> {
+        match self {
+            Data::Real(_)    => Ok(self.clone()),
+            Data::Integer(n) => Ok(Data::Real(*n as f64)),
+            // big integers too large for a real become an infinity
+            #[cfg(feature = "bignum")]
+            Data::BigInt(n)  => Ok(Data::Real(n.to_f64().unwrap_or(match n.sign() {
+                Sign::Minus => f64::NEG_INFINITY,
+                _           => f64::INFINITY,
+            }))),
+            other => Err(Data::not_a_number(other)),
+        }
+    }
+
+    /// Rounds a number down to an integer, see `Data::to_int`.
+    pub fn floor(&self) -> std::result::Result<Data, Trace> {
+        self.rounded(f64::floor)
+    }
+
+    /// Rounds a number up to an integer, see `Data::to_int`.
+    pub fn ceil(&self) -> std::result::Result<Data, Trace> {
+        self.rounded(f64::ceil)
+    }
+
+    /// Rounds a number to the nearest integer,
+    /// with halves rounded away from zero, see `Data::to_int`.
+    pub fn round(&self) -> std::result::Result<Data, Trace> {
+        self.rounded(f64::round)
+    }
+
+    /// Rounds a number towards zero to an integer, see `Data::to_int`.
+    pub fn truncate(&self) -> std::result::Result<Data, Trace> {
+        self.rounded(f64::trunc)
+    }
+
+    /// Rounds a real to an integer with `op`, leaving integers as they are.
+    fn rounded(&self, op: fn(f64) -> f64) -> std::result::Result<Data, Trace> {
+        match self {
+            Data::Integer(_) => Ok(self.clone()),
+            #[cfg(feature = "bignum")]
+            Data::BigInt(_)  => Ok(self.clone()),
+            Data::Real(r) if r.is_nan() => Err(Trace::error(
+                "Value", "Can not convert NaN to an integer", vec![],
+            )),
+            // `i64::MAX` isn't a real, so it's rounded up to 2^63
+            Data::Real(r) => match op(*r) {
+                n if n >= i64::MIN as f64 && n < i64::MAX as f64 => Ok(Data::Integer(n as i64)),
+                _ => Err(Trace::error(
+                    "Value", &format!("The real {} is out of the range of an integer", self), vec![],
+                )),
+            },
+            other => Err(Data::not_a_number(other)),
+        }
+    }
+
+    fn not_a_number(data: &Data) -> Trace {
+        Trace::error("Type", &format!("Expected a number, found {}", data), vec![])
+    }
+
+    /// Formats a real, always with a `.` as the decimal separator,
+    /// as Rust's formatting doesn't depend on the system locale.
+    /// The shortest form that reads back as the same real is used,
+    /// which always has a decimal point or an exponent,
+    /// so reals are never mistaken for integers when lexed again.
+    /// If `precise`, all 17 significant digits are shown instead,
+    /// i.e. `0.1` is `1.0000000000000001e-1`, which is handy when debugging.
+    pub fn format_real(f: &mut Formatter<'_>, n: f64, precise: bool) -> Result {
+        if precise && n.is_finite() {
+            write!(f, "{:.16e}", n)
+        } else {
+            write!(f, "{:?}", n)
+        }
+    }
+}
+
+impl PartialOrd for Data {
+    /// Orders reals, integers, and strings (lexicographically)
+    /// against values of the same type.
+    /// Values of different types, like an integer and a real, are unordered,
+    /// as they are never equal; see `Data::compare` to order those.
+    /// Other values are unordered unless they're equal, to agree with `PartialEq`,
+    /// so a NaN is unordered, even against itself.
+    fn partial_cmp(&self, other: &Data) -> Option<Ordering> {
+        match (self, other) {
+            (Data::Real(l),    Data::Real(r))    => l.partial_cmp(r),
+            (Data::Integer(l), Data::Integer(r)) => l.partial_cmp(r),
+            #[cfg(feature = "bignum")]
+            (Data::BigInt(l),  Data::BigInt(r))  => l.partial_cmp(r),
+            // interned strings share an allocation, so needn't be compared
+            (Data::String(l),  Data::String(r)) if Rc::ptr_eq(l, r) => Some(Ordering::Equal),
+            (Data::String(l),  Data::String(r))  => l.partial_cmp(r),
+            _ if self == other => Some(Ordering::Equal),
+            _ => None,
+        }
+    }
+}
+
+/// Serializes `Data` like a derived implementation would,
+/// i.e. as `{ "Variant": contents }`.
+/// Heaped data is serialized as the data on the heap,
+/// and functions are serialized by name only, as they are opaque.
+#[cfg(feature = "serde")]
+impl Serialize for Data {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        match self {
+            Data::Heaped(h)   => h.borrow().serialize(serializer),
+            Data::NotInit     => serializer.serialize_unit_variant("Data", 1, "NotInit"),
+            Data::Real(n)     => serializer.serialize_newtype_variant("Data", 2, "Real", n),
+            Data::Integer(n)  => serializer.serialize_newtype_variant("Data", 3, "Integer", n),
+            #[cfg(feature = "bignum")]
+            Data::BigInt(n)   => serializer.serialize_newtype_variant("Data", 4, "BigInt", &n.to_string()),
+            Data::Boolean(b)  => serializer.serialize_newtype_variant("Data", 5, "Boolean", b),
+            Data::String(s)   => serializer.serialize_newtype_variant("Data", 6, "String", &**s),
+            Data::Lambda(_)   => serializer.serialize_unit_variant("Data", 7, "Lambda"),
+            Data::Closure(_)  => serializer.serialize_unit_variant("Data", 8, "Closure"),
+            Data::NativeFunction(_) => serializer.serialize_unit_variant("Data", 9, "NativeFunction"),
+            Data::Kind(n)     => serializer.serialize_newtype_variant("Data", 10, "Kind", n),
+            Data::Label(n, v) => serializer.serialize_newtype_variant("Data", 11, "Label", &(n, v)),
+            Data::Unit        => serializer.serialize_unit_variant("Data", 12, "Unit"),
+            Data::Tuple(t)    => serializer.serialize_newtype_variant("Data", 13, "Tuple", t),
+        }
+    }
+}
+
+/// Builds a tuple from its items, for passing aggregates from Rust.
+/// No items is `Data::Unit`, the empty tuple.
+impl From<Vec<Data>> for Data {
+    fn from(items: Vec<Data>) -> Data {
+        if items.is_empty() { Data::Unit } else { Data::Tuple(items) }
+    }
+}
+
+/// The error for data that was expected to be a tuple, but isn't one.
+pub(crate) fn not_a_tuple(data: &Data) -> Trace {
+    return Trace::error(
+        "Pattern Matching",
+        &format!("The data '{}' is not a tuple", data),
+        vec![],
+    );
+}
+
+/// The error for a tuple of the wrong length,
+/// where one of length `expected` was needed.
+pub(crate) fn wrong_tuple_length(items: Vec<Data>, expected: usize) -> Trace {
+    let length = items.len();
+    return Trace::error(
+        "Pattern Matching",
+        &format!(
+            "The tuple '{}' is of length {}, but a tuple of length {} was expected",
+            Data::Tuple(items), length, expected,
+        ),
+        vec![],
+    );
+}
+
+/// Takes the items out of a tuple, for reading aggregates from Rust.
+/// `Data::Unit` has no items, and heaped tuples are cloned out of the heap.
+/// Returns an error if the `Data` is not a tuple.
+impl TryFrom<Data> for Vec<Data> {
+    type Error = Trace;
+
+    fn try_from(data: Data) -> std::result::Result<Vec<Data>, Trace> {
+        match data {
+            Data::Tuple(items) => Ok(items),
+            Data::Unit         => Ok(vec![]),
+            Data::Heaped(h)    => Vec::try_from(h.borrow().clone()),
+            other => Err(not_a_tuple(&other)),
+        }
+    }
+}
+
+/// Takes the items out of a tuple of exactly `n` items.
+fn tuple_items(data: Data, n: usize) -> std::result::Result<Vec<Data>, Trace> {
+    let items = Vec::try_from(data)?;
+    if items.len() != n {
+        return Err(wrong_tuple_length(items, n));
+    }
+
+    return Ok(items);
+}
+
+impl From<(Data, Data)> for Data {
+    fn from((a, b): (Data, Data)) -> Data {
+        Data::Tuple(vec![a, b])
+    }
+}
+
+impl From<(Data, Data, Data)> for Data {
+    fn from((a, b, c): (Data, Data, Data)) -> Data {
+        Data::Tuple(vec![a, b, c])
+    }
+}
+
+/// Destructures a pair, returning an error if the `Data` isn't one.
+impl TryFrom<Data> for (Data, Data) {
+    type Error = Trace;
+
+    fn try_from(data: Data) -> std::result::Result<(Data, Data), Trace> {
+        let mut items = tuple_items(data, 2)?.into_iter();
+        return Ok((items.next().unwrap(), items.next().unwrap()));
+    }
+}
+
+/// Destructures a triple, returning an error if the `Data` isn't one.
+impl TryFrom<Data> for (Data, Data, Data) {
+    type Error = Trace;
+
+    fn try_from(data: Data) -> std::result::Result<(Data, Data, Data), Trace> {
+        let mut items = tuple_items(data, 3)?.into_iter();
+        return Ok((items.next().unwrap(), items.next().unwrap(), items.next().unwrap()));
+    }
+}
+
+/// A wrapper around `Data` that can be hashed, e.g. for use as a map key.
+/// Only strings, integers, booleans, unit,
+/// and tuples of the above are hashable.
+/// Reals can't be sensible keys (`NaN != NaN`),
+/// nor can functions or mutable values on the heap.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HashableData(Data);
+
+impl HashableData {
+    /// Wraps some `Data`, returning an error if it can not be hashed.
+    pub fn new(data: Data) -> std::result::Result<HashableData, String> {
+        if !HashableData::is_hashable(&data) {
+            return Err(format!("The data '{:?}' can not be used as a key", data));
+        }
+
+        Ok(HashableData(data))
+    }
+
+    /// Returns whether some `Data` can be hashed.
+    pub fn is_hashable(data: &Data) -> bool {
+        match data {
+            Data::String(_)
+            | Data::Integer(_)
+            | Data::Boolean(_)
+            | Data::Unit => true,
+            #[cfg(feature = "bignum")]
+            Data::BigInt(_) => true,
+            Data::Tuple(t) => t.iter().all(HashableData::is_hashable),
+            _ => false,
+        }
+    }
+
+    /// Returns the wrapped `Data`.
+    pub fn data(&self) -> &Data {
+        &self.0
+    }
+
+    /// Unwraps the `Data`.
+    pub fn into_data(self) -> Data {
+        self.0
+    }
+}
+
+/// Hashes the variant, then its contents.
+/// Note that only hashable variants are ever wrapped.
+fn hash_data<H: Hasher>(data: &Data, state: &mut H) {
+    mem::discriminant(data).hash(state);
+
+    match data {
+        Data::String(s)  => s.hash(state),
+        Data::Integer(n) => n.hash(state),
+        Data::Boolean(b) => b.hash(state),
+        Data::Unit       => (),
+        #[cfg(feature = "bignum")]
+        Data::BigInt(n)  => n.hash(state),
+        Data::Tuple(t)   => {
+            t.len().hash(state);
+            for item in t { hash_data(item, state); }
+        },
+        _ => unreachable!("Data is not hashable"),
+    }
+}
+
+impl Hash for HashableData {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        hash_data(&self.0, state);
+    }
 }
 
 // TODO: manually implement the equality trait
@@ -66,24 +510,36 @@ impl Eq for Data {}
 
 impl Display for Data {
     /// Displays some Passerine Data in a pretty manner, as if it were printed to console.
+    /// The alternate flag, i.e. `{:#}`, shows reals precisely, see `Data::format_real`,
+    /// including those in labels and tuples.
     fn fmt(&self, f: &mut Formatter<'_>) -> Result {
         match self {
             Data::Heaped(_)   => unreachable!("Can not display heaped data"),
             Data::NotInit     => unreachable!("found uninitialized data on top of stack"),
-            Data::Real(n)     => write!(f, "{}", n),
+            Data::Real(n)     => Data::format_real(f, *n, f.alternate()),
             Data::Integer(n)  => write!(f, "{}", n),
+            #[cfg(feature = "bignum")]
+            Data::BigInt(n)   => write!(f, "{}", n),
             Data::Boolean(b)  => write!(f, "{}", if *b { "true" } else { "false" }),
             Data::String(s)   => write!(f, "{}", s),
             Data::Lambda(_)   => unreachable!("Can not display naked functions"),
             Data::Closure(c)  => write!(f, "Function ~ {}", c.id),
+            Data::NativeFunction(_) => write!(f, "Native Function"),
             Data::Kind(_)     => unreachable!("Can not display naked labels"),
-            Data::Label(n, v) => write!(f, "{} {}", n, v),
+            // the contents are written to the same formatter, so share its flags
+            Data::Label(n, v) => {
+                write!(f, "{} ", n)?;
+                Display::fmt(v, f)
+            },
             Data::Unit        => write!(f, "()"),
-            Data::Tuple(t)    => write!(f, "({})", t.iter()
-                .map(|i| format!("{}", i))
-                .collect::<Vec<String>>()
-                .join(", ")
-            ),
+            Data::Tuple(t)    => {
+                write!(f, "(")?;
+                for (index, item) in t.iter().enumerate() {
+                    if index > 0 { write!(f, ", ")?; }
+                    Display::fmt(item, f)?;
+                }
+                write!(f, ")")
+            },
         }
     }
 }
@@ -99,10 +555,13 @@ impl Debug for Data {
             Data::NotInit     => write!(f, "NotInit"),
             Data::Real(n)     => write!(f, "Real({:?})", n),
             Data::Integer(n)  => write!(f, "Integer({:?})", n),
+            #[cfg(feature = "bignum")]
+            Data::BigInt(n)   => write!(f, "BigInt({:?})", n),
             Data::Boolean(b)  => write!(f, "Boolean({:?})", b),
             Data::String(s)   => write!(f, "String({:?})", s),
             Data::Lambda(_)   => write!(f, "Function(...)"),
             Data::Closure(c)  => write!(f, "Closure({})", c.id),
+            Data::NativeFunction(_) => write!(f, "NativeFunction(...)"),
             Data::Kind(n)     => write!(f, "Kind({})", n),
             Data::Label(n, v) => write!(f, "Label({}, {:?})", n, v),
             Data::Unit        => write!(f, "Unit"),
@@ -110,3 +569,347 @@ impl Debug for Data {
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::collections::{
+        HashMap,
+        hash_map::DefaultHasher,
+    };
+
+    #[test]
+    fn shared_strings() {
+        let banana: Rc<str> = "Banana".into();
+        let shared = (Data::String(Rc::clone(&banana)), Data::String(Rc::clone(&banana)));
+        assert_eq!(shared.0, shared.1);
+        assert_eq!(shared.0.partial_cmp(&shared.1), Some(Ordering::Equal));
+
+        // equal strings in different allocations are compared by contents
+        let distinct = Data::String("Banana".into());
+        assert!(matches!(&distinct, Data::String(s) if !Rc::ptr_eq(s, &banana)));
+        assert_eq!(shared.0, distinct);
+        assert_eq!(shared.0.partial_cmp(&distinct), Some(Ordering::Equal));
+        assert_eq!(shared.0.partial_cmp(&Data::String("Cherry".into())), Some(Ordering::Less));
+        assert_ne!(shared.0, Data::String("Bananas".into()));
+    }
+
+    #[test]
+    fn vec_conversion() {
+        let items = vec![Data::Integer(1), Data::String("a".into()), Data::Unit];
+        let tuple = Data::from(items.clone());
+        assert_eq!(tuple, Data::Tuple(items.clone()));
+        assert_eq!(Vec::try_from(tuple), Ok(items.clone()));
+
+        // the empty tuple is unit
+        assert_eq!(Data::from(vec![]), Data::Unit);
+        assert_eq!(Vec::try_from(Data::Unit), Ok(vec![]));
+
+        let heaped = Data::Heaped(Rc::new(RefCell::new(Data::from(items.clone()))));
+        assert_eq!(Vec::try_from(heaped), Ok(items));
+
+        assert_eq!(
+            Vec::try_from(Data::Integer(7)),
+            Err::<Vec<Data>, _>(not_a_tuple(&Data::Integer(7))),
+        );
+    }
+
+    #[test]
+    fn tuple_conversion() {
+        let pair = Data::from((Data::Integer(1), Data::Boolean(true)));
+        assert_eq!(pair, Data::Tuple(vec![Data::Integer(1), Data::Boolean(true)]));
+        assert_eq!(<(Data, Data)>::try_from(pair.clone()), Ok((Data::Integer(1), Data::Boolean(true))));
+
+        let triple = Data::from((Data::Integer(1), Data::Integer(2), Data::Integer(3)));
+        assert_eq!(
+            <(Data, Data, Data)>::try_from(triple.clone()),
+            Ok((Data::Integer(1), Data::Integer(2), Data::Integer(3))),
+        );
+
+        assert_eq!(
+            <(Data, Data)>::try_from(triple).unwrap_err().message(),
+            "The tuple '(1, 2, 3)' is of length 3, but a tuple of length 2 was expected",
+        );
+        assert!(<(Data, Data, Data)>::try_from(pair).is_err());
+        assert!(<(Data, Data)>::try_from(Data::Real(1.0)).is_err());
+    }
+
+    #[test]
+    fn aggregate_equality() {
+        let heaped = |d| Data::Heaped(Rc::new(RefCell::new(d)));
+        let pair = || Data::Tuple(vec![Data::Integer(1), Data::String("a".into())]);
+        let nested = |inner| Data::Tuple(vec![inner, heaped(pair()), Data::Unit]);
+
+        assert_eq!(pair(), pair());
+        assert_eq!(nested(pair()), nested(pair()));
+        assert_eq!(heaped(nested(pair())), heaped(nested(pair())));
+        assert_eq!(
+            Data::Label(Box::new("Some".to_string()), Box::new(pair())),
+            Data::Label(Box::new("Some".to_string()), Box::new(pair())),
+        );
+
+        // different items, lengths, and nesting
+        assert_ne!(pair(), Data::Tuple(vec![Data::Integer(1), Data::String("b".into())]));
+        assert_ne!(pair(), Data::Tuple(vec![Data::Integer(1)]));
+        assert_ne!(nested(pair()), nested(Data::Tuple(vec![pair()])));
+        assert_ne!(nested(pair()), nested(heaped(Data::Integer(2))));
+        assert_ne!(
+            Data::Label(Box::new("Some".to_string()), Box::new(pair())),
+            Data::Label(Box::new("None".to_string()), Box::new(pair())),
+        );
+
+        // reals are compared like reals, even when nested
+        let nan = Data::Tuple(vec![Data::Real(f64::NAN)]);
+        assert_ne!(nan, nan.clone());
+        assert_eq!(nested(Data::Real(0.0)), nested(Data::Real(-0.0)));
+    }
+
+    #[test]
+    fn booleans() {
+        assert_eq!(Data::Boolean(true).as_bool(),  Ok(true));
+        assert_eq!(Data::Boolean(false).as_bool(), Ok(false));
+        assert_eq!(Data::Boolean(true).not(),      Ok(Data::Boolean(false)));
+        assert_eq!(Data::Boolean(false).not(),     Ok(Data::Boolean(true)));
+    }
+
+    #[test]
+    fn only_booleans_are_truthy() {
+        for value in [
+            Data::Integer(0),
+            Data::Integer(1),
+            Data::Unit,
+            Data::String("true".into()),
+        ] {
+            let error = Trace::error(
+                "Type",
+                &format!("Expected a boolean, found {}", value),
+                vec![],
+            );
+            assert_eq!(value.as_bool(), Err(error));
+            assert!(value.not().is_err());
+        }
+    }
+
+    #[test]
+    fn real_formatting() {
+        // never formatted using the locale's decimal separator, like `1,5`
+        for (real, shown) in [(1.5, "1.5"), (-0.25, "-0.25"), (1e300, "1e300"), (3.0, "3.0")] {
+            assert_eq!(Data::Real(real).to_string(), shown);
+        }
+        assert_eq!(Data::Tuple(vec![Data::Real(0.5), Data::Integer(1)]).to_string(), "(0.5, 1)");
+
+        assert_eq!(format!("{:#}", Data::Real(0.1)), "1.0000000000000001e-1");
+        assert_eq!(format!("{:?}", Data::Real(2.5)), "Real(2.5)");
+        assert_eq!(format!("{:#}", Data::Real(f64::INFINITY)), "inf");
+        assert_eq!(format!("{:?}", Data::Real(f64::NAN)), "Real(NaN)");
+
+        // items are shown precisely too
+        let nested = Data::Tuple(vec![Data::Real(0.1), Data::Tuple(vec![Data::Integer(1), Data::Real(0.5)])]);
+        assert_eq!(format!("{:#}", nested), "(1.0000000000000001e-1, (1, 5.0000000000000000e-1))");
+        assert_eq!(nested.to_string(), "(0.1, (1, 0.5))");
+        let label = Data::Label(Box::new("Some".to_string()), Box::new(Data::Real(0.1)));
+        assert_eq!(format!("{:#}", label), "Some 1.0000000000000001e-1");
+    }
+
+    #[test]
+    fn precise_reals_round_trip() {
+        for real in [0.1, 1.0 / 3.0, f64::consts::PI, -1e-300, f64::MAX, f64::MIN_POSITIVE, 123456789.125] {
+            let precise = format!("{:#}", Data::Real(real));
+            assert_eq!(precise.parse::<f64>().unwrap(), real, "reading back {}", precise);
+            let shortest = Data::Real(real).to_string();
+            assert_eq!(shortest.parse::<f64>().unwrap(), real, "reading back {}", shortest);
+        }
+    }
+
+    #[test]
+    fn rounding() {
+        for (real, floor, ceil, round, truncate) in [
+            ( 2.5,  2,  3,  3,  2),
+            (-2.5, -3, -2, -3, -2),
+            ( 2.4,  2,  3,  2,  2),
+            (-0.5, -1,  0, -1,  0),
+            ( 7.0,  7,  7,  7,  7),
+        ] {
+            let real = Data::Real(real);
+            assert_eq!(real.floor(),    Ok(Data::Integer(floor)));
+            assert_eq!(real.ceil(),     Ok(Data::Integer(ceil)));
+            assert_eq!(real.round(),    Ok(Data::Integer(round)));
+            assert_eq!(real.truncate(), Ok(Data::Integer(truncate)));
+            assert_eq!(real.to_int(),   Ok(Data::Integer(truncate)));
+        }
+
+        // integers are already rounded
+        for op in [Data::floor, Data::ceil, Data::round, Data::truncate, Data::to_int] {
+            assert_eq!(op(&Data::Integer(-3)), Ok(Data::Integer(-3)));
+        }
+    }
+
+    #[test]
+    fn to_real() {
+        assert_eq!(Data::Integer(-3).to_real(), Ok(Data::Real(-3.0)));
+        assert_eq!(Data::Real(0.5).to_real(),   Ok(Data::Real(0.5)));
+        assert_eq!(Data::Integer(i64::MAX).to_real(), Ok(Data::Real(9.223372036854776e18)));
+        assert!(Data::String("1".into()).to_real().is_err());
+
+        #[cfg(feature = "bignum")]
+        {
+            let big = BigInt::from(10).pow(400);
+            assert_eq!(Data::BigInt(BigInt::from(1) << 70).to_real(), Ok(Data::Real(2f64.powi(70))));
+            assert_eq!(Data::BigInt(big.clone()).to_real(), Ok(Data::Real(f64::INFINITY)));
+            assert_eq!(Data::BigInt(-big).to_real(),        Ok(Data::Real(f64::NEG_INFINITY)));
+        }
+    }
+
+    #[test]
+    fn to_int_errors() {
+        let nan = Trace::error("Value", "Can not convert NaN to an integer", vec![]);
+        assert_eq!(Data::Real(f64::NAN).to_int(), Err(nan));
+
+        for real in [f64::INFINITY, f64::NEG_INFINITY, 1e19, -1e19, 9.223372036854776e18] {
+            let out_of_range = Trace::error(
+                "Value",
+                &format!("The real {} is out of the range of an integer", Data::Real(real)),
+                vec![],
+            );
+            assert_eq!(Data::Real(real).floor(), Err(out_of_range));
+        }
+        assert_eq!(Data::Real(-9.223372036854776e18).to_int(), Ok(Data::Integer(i64::MIN)));
+
+        let not_a_number = Trace::error("Type", "Expected a number, found true", vec![]);
+        assert_eq!(Data::Boolean(true).round(), Err(not_a_number));
+    }
+
+    fn hash(data: Data) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        HashableData::new(data).unwrap().hash(&mut hasher);
+        hasher.finish()
+    }
+
+    #[test]
+    fn equal_hashes() {
+        assert_eq!(
+            hash(Data::String("Banana".into())),
+            hash(Data::String("Banana".into())),
+        );
+        assert_eq!(
+            hash(Data::Tuple(vec![Data::Integer(1), Data::Unit])),
+            hash(Data::Tuple(vec![Data::Integer(1), Data::Unit])),
+        );
+    }
+
+    #[test]
+    // heaped data is never wrapped, so keys are never mutated
+    #[allow(clippy::mutable_key_type)]
+    fn map_key() {
+        let mut map = HashMap::new();
+        map.insert(HashableData::new(Data::Boolean(true)).unwrap(), 1);
+        assert_eq!(map.get(&HashableData::new(Data::Boolean(true)).unwrap()), Some(&1));
+        assert_eq!(map.get(&HashableData::new(Data::Boolean(false)).unwrap()), None);
+    }
+
+    #[test]
+    fn reject_unhashable() {
+        assert!(HashableData::new(Data::Real(f64::NAN)).is_err());
+        assert!(HashableData::new(Data::Real(1.0)).is_err());
+        assert!(HashableData::new(Data::Tuple(vec![Data::Integer(1), Data::Real(2.0)])).is_err());
+    }
+
+    #[test]
+    fn numeric_ordering() {
+        assert!(Data::Integer(1) < Data::Integer(2));
+        assert!(Data::Real(-0.5) < Data::Real(0.5));
+        assert_eq!(Data::Integer(3).compare(&Data::Integer(3)), Ok(Some(Ordering::Equal)));
+        assert_eq!(Data::Real(2.5).compare(&Data::Real(1.0)),   Ok(Some(Ordering::Greater)));
+    }
+
+    #[test]
+    fn string_ordering() {
+        let apple  = Data::String("apple".into());
+        let banana = Data::String("banana".into());
+        assert!(apple < banana);
+        assert_eq!(banana.compare(&apple), Ok(Some(Ordering::Greater)));
+    }
+
+    #[test]
+    fn cross_type_ordering() {
+        // the trait only orders values of the same type
+        assert_eq!(Data::Integer(1).partial_cmp(&Data::Real(1.0)), None);
+        // and only orders others as far as they're equal
+        let tuple = || Data::Tuple(vec![Data::Boolean(true), Data::Unit]);
+        assert_eq!(tuple().partial_cmp(&tuple()), Some(Ordering::Equal));
+        assert_eq!(tuple().partial_cmp(&Data::Tuple(vec![])), None);
+        let nan = Data::Tuple(vec![Data::Real(f64::NAN)]);
+        assert_eq!(nan.partial_cmp(&nan), None);
+
+        assert_eq!(Data::Integer(1).compare(&Data::Real(1.5)), Ok(Some(Ordering::Less)));
+        assert_eq!(Data::Real(2.0).compare(&Data::Integer(2)), Ok(Some(Ordering::Equal)));
+        assert!(Data::Real(1.0).compare(&Data::String("1".into())).is_err());
+        assert!(Data::Boolean(true).compare(&Data::Boolean(false)).is_err());
+        assert!(Data::Real(f64::NAN).compare(&Data::String("NaN".into())).is_err());
+    }
+
+    #[test]
+    fn approx_eq() {
+        assert!(Data::Real(0.1 + 0.2).approx_eq(&Data::Real(0.3), 1e-9));
+        assert!(Data::Real(1.0).approx_eq(&Data::Real(1.5), 0.5));
+        assert!(!Data::Real(1.0).approx_eq(&Data::Real(1.6), 0.5));
+        assert!(!Data::Real(0.1 + 0.2).approx_eq(&Data::Real(0.3), 0.0));
+
+        // non-reals are compared exactly
+        assert!(Data::Integer(1).approx_eq(&Data::Integer(1), 0.5));
+        assert!(!Data::Integer(1).approx_eq(&Data::Real(1.0), 0.5));
+        assert!(!Data::Integer(1).approx_eq(&Data::Integer(2), 5.0));
+    }
+
+    #[test]
+    fn approx_eq_special() {
+        let nan = Data::Real(f64::NAN);
+        let inf = Data::Real(f64::INFINITY);
+        let neg = Data::Real(f64::NEG_INFINITY);
+
+        assert!(!nan.approx_eq(&nan, f64::INFINITY));
+        assert!(!nan.approx_eq(&Data::Real(1.0), 1.0));
+        assert!(inf.approx_eq(&inf, 0.0));
+        assert!(!inf.approx_eq(&neg, f64::INFINITY));
+        assert!(!inf.approx_eq(&Data::Real(f64::MAX), f64::INFINITY));
+    }
+
+    #[test]
+    fn nan_ordering() {
+        let nan = Data::Real(f64::NAN);
+        assert_eq!(nan.compare(&Data::Real(1.0)), Ok(None));
+        assert_eq!(Data::Integer(1).compare(&nan), Ok(None));
+        assert_eq!(nan.compare(&nan), Ok(None));
+        let tests = [nan < nan, nan > nan, nan <= nan, nan >= nan];
+        assert!(tests.iter().all(|passed| !passed));
+    }
+
+    #[cfg(feature = "cycle-check")]
+    #[test]
+    fn cycles() {
+        use crate::common::lambda::Lambda;
+
+        let heaped = |d| Rc::new(RefCell::new(d));
+
+        // a heaped tuple containing itself
+        let cell = heaped(Data::Unit);
+        *cell.borrow_mut() = Data::Tuple(vec![Data::Integer(1), Data::Heaped(Rc::clone(&cell))]);
+        assert!(Data::Heaped(Rc::clone(&cell)).is_cyclic());
+        assert!(Data::Label(Box::new("Box".to_string()), Box::new(Data::Heaped(Rc::clone(&cell)))).is_cyclic());
+        *cell.borrow_mut() = Data::Unit;
+
+        // a recursive closure, capturing the variable it's assigned to
+        let variable = heaped(Data::Unit);
+        let mut closure = Closure::wrap(Lambda::empty());
+        closure.captures.push(Rc::clone(&variable));
+        *variable.borrow_mut() = Data::Closure(Box::new(closure));
+        assert!(Data::Heaped(Rc::clone(&variable)).is_cyclic());
+        *variable.borrow_mut() = Data::Unit;
+
+        // sharing isn't a cycle
+        let shared = heaped(Data::Integer(1));
+        let twice = Data::Tuple(vec![Data::Heaped(Rc::clone(&shared)), Data::Heaped(Rc::clone(&shared))]);
+        assert!(!twice.is_cyclic());
+        assert!(!Data::Heaped(heaped(twice)).is_cyclic());
+    }
+}
@@ -0,0 +1,103 @@
+use std::fmt;
+
+use crate::{
+    common::source::Source,
+    compiler::syntax::Syntax,
+    vm::trace::Trace,
+};
+
+/// Any error raised while running some source code,
+/// from lexing it all the way through to executing it on the `VM`.
+/// Compile errors are reported together, hence several `Syntax` errors.
+#[derive(Debug, PartialEq, Eq)]
+pub enum PasserineError {
+    Syntax(Vec<Syntax>),
+    Runtime(Trace),
+}
+
+impl PasserineError {
+    /// Renders the error like `Syntax` and `Trace` do,
+    /// separating several compile errors by a blank line.
+    /// See `Trace::report` for how runtime errors are rendered.
+    pub fn report(&self, source: &Source) -> String {
+        match self {
+            PasserineError::Syntax(errors) => errors.iter()
+                .map(|error| format!("{}\n", error))
+                .collect::<Vec<_>>()
+                .join("\n"),
+            PasserineError::Runtime(trace) => trace.report(source),
+        }
+    }
+}
+
+impl From<Syntax> for PasserineError {
+    fn from(error: Syntax) -> PasserineError {
+        PasserineError::Syntax(vec![error])
+    }
+}
+
+impl From<Vec<Syntax>> for PasserineError {
+    fn from(errors: Vec<Syntax>) -> PasserineError {
+        PasserineError::Syntax(errors)
+    }
+}
+
+impl From<Trace> for PasserineError {
+    fn from(trace: Trace) -> PasserineError {
+        PasserineError::Runtime(trace)
+    }
+}
+
+impl fmt::Display for PasserineError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PasserineError::Syntax(errors) => {
+                for (index, error) in errors.iter().enumerate() {
+                    if index > 0 { writeln!(f)?; }
+                    fmt::Display::fmt(error, f)?;
+                }
+                Ok(())
+            },
+            PasserineError::Runtime(trace) => fmt::Display::fmt(trace, f),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{run_str, common::data::Data};
+
+    #[test]
+    fn run_to_value() {
+        assert_eq!(run_str("x = 2\nx + 3"), Ok(Data::Integer(5)));
+    }
+
+    #[test]
+    fn syntax_error() {
+        let source = Source::source("x = )");
+        let error = run_str(&source.contents).unwrap_err();
+
+        match &error {
+            PasserineError::Syntax(errors) => assert_eq!(errors.len(), 1),
+            other => panic!("Expected a syntax error, found {:?}", other),
+        }
+        let report = error.report(&source);
+        assert!(report.starts_with("In ./source:1:5\n"), "{}", report);
+        assert!(report.contains("Syntax Error: "), "{}", report);
+    }
+
+    #[test]
+    fn runtime_error() {
+        let source = Source::source("x = 1\nx / 0");
+        let error = run_str(&source.contents).unwrap_err();
+
+        let trace = match &error {
+            PasserineError::Runtime(trace) => trace,
+            other => panic!("Expected a runtime error, found {:?}", other),
+        };
+        assert_eq!(error.report(&source), trace.report(&source));
+        assert!(error.report(&source).starts_with("Runtime "), "{}", error.report(&source));
+        assert_eq!(error.to_string(), trace.to_string());
+    }
+}
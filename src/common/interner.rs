@@ -0,0 +1,76 @@
+use std::{
+    collections::HashSet,
+    rc::Rc,
+};
+
+use crate::common::data::Data;
+
+/// Deduplicates strings, so identical strings share one allocation.
+/// Used by the compiler for string constants,
+/// so every use of the same literal, in any function,
+/// loads the same `Data::String` at runtime.
+/// Strings built at runtime, e.g. by concatenation, are not interned.
+#[derive(Debug, Clone, Default)]
+pub struct Interner {
+    strings: HashSet<Rc<str>>,
+}
+
+impl Interner {
+    /// Creates a new empty `Interner`.
+    pub fn new() -> Interner {
+        Interner { strings: HashSet::new() }
+    }
+
+    /// Returns the shared copy of a string,
+    /// adding it if it hasn't been seen before.
+    pub fn intern(&mut self, string: &str) -> Rc<str> {
+        if let Some(interned) = self.strings.get(string) {
+            return Rc::clone(interned);
+        }
+
+        let interned: Rc<str> = string.into();
+        self.strings.insert(Rc::clone(&interned));
+        return interned;
+    }
+
+    /// Interns `Data::String`s, leaving all other `Data` as is.
+    pub fn intern_data(&mut self, data: Data) -> Data {
+        match data {
+            Data::String(s) => Data::String(self.intern(&s)),
+            other => other,
+        }
+    }
+
+    /// The number of distinct strings interned.
+    pub fn len(&self) -> usize {
+        self.strings.len()
+    }
+
+    /// Whether no strings have been interned.
+    pub fn is_empty(&self) -> bool {
+        self.strings.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn shares_storage() {
+        let mut interner = Interner::new();
+        let a = interner.intern("banana");
+        let owned = String::from("banana");
+        let b = interner.intern(&owned);
+        let c = interner.intern("apple");
+
+        assert!(Rc::ptr_eq(&a, &b));
+        assert!(!Rc::ptr_eq(&a, &c));
+        assert_eq!(interner.len(), 2);
+
+        match (interner.intern_data(Data::String("apple".into())), interner.intern_data(Data::Integer(1))) {
+            (Data::String(d), Data::Integer(1)) => assert!(Rc::ptr_eq(&c, &d)),
+            other => panic!("{:?}", other),
+        }
+    }
+}
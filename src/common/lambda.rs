@@ -30,6 +30,10 @@ pub struct Lambda {
     pub code: Vec<u8>,
     /// Each usize indexes the bytecode op that begins each line.
     pub spans: Vec<(usize, Span)>,
+    /// Each usize indexes an FFI call,
+    /// paired with the spans of the operands passed to it,
+    /// so a failed call can point back at the offending operand.
+    pub operands: Vec<(usize, Vec<Span>)>,
     /// Number-stream indexed, used to load constants.
     pub constants: Vec<Data>,
     /// List of positions of locals in the scope where this lambda is defined,
@@ -47,6 +51,7 @@ impl Lambda {
             decls:     0,
             code:      vec![],
             spans:     vec![],
+            operands:  vec![],
             constants: vec![],
             captures:  vec![],
             ffi:       vec![],
@@ -70,6 +75,13 @@ impl Lambda {
         self.spans.push((self.code.len(), span.clone()))
     }
 
+    /// Emits the spans of the operands of an FFI call,
+    /// should be called before the `FFICall` opcode is emitted.
+    /// See index_operands as well.
+    pub fn emit_operands(&mut self, spans: Vec<Span>) {
+        self.operands.push((self.code.len(), spans))
+    }
+
     /// Removes the last emitted byte.
     pub fn demit(&mut self) {
         self.code.pop();
@@ -78,10 +90,11 @@ impl Lambda {
     /// Given some data, this function adds it to the constants table,
     /// and returns the data's index.
     /// The constants table is push only, so constants are identified by their index.
+    /// Repeated constants share an index, see `same_constant`.
     /// The resulting usize can be split up into a number byte stream,
     /// and be inserted into the bytecode.
     pub fn index_data(&mut self, data: Data) -> usize {
-        match self.constants.iter().position(|d| d == &data) {
+        match self.constants.iter().position(|d| same_constant(d, &data)) {
             Some(d) => d,
             None => {
                 self.constants.push(data);
@@ -102,6 +115,19 @@ impl Lambda {
         return best.clone();
     }
 
+    /// Look up the operand spans of the nearest FFI call at or before
+    /// the index of a specific bytecode op.
+    pub fn index_operands(&self, index: usize) -> Vec<Span> {
+        let mut best: &[Span] = &[];
+
+        for (i, spans) in self.operands.iter() {
+            if i > &index { break; }
+            best = spans;
+        }
+
+        return best.to_vec();
+    }
+
     /// Adds a ffi function to the ffi table,
     /// without checking for duplicates.
     /// The `Compiler` ensures that functions are valid
@@ -112,6 +138,19 @@ impl Lambda {
     }
 }
 
+/// Whether two constants can share a slot in the constants table.
+/// Reals are compared by their bits rather than by `==`,
+/// so `0.0` and `-0.0` are kept apart,
+/// and a `NaN` is shared with an identical `NaN`, though `NaN != NaN`.
+fn same_constant(a: &Data, b: &Data) -> bool {
+    match (a, b) {
+        (Data::Real(a), Data::Real(b)) => a.to_bits() == b.to_bits(),
+        (Data::Tuple(a), Data::Tuple(b)) => a.len() == b.len()
+            && a.iter().zip(b.iter()).all(|(a, b)| same_constant(a, b)),
+        (a, b) => a == b,
+    }
+}
+
 impl fmt::Display for Lambda {
     /// Dump a human-readable breakdown of a `Lambda`'s bytecode.
     /// Including constants, captures, and variables declared.
@@ -172,6 +211,11 @@ impl fmt::Display for Lambda {
                     index += consumed;
                     writeln!(f, "Load Cap\t{}\tIndexed upvalue on heap", upvalue_index)?;
                 },
+                Opcode::Global => {
+                    let (global_index, consumed) = build_number(&self.code[index..]);
+                    index += consumed;
+                    writeln!(f, "Global  \t{}\tIndexed global in prelude", global_index)?;
+                },
                 Opcode::Call => { writeln!(f, "Call    \t\tRun top function using next stack value")?; }
                 Opcode::Return => {
                     let (num_locals, consumed) = build_number(&self.code[index..]);
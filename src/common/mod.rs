@@ -4,12 +4,15 @@
 //! - Core data-strucutres.
 //! - Opcodes and number splicing.
 //! - Source code representation and span annotations.
+//! - A single error type for the whole pipeline.
 
 pub mod source;
 pub mod span;
 pub mod data;
+pub mod interner;
 pub mod number;
 pub mod opcode;
 pub mod lambda;
 pub mod closure;
 pub mod stamp;
+pub mod error;
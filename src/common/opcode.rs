@@ -24,6 +24,8 @@ pub enum Opcode {
     Load,
     /// Load a copy of a captured variable.
     LoadCap,
+    /// Load a copy of a global from the `Prelude`.
+    Global,
     /// Call a function.
     Call,
     /// Return from a function.
@@ -12,6 +12,7 @@ use std::{
 /// It's essentially a string with a path, the path serving as the source's name.
 /// Source files without a path point to `./source`,
 /// though this behaviour might change in the future.
+/// In-memory sources can be given a name of their own, see `Source::named`.
 #[derive(Debug, PartialEq, Eq)]
 pub struct Source {
     pub contents: String,
@@ -42,4 +43,11 @@ impl Source {
     pub fn source(source: &str) -> Rc<Source> {
         Source::new(&source.to_string(), &PathBuf::from("./source"))
     }
+
+    /// Build an in-memory `Source` with a name, e.g. `<repl>` or `<test>`,
+    /// which is shown in diagnostics in place of a path.
+    /// The name is stored as the `Source`'s path, but never read from disk.
+    pub fn named(name: &str, contents: &str) -> Rc<Source> {
+        Source::new(contents, Path::new(name))
+    }
 }
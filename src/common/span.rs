@@ -7,10 +7,14 @@ use std::{
     },
     usize,
     rc::Rc,
+    cmp::Ordering,
 };
 
 use crate::common::source::Source;
 
+#[cfg(feature = "serde")]
+use serde::{Serialize, Serializer, ser::SerializeStruct};
+
 /// A `Span` refers to a section of a source,
 /// much like a `&str`, but with a reference to a `Source` rather than a `String`.
 /// A `Span` is  meant to be paired with other datastructures,
@@ -53,6 +57,17 @@ impl Span {
         self.offset + self.length
     }
 
+    /// Checks if a byte offset falls within a `Span`.
+    /// Spans are half-open, so an offset on the boundary between two spans
+    /// is within the later one.
+    /// A zero-width `Span` contains only its own offset,
+    /// and an empty `Span` contains nothing.
+    pub fn contains(&self, offset: usize) -> bool {
+        if self.is_empty() { return false; }
+        return (self.offset <= offset && offset < self.end())
+            || (self.length == 0 && self.offset == offset);
+    }
+
     /// Compares two Spans.
     /// Returns true if this span starts the latest
     /// or is the longest in the case of a tie
@@ -87,6 +102,28 @@ impl Span {
         return Span::new(&a.source.as_ref().unwrap(), offset, length);
     }
 
+    /// Grows this `Span` rightward so that it ends where `other` ends.
+    /// Assumes `other` is from the same source, and does not start before this `Span`.
+    /// Does nothing if `other` is empty or already covered.
+    /// ```plain
+    /// hello this is cool
+    /// ^^^^^              | self
+    ///            ^^      | other
+    /// ^^^^^^^^^^^^^      | self, extended
+    /// ```
+    pub fn extend_to(&mut self, other: &Span) {
+        if other.is_empty() { return; }
+        if self.is_empty()  { *self = other.clone(); return; }
+
+        if self.source != other.source {
+            panic!("Can't extend a Span to a Span with a separate source")
+        }
+
+        if other.end() > self.end() {
+            self.length = other.end() - self.offset;
+        }
+    }
+
     /// Combines a set of `Span`s (think fold-left over `Span::combine`).
     pub fn join(mut spans: Vec<Span>) -> Span {
         let mut combined = match spans.pop() {
@@ -110,6 +147,21 @@ impl Span {
         self.source.as_ref().unwrap().contents[self.offset..(self.end())].to_string()
     }
 
+    /// Returns where the `Span` starts in its source,
+    /// as `path:line:column`, where lines and columns count from one.
+    /// Like `contents`, this panics if the `Span` is empty.
+    pub fn location(&self) -> String {
+        if self.is_empty() { panic!("An empty span does not have a location") }
+
+        let source = self.source.as_ref().unwrap();
+        let (line, col) = match Span::line_index(&source.contents, self.offset) {
+            Some(li) => li,
+            None     => unreachable!(),
+        };
+
+        return format!("{}:{}:{}", source.path.to_string_lossy(), line + 1, col + 1);
+    }
+
     // Used by fmt::Display:
 
     // NOTE: once split_inclusive is included in rust's stdlib,
@@ -135,6 +187,48 @@ impl Span {
     }
 }
 
+/// Serializes a `Span` as its offset and length in the source,
+/// or `null` if the `Span` is empty.
+#[cfg(feature = "serde")]
+impl Serialize for Span {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        if self.is_empty() { return serializer.serialize_none(); }
+
+        let mut span = serializer.serialize_struct("Span", 2)?;
+        span.serialize_field("offset", &self.offset)?;
+        span.serialize_field("length", &self.length)?;
+        span.end()
+    }
+}
+
+/// `Span`s are ordered by where they are in the source,
+/// i.e. by offset, then by end, so shorter spans come first.
+/// Spans in different sources are grouped by path,
+/// and empty spans, which have no position, come last.
+/// Spans are only equal in this order if they're equal by `Eq`.
+impl Ord for Span {
+    fn cmp(&self, other: &Span) -> Ordering {
+        match (&self.source, &other.source) {
+            // only to stay consistent with `Eq`
+            (None,    None)    => self.offset.cmp(&other.offset)
+                .then(self.length.cmp(&other.length)),
+            (None,    Some(_)) => Ordering::Greater,
+            (Some(_), None)    => Ordering::Less,
+            (Some(a), Some(b)) => a.path.cmp(&b.path)
+                .then(self.offset.cmp(&other.offset))
+                .then(self.end().cmp(&other.end()))
+                // only to stay consistent with `Eq`
+                .then_with(|| a.contents.cmp(&b.contents)),
+        }
+    }
+}
+
+impl PartialOrd for Span {
+    fn partial_cmp(&self, other: &Span) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
 impl Debug for Span {
     // TODO: use the field, etc. constructor.
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
@@ -179,18 +273,10 @@ impl Display for Span {
             None     => unreachable!(),
         };
 
-        let readable_start_line = (start_line + 1).to_string();
         let readable_end_line   = (end_line   + 1).to_string();
-        let readable_start_col  = (start_col  + 1).to_string();
         let padding = readable_end_line.len();
 
-        let location  = format!(
-            "In {}:{}:{}",
-            self.source.clone().unwrap()
-                .path.to_string_lossy(),
-            readable_start_line,
-            readable_start_col
-        );
+        let location  = format!("In {}", self.location());
 
         let separator = format!(" {} |", " ".repeat(padding));
 
@@ -241,6 +327,7 @@ impl Display for Span {
 /// ```
 /// or the like, can be spanned to indicate where it was parsed from (a `Spanned<Token>`).
 #[derive(Debug, Clone, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
 pub struct Spanned<T> {
     pub item: T,
     pub span: Span,
@@ -252,6 +339,16 @@ impl<T> Spanned<T> {
         Spanned { item, span }
     }
 
+    /// Returns a reference to the spanned item.
+    pub fn item(&self) -> &T {
+        &self.item
+    }
+
+    /// Returns a reference to the `Span` of the item.
+    pub fn span(&self) -> &Span {
+        &self.span
+    }
+
     /// Joins a Vector of spanned items into a single span.
     pub fn build(spanneds: &Vec<Spanned<T>>) -> Span {
         let spans = spanneds.iter()
@@ -270,6 +367,58 @@ impl<T> Spanned<T> {
 mod test {
     use super::*;
 
+    #[test]
+    fn contains() {
+        let source = Source::source("x = 7");
+        let span = Span::new(&source, 2, 1);
+        assert!(!span.contains(1));
+        assert!(span.contains(2));
+        // half-open, so the end belongs to whatever follows
+        assert!(!span.contains(3));
+
+        let point = Span::point(&source, 4);
+        assert!(point.contains(4));
+        assert!(!point.contains(5));
+        assert!(!Span::empty().contains(0));
+    }
+
+    #[test]
+    fn source_order() {
+        let source = Source::source("x = f 1 2\ny = 3");
+        let other = Source::new("z = 4", std::path::Path::new("./other"));
+
+        let sorted = vec![
+            Span::new(&other, 0, 1),
+            Span::point(&source, 0),
+            Span::new(&source, 0, 1),
+            Span::new(&source, 0, 9),
+            Span::new(&source, 4, 1),
+            Span::new(&source, 4, 5),
+            Span::new(&source, 6, 1),
+            Span::new(&source, 10, 5),
+            Span::empty(),
+        ];
+
+        let mut shuffled = vec![
+            sorted[5].clone(), sorted[8].clone(), sorted[1].clone(),
+            sorted[7].clone(), sorted[0].clone(), sorted[3].clone(),
+            sorted[6].clone(), sorted[2].clone(), sorted[4].clone(),
+        ];
+        shuffled.sort();
+        assert_eq!(shuffled, sorted);
+
+        assert_eq!(Span::empty().cmp(&Span::empty()), Ordering::Equal);
+        assert_eq!(Span::new(&source, 4, 1).cmp(&Span::new(&source, 4, 1)), Ordering::Equal);
+
+        // the order agrees with `Eq`, even for spans without a source
+        let sourceless = Span { offset: 3, ..Span::empty() };
+        assert_ne!(sourceless, Span::empty());
+        assert_ne!(sourceless.cmp(&Span::empty()), Ordering::Equal);
+        let edited = Source::source("x = g 1 2\ny = 3");
+        assert_ne!(Span::new(&source, 4, 1), Span::new(&edited, 4, 1));
+        assert_ne!(Span::new(&source, 4, 1).cmp(&Span::new(&edited, 4, 1)), Ordering::Equal);
+    }
+
     #[test]
     fn combination() {
         let source = Source::source("heck, that's awesome");
@@ -279,6 +428,28 @@ mod test {
         assert_eq!(Span::combine(&a, &b), Span::new(&source, 0, 13));
     }
 
+    #[test]
+    fn extend_to() {
+        let source = Source::source("heck, that's awesome");
+        let mut span = Span::new(&source, 0, 4);
+
+        span.extend_to(&Span::new(&source, 6, 6));
+        assert_eq!(span, Span::new(&source, 0, 12));
+        assert_eq!(span.contents(), "heck, that's");
+    }
+
+    #[test]
+    fn extend_to_covered() {
+        let source = Source::source("heck, that's awesome");
+        let mut span = Span::new(&source, 0, 12);
+
+        span.extend_to(&Span::new(&source, 6, 4));
+        assert_eq!(span, Span::new(&source, 0, 12));
+
+        span.extend_to(&Span::empty());
+        assert_eq!(span, Span::new(&source, 0, 12));
+    }
+
     #[test]
     fn span_and_contents() {
         let source = Source::source("hello, this is some text!");
@@ -292,6 +463,16 @@ mod test {
         assert_eq!(Span::join(spans).contents(), result.contents());
     }
 
+    #[test]
+    fn accessors() {
+        let source  = Source::source("banana");
+        let spanned = Spanned::new("banana", Span::new(&source, 0, 6));
+
+        assert_eq!(spanned.item(), &"banana");
+        assert_eq!(spanned.span(), &Span::new(&source, 0, 6));
+        assert_eq!(spanned.span().contents(), "banana");
+    }
+
     #[test]
     fn display() {
         let source = Source::source("hello\nbanana boat\nmagination\n");
@@ -1,19 +1,45 @@
-use std::convert::TryFrom;
+use std::{
+    convert::TryFrom,
+    collections::HashSet,
+};
 
 use crate::common::{
-    span::Spanned,
+    span::{Span, Spanned},
     data::Data,
 };
+use crate::compiler::{
+    token::Token,
+    operator::Assoc,
+};
+
+#[cfg(feature = "serde")]
+use serde::Serialize;
 
 /// Represents an argument pattern,
 /// i.e. the mini language used to match macros.
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
 pub enum ArgPattern {
     Keyword(String),
     Symbol(String),
     Group(Vec<Spanned<ArgPattern>>),
 }
 
+impl ArgPattern {
+    /// Returns a copy of this pattern with every nested span emptied.
+    pub fn without_spans(&self) -> ArgPattern {
+        let mut pattern = self.clone();
+        pattern.respan(&empty_span);
+        pattern
+    }
+
+    /// Applies `f` to every nested span in place,
+    /// e.g. to move the pattern after an edit to the source.
+    pub fn respan(&mut self, f: &dyn Fn(&mut Span)) {
+        if let ArgPattern::Group(g) = self { respan_all(g, f, ArgPattern::respan); }
+    }
+}
+
 impl TryFrom<AST> for ArgPattern {
     type Error = String;
 
@@ -35,20 +61,58 @@ impl TryFrom<AST> for ArgPattern {
     }
 }
 
+/// The path of a module reference,
+/// either a dotted module path, `use foo.bar`,
+/// or a file path, `import "path"`.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+pub enum ASTImport {
+    Module(Vec<String>),
+    File(String),
+}
+
+/// An attribute, `@name` or `@name(arguments)`,
+/// attaching metadata, like `@inline`, to the declaration that follows it.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+pub struct Attribute {
+    pub name:      String,
+    pub arguments: Vec<Spanned<AST>>,
+}
+
+impl Attribute {
+    /// Returns a copy of this `Attribute` with every nested span emptied.
+    pub fn without_spans(&self) -> Attribute {
+        let mut attribute = self.clone();
+        attribute.respan(&empty_span);
+        attribute
+    }
+
+    /// Applies `f` to every nested span in place,
+    /// e.g. to move the `Attribute` after an edit to the source.
+    pub fn respan(&mut self, f: &dyn Fn(&mut Span)) {
+        respan_all(&mut self.arguments, f, AST::respan);
+    }
+}
+
 /// Represents a CSTPattern during the AST phase of compilation.
 /// A pattern is like a very general type,
 /// because Passerine uses structural row-based typing.
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
 pub enum ASTPattern {
     Symbol(String),
     Data(Data),
     Chain(Vec<Spanned<ASTPattern>>), // used inside lambdas
     Label(String, Box<Spanned<ASTPattern>>),
     Tuple(Vec<Spanned<ASTPattern>>),
-    // Where {
-    //     pattern: Box<ASTPattern>,
-    //     expression: Box<AST>,
-    // },
+    /// A pattern with a type annotation, like `x: Number`,
+    /// e.g. on the parameters of a lambda, `(x: Number) -> x`.
+    /// Types are not checked yet, so the annotation is dropped when desugaring.
+    Typed(Box<Spanned<ASTPattern>>, Box<Spanned<ASTType>>),
+    /// A pattern with a guard, like `n if n > 0`,
+    /// on an arm of a function literal, `fn { n if n > 0 -> n, _ -> 0 }`.
+    Where(Box<Spanned<ASTPattern>>, Box<Spanned<AST>>),
 }
 
 impl ASTPattern {
@@ -56,6 +120,38 @@ impl ASTPattern {
     pub fn label(name: String, pattern: Spanned<ASTPattern>) -> ASTPattern {
         ASTPattern::Label(name, Box::new(pattern))
     }
+
+    /// Shortcut for creating an `ASTPattern::Typed` variant.
+    pub fn typed(pattern: Spanned<ASTPattern>, annotation: Spanned<ASTType>) -> ASTPattern {
+        ASTPattern::Typed(Box::new(pattern), Box::new(annotation))
+    }
+
+    /// Shortcut for creating an `ASTPattern::Where` variant.
+    pub fn guarded(pattern: Spanned<ASTPattern>, guard: Spanned<AST>) -> ASTPattern {
+        ASTPattern::Where(Box::new(pattern), Box::new(guard))
+    }
+
+    /// Returns a copy of this pattern with every nested span emptied.
+    pub fn without_spans(&self) -> ASTPattern {
+        let mut pattern = self.clone();
+        pattern.respan(&empty_span);
+        pattern
+    }
+
+    /// Applies `f` to every nested span in place,
+    /// e.g. to move the pattern after an edit to the source.
+    pub fn respan(&mut self, f: &dyn Fn(&mut Span)) {
+        let strip = ASTPattern::respan;
+        match self {
+            ASTPattern::Chain(c)
+            | ASTPattern::Tuple(c)  => respan_all(c, f, strip),
+            ASTPattern::Label(_, p) => respan_box(p, f, strip),
+            ASTPattern::Typed(p, t) => { respan_box(p, f, strip); respan_box(t, f, ASTType::respan); },
+            ASTPattern::Where(p, g) => { respan_box(p, f, strip); respan_box(g, f, AST::respan); },
+            ASTPattern::Symbol(_)
+            | ASTPattern::Data(_)   => (),
+        }
+    }
 }
 
 impl TryFrom<AST> for ASTPattern {
@@ -94,6 +190,52 @@ impl TryFrom<AST> for ASTPattern {
     }
 }
 
+/// Represents a type expression,
+/// the restricted grammar on the right side of a `type` definition.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+pub enum ASTType {
+    /// A named type, like `Integer`, or a type variable, like `a`.
+    Name(String),
+    /// A type applied to another, like `List a`.
+    Apply(Box<Spanned<ASTType>>, Box<Spanned<ASTType>>),
+    /// A tuple type, like `(A, B)`, or `()` if empty.
+    Tuple(Vec<Spanned<ASTType>>),
+    /// A function type, like `A -> B`.
+    Function(Box<Spanned<ASTType>>, Box<Spanned<ASTType>>),
+}
+
+impl ASTType {
+    /// Shortcut for creating an `ASTType::Apply` variant.
+    pub fn apply(function: Spanned<ASTType>, argument: Spanned<ASTType>) -> ASTType {
+        ASTType::Apply(Box::new(function), Box::new(argument))
+    }
+
+    /// Shortcut for creating an `ASTType::Function` variant.
+    pub fn function(argument: Spanned<ASTType>, result: Spanned<ASTType>) -> ASTType {
+        ASTType::Function(Box::new(argument), Box::new(result))
+    }
+
+    /// Returns a copy of this type with every nested span emptied.
+    pub fn without_spans(&self) -> ASTType {
+        let mut typ = self.clone();
+        typ.respan(&empty_span);
+        typ
+    }
+
+    /// Applies `f` to every nested span in place,
+    /// e.g. to move the type after an edit to the source.
+    pub fn respan(&mut self, f: &dyn Fn(&mut Span)) {
+        let strip = ASTType::respan;
+        match self {
+            ASTType::Name(_)        => (),
+            ASTType::Apply(a, b)
+            | ASTType::Function(a, b) => { respan_box(a, f, strip); respan_box(b, f, strip); },
+            ASTType::Tuple(t)       => respan_all(t, f, strip),
+        }
+    }
+}
+
 /// Represents an item in a sugared `AST`.
 /// Which is the direct result of parsing
 /// Each syntax-level construct has it's own `AST` variant.
@@ -101,18 +243,39 @@ impl TryFrom<AST> for ASTPattern {
 /// But not in the `CST`, which is the desugared syntax tree,
 /// and represents language-level constructs
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
 pub enum AST {
+    /// A variable, compared by name.
     Symbol(String),
     Data(Data),
+    /// A sequence of statements, any of which may be a bare expression.
+    /// The value of the block is that of its last statement,
+    /// the values of the others are discarded, see `AST::discarded`.
     Block(Vec<Spanned<AST>>),
+    /// A block with a label, `'name: { ... }`,
+    /// which can be broken out of early with `break 'name value`.
+    LabeledBlock {
+        label: String,
+        block: Box<Spanned<AST>>,
+    },
+    /// An early exit from the enclosing labeled block with a value,
+    /// `break 'name value`.
+    /// Labels are resolved later, so the label may not exist.
+    Break {
+        label:      String,
+        expression: Box<Spanned<AST>>,
+    },
     Form(Vec<Spanned<AST>>),
     Group(Box<Spanned<AST>>),
     CSTPattern(ASTPattern),
     ArgPattern(ArgPattern),
     Tuple(Vec<Spanned<AST>>),
+    /// A binding, optionally documented by
+    /// the doc comments (`--|`) directly preceding it.
     Assign {
         pattern:    Box<Spanned<ASTPattern>>,
         expression: Box<Spanned<AST>>,
+        doc:        Option<String>,
     },
     Lambda {
         pattern:    Box<Spanned<ASTPattern>>,
@@ -123,27 +286,75 @@ pub enum AST {
         function: Box<Spanned<AST>>,
     },
     Label(String, Box<Spanned<AST>>),
+    /// A postfix `?`, which propagates a failure early.
+    Try(Box<Spanned<AST>>),
+    /// A spread, `...xs`, which expands into multiple arguments or items.
+    /// Only allowed as a call argument or a tuple item.
+    Spread(Box<Spanned<AST>>),
     Syntax {
         arg_pat:    Box<Spanned<ArgPattern>>,
         expression: Box<Spanned<AST>>,
     },
+    /// An assertion, `assert condition`,
+    /// optionally followed by a message, `assert (condition) "message"`.
+    Assert {
+        expression: Box<Spanned<AST>>,
+        message:    Option<Box<Spanned<AST>>>,
+    },
+    /// A type definition, `type Name = <type>`.
+    /// Only allowed as a statement.
+    TypeDef {
+        name:      String,
+        type_expr: Box<Spanned<ASTType>>,
+    },
+    /// A module reference, `use foo.bar` or `import "path"`.
+    /// Only allowed as a statement.
+    Import(ASTImport),
+    /// A fixity declaration, like `infixl 6 (<>) join`, `prefix (~~) negate`, or `infixl 6 (+)`,
+    /// holding the declared precedence level, unless the operator is prefix,
+    /// and the name of the function the operator calls, if one is named.
+    /// Built-in operators call their own function, so never name one.
+    /// The parser collects these before parsing the rest, see `Parser::fixities`.
+    /// Only allowed as a statement.
+    Fixity {
+        assoc:    Assoc,
+        level:    Option<u8>,
+        operator: String,
+        function: Option<String>,
+    },
+    /// A quasiquote, `` `( ... ) ``, holding the tokens between the parens.
+    /// The tokens aren't parsed, apart from checking that each `Unquote`
+    /// is followed by something to unquote.
+    Quote(Vec<Spanned<Token>>),
+    /// A declaration, i.e. a binding, type definition, or macro,
+    /// preceded by one or more attributes.
+    /// Only allowed as a statement.
+    Attributed {
+        attributes: Vec<Spanned<Attribute>>,
+        node:       Box<Spanned<AST>>,
+    },
     // TODO: Currently quite basic
     // Use a symbol or the like?
     FFI {
         name:       String,
         expression: Box<Spanned<AST>>,
     },
+    /// Something that couldn't be parsed, in place of which an error was reported,
+    /// e.g. the missing right side of `x =`, see `parse_recover`.
+    Error,
 }
 
 impl AST {
     /// Shortcut for creating an `AST::Assign` variant.
     pub fn assign(
         pattern:    Spanned<ASTPattern>,
-        expression: Spanned<AST>
+        expression: Spanned<AST>,
+        doc:        Option<String>,
     ) -> AST {
         AST::Assign {
             pattern:    Box::new(pattern),
-            expression: Box::new(expression)
+            expression: Box::new(expression),
+            doc,
         }
     }
 
@@ -181,6 +392,57 @@ impl AST {
         }
     }
 
+    /// Shortcut for creating an `AST::Assert` variant.
+    pub fn assert(expression: Spanned<AST>, message: Option<Spanned<AST>>) -> AST {
+        AST::Assert {
+            expression: Box::new(expression),
+            message:    message.map(Box::new),
+        }
+    }
+
+    /// Shortcut for creating an `AST::TypeDef` variant.
+    pub fn type_def(name: &str, type_expr: Spanned<ASTType>) -> AST {
+        AST::TypeDef {
+            name:      name.to_string(),
+            type_expr: Box::new(type_expr),
+        }
+    }
+
+    /// Shortcut for creating an `AST::Import` variant.
+    pub fn import(path: ASTImport) -> AST {
+        AST::Import(path)
+    }
+
+    /// Shortcut for creating an `AST::Fixity` variant.
+    pub fn fixity(assoc: Assoc, level: Option<u8>, operator: &str, function: Option<&str>) -> AST {
+        AST::Fixity {
+            assoc,
+            level,
+            operator: operator.to_string(),
+            function: function.map(str::to_string),
+        }
+    }
+
+    /// Shortcut for creating an `AST::Quote` variant.
+    pub fn quote(tokens: Vec<Spanned<Token>>) -> AST {
+        AST::Quote(tokens)
+    }
+
+    /// Shortcut for creating an `AST::LabeledBlock` variant.
+    pub fn labeled_block(label: &str, block: Spanned<AST>) -> AST {
+        AST::LabeledBlock { label: label.to_string(), block: Box::new(block) }
+    }
+
+    /// Shortcut for creating an `AST::Break` variant.
+    pub fn break_(label: &str, expression: Spanned<AST>) -> AST {
+        AST::Break { label: label.to_string(), expression: Box::new(expression) }
+    }
+
+    /// Shortcut for creating an `AST::Attributed` variant.
+    pub fn attributed(attributes: Vec<Spanned<Attribute>>, node: Spanned<AST>) -> AST {
+        AST::Attributed { attributes, node: Box::new(node) }
+    }
+
     /// Shortcut for creating a `AST::Label` variant.
     pub fn label(name: &str, expression: Spanned<AST>) -> AST {
         AST::Label(name.to_string(), Box::new(expression))
@@ -194,8 +456,393 @@ impl AST {
         }
     }
 
+    /// Shortcut for creating an `AST::Try` variant.
+    pub fn try_(expression: Spanned<AST>) -> AST {
+        AST::Try(Box::new(expression))
+    }
+
+    /// Shortcut for creating an `AST::Spread` variant.
+    pub fn spread(expression: Spanned<AST>) -> AST {
+        AST::Spread(Box::new(expression))
+    }
+
     /// Shortcut for creating an `AST::Group` variant.
     pub fn group(expression: Spanned<AST>) -> AST {
         AST::Group(Box::new(expression))
     }
+
+    /// Returns the statements of a block whose values are discarded,
+    /// i.e. every statement but the last, which is the value of the block.
+    /// Statements like `print x` are run for their effects alone.
+    /// Anything other than a block discards nothing.
+    pub fn discarded(&self) -> &[Spanned<AST>] {
+        match self {
+            AST::Block(statements) if !statements.is_empty() => {
+                &statements[..statements.len() - 1]
+            },
+            _ => &[],
+        }
+    }
+
+    /// Returns the `AST` nodes directly nested within this one, in source order.
+    /// Patterns, types and quoted tokens are not `AST` nodes, so are skipped.
+    pub fn children(&self) -> Vec<&Spanned<AST>> {
+        match self {
+            AST::Block(items)
+            | AST::Form(items)
+            | AST::Tuple(items) => items.iter().collect(),
+            AST::Group(e)
+            | AST::Label(_, e)
+            | AST::Try(e)
+            | AST::Spread(e)
+            | AST::LabeledBlock { block: e, .. }
+            | AST::Break { expression: e, .. }
+            | AST::Assign { expression: e, .. }
+            | AST::Lambda { expression: e, .. }
+            | AST::Syntax { expression: e, .. }
+            | AST::FFI { expression: e, .. } => vec![e],
+            AST::Composition { argument, function } => vec![argument, function],
+            AST::Attributed { attributes, node } => attributes.iter()
+                .flat_map(|attribute| attribute.item.arguments.iter())
+                .chain(std::iter::once(&**node))
+                .collect(),
+            AST::Assert { expression, message } => {
+                let mut children = vec![&**expression];
+                if let Some(m) = message { children.push(m); }
+                children
+            },
+            AST::Symbol(_)
+            | AST::Data(_)
+            | AST::CSTPattern(_)
+            | AST::ArgPattern(_)
+            | AST::TypeDef { .. }
+            | AST::Import(_)
+            | AST::Quote(_)
+            | AST::Fixity { .. }
+            | AST::Error => vec![],
+        }
+    }
+
+    /// Rebuilds this `AST`, replacing each node directly nested within it,
+    /// i.e. each of its `children`, with the result of `f`.
+    /// Patterns, types and quoted tokens are kept as they are.
+    pub fn map_children(self, f: &mut impl FnMut(Spanned<AST>) -> Spanned<AST>) -> AST {
+        fn map_box(e: Spanned<AST>, f: &mut impl FnMut(Spanned<AST>) -> Spanned<AST>) -> Box<Spanned<AST>> {
+            Box::new(f(e))
+        }
+
+        match self {
+            AST::Block(b)    => AST::Block(b.into_iter().map(&mut *f).collect()),
+            AST::Form(items) => AST::Form(items.into_iter().map(&mut *f).collect()),
+            AST::Tuple(t)    => AST::Tuple(t.into_iter().map(&mut *f).collect()),
+            AST::Group(e)    => AST::Group(map_box(*e, f)),
+            AST::Label(n, e) => AST::Label(n, map_box(*e, f)),
+            AST::Try(e)      => AST::Try(map_box(*e, f)),
+            AST::Spread(e)   => AST::Spread(map_box(*e, f)),
+            AST::LabeledBlock { label, block } => AST::LabeledBlock { label, block: map_box(*block, f) },
+            AST::Break { label, expression } => AST::Break { label, expression: map_box(*expression, f) },
+            AST::Assign { pattern, expression, doc } => AST::Assign {
+                pattern,
+                expression: map_box(*expression, f),
+                doc,
+            },
+            AST::Lambda { pattern, expression } => AST::Lambda { pattern, expression: map_box(*expression, f) },
+            AST::Syntax { arg_pat, expression } => AST::Syntax { arg_pat, expression: map_box(*expression, f) },
+            AST::FFI { name, expression } => AST::FFI { name, expression: map_box(*expression, f) },
+            AST::Composition { argument, function } => AST::Composition {
+                argument: map_box(*argument, f),
+                function: map_box(*function, f),
+            },
+            AST::Assert { expression, message } => AST::Assert {
+                expression: map_box(*expression, f),
+                message:    message.map(|m| map_box(*m, f)),
+            },
+            AST::Attributed { attributes, node } => AST::Attributed {
+                attributes: attributes.into_iter().map(|Spanned { item, span }| Spanned::new(
+                    Attribute { name: item.name, arguments: item.arguments.into_iter().map(&mut *f).collect() },
+                    span,
+                )).collect(),
+                node: map_box(*node, f),
+            },
+            leaf @ AST::Symbol(_)
+            | leaf @ AST::Data(_)
+            | leaf @ AST::CSTPattern(_)
+            | leaf @ AST::ArgPattern(_)
+            | leaf @ AST::TypeDef { .. }
+            | leaf @ AST::Import(_)
+            | leaf @ AST::Quote(_)
+            | leaf @ AST::Fixity { .. }
+            | leaf @ AST::Error => leaf,
+        }
+    }
+
+    /// Returns a copy of this `AST` with every nested span emptied,
+    /// including those of patterns and types.
+    pub fn without_spans(&self) -> AST {
+        let mut ast = self.clone();
+        ast.respan(&empty_span);
+        ast
+    }
+
+    /// Applies `f` to every nested span in place,
+    /// including those of patterns and types,
+    /// e.g. to move the `AST` after an edit to the source.
+    pub fn respan(&mut self, f: &dyn Fn(&mut Span)) {
+        let strip = AST::respan;
+        match self {
+            AST::Block(items)
+            | AST::Form(items)
+            | AST::Tuple(items) => respan_all(items, f, strip),
+            AST::Group(e)
+            | AST::Label(_, e)
+            | AST::Try(e)
+            | AST::Spread(e)
+            | AST::LabeledBlock { block: e, .. }
+            | AST::Break { expression: e, .. }
+            | AST::FFI { expression: e, .. } => respan_box(e, f, strip),
+            AST::CSTPattern(p) => p.respan(f),
+            AST::ArgPattern(p) => p.respan(f),
+            AST::Assign { pattern, expression, .. }
+            | AST::Lambda { pattern, expression } => {
+                respan_box(pattern, f, ASTPattern::respan);
+                respan_box(expression, f, strip);
+            },
+            AST::Composition { argument, function } => {
+                respan_box(argument, f, strip);
+                respan_box(function, f, strip);
+            },
+            AST::Syntax { arg_pat, expression } => {
+                respan_box(arg_pat, f, ArgPattern::respan);
+                respan_box(expression, f, strip);
+            },
+            AST::Assert { expression, message } => {
+                respan_box(expression, f, strip);
+                if let Some(m) = message { respan_box(m, f, strip); }
+            },
+            AST::TypeDef { type_expr, .. } => respan_box(type_expr, f, ASTType::respan),
+            AST::Attributed { attributes, node } => {
+                respan_all(attributes, f, Attribute::respan);
+                respan_box(node, f, strip);
+            },
+            AST::Quote(tokens) => for token in tokens { f(&mut token.span); },
+            AST::Symbol(_)
+            | AST::Data(_)
+            | AST::Import(_)
+            | AST::Fixity { .. }
+            | AST::Error => (),
+        }
+    }
+}
+
+/// Compares the structure and contents of two `AST`s,
+/// ignoring where in the source each node was found.
+/// Useful for testing the parser without computing every span by hand.
+pub fn eq_ignoring_spans(a: &Spanned<AST>, b: &Spanned<AST>) -> bool {
+    a.item.without_spans() == b.item.without_spans()
+}
+
+/// Finds the nodes nested within an `AST` that have an empty span,
+/// i.e. nodes that lost track of where they came from,
+/// which makes for errors that can't point at anything.
+/// Only the root, the implicit block around a whole source, is expected to be empty,
+/// so it is never reported.
+/// Every node produced by `parse` should have a span,
+/// so this is useful for catching regressions in tests.
+pub fn find_empty_spans(ast: &Spanned<AST>) -> Vec<&Spanned<AST>> {
+    fn walk<'a>(ast: &'a Spanned<AST>, empty: &mut Vec<&'a Spanned<AST>>) {
+        if ast.span.is_empty() { empty.push(ast); }
+        for child in ast.item.children() { walk(child, empty); }
+    }
+
+    let mut empty = vec![];
+    for child in ast.item.children() { walk(child, &mut empty); }
+    return empty;
+}
+
+/// Returns the number of parameters a lambda takes,
+/// counting those of lambdas it directly returns,
+/// so `a b -> c` and `a -> b -> c` both take 2.
+/// Returns `None` if the `AST` is not a lambda.
+pub fn arity(lambda: &AST) -> Option<usize> {
+    match lambda {
+        AST::Lambda { pattern, expression } => {
+            let parameters = match &pattern.item {
+                ASTPattern::Chain(chain) => chain.len(),
+                _ => 1,
+            };
+            Some(parameters + arity(&expression.item).unwrap_or(0))
+        },
+        _ => None,
+    }
+}
+
+/// Finds the free variables of a lambda, in the order they're first used,
+/// i.e. the symbols it references that aren't bound by its parameters,
+/// by an assignment in its body, or by a lambda nested within it.
+/// These are the variables the lambda would capture from its enclosing scope.
+/// Assignments are hoisted to the top of the lambda they're in,
+/// so a symbol assigned anywhere in the body is bound throughout it.
+/// Anything other than a lambda is treated as the body of a lambda without parameters.
+/// Macro definitions are skipped, as their bodies are only templates.
+pub fn free_vars(lambda: &Spanned<AST>) -> Vec<String> {
+    let mut bound = HashSet::new();
+    assigned(&lambda.item, &mut bound);
+
+    let mut free = vec![];
+    walk_free(&lambda.item, &bound, &mut free);
+    return free;
+}
+
+/// Adds the free variables of an `AST` to `free`,
+/// given the variables `bound` in the enclosing scopes.
+fn walk_free(ast: &AST, bound: &HashSet<String>, free: &mut Vec<String>) {
+    match ast {
+        AST::Symbol(name) => if !bound.contains(name) && !free.contains(name) {
+            free.push(name.clone());
+        },
+        AST::Lambda { pattern, expression } => {
+            let mut scope = bound.clone();
+            pattern_names(&pattern.item, &mut scope);
+            if let ASTPattern::Where(_, guard) = &pattern.item {
+                walk_free(&guard.item, &scope, free);
+            }
+            assigned(&expression.item, &mut scope);
+            walk_free(&expression.item, &scope, free);
+        },
+        AST::Syntax { .. } => (),
+        other => for child in other.children() { walk_free(&child.item, bound, free); },
+    }
+}
+
+/// Adds the symbols assigned to within an `AST` to `names`,
+/// without looking inside of nested lambdas, which are scopes of their own.
+fn assigned(ast: &AST, names: &mut HashSet<String>) {
+    match ast {
+        AST::Assign { pattern, expression, .. } => {
+            pattern_names(&pattern.item, names);
+            assigned(&expression.item, names);
+        },
+        AST::Lambda { .. } | AST::Syntax { .. } => (),
+        other => for child in other.children() { assigned(&child.item, names); },
+    }
+}
+
+/// Adds the symbols a pattern binds to `names`.
+fn pattern_names(pattern: &ASTPattern, names: &mut HashSet<String>) {
+    match pattern {
+        ASTPattern::Symbol(name) => { names.insert(name.clone()); },
+        ASTPattern::Data(_) => (),
+        ASTPattern::Label(_, inner)
+        | ASTPattern::Typed(inner, _)
+        | ASTPattern::Where(inner, _) => pattern_names(&inner.item, names),
+        ASTPattern::Chain(patterns)
+        | ASTPattern::Tuple(patterns) => for p in patterns { pattern_names(&p.item, names); },
+    }
+}
+
+/// Rewrites an `AST` bottom-up, applying `f` to every node,
+/// after its children have been rewritten, and rebuilding the tree.
+/// Useful for passes like constant folding, which match on a node
+/// and replace it, leaving the rest of the tree as it is.
+pub fn transform(
+    ast: Spanned<AST>,
+    f: &mut impl FnMut(Spanned<AST>) -> Spanned<AST>,
+) -> Spanned<AST> {
+    let Spanned { item, span } = ast;
+    let item = item.map_children(&mut |child| transform(child, f));
+    return f(Spanned::new(item, span));
+}
+
+/// Applies a function to the spans nested within an item, like `AST::respan`.
+type Respan<T> = fn(&mut T, &dyn Fn(&mut Span));
+
+/// Used to strip the spans of an item, see `AST::without_spans`.
+fn empty_span(span: &mut Span) {
+    *span = Span::empty();
+}
+
+/// Applies a function to the span of a spanned item, and the spans nested within it.
+fn respan<T>(spanned: &mut Spanned<T>, f: &dyn Fn(&mut Span), nested: Respan<T>) {
+    f(&mut spanned.span);
+    nested(&mut spanned.item, f);
+}
+
+fn respan_box<T>(spanned: &mut Box<Spanned<T>>, f: &dyn Fn(&mut Span), nested: Respan<T>) {
+    respan(spanned, f, nested);
+}
+
+fn respan_all<T>(spanneds: &mut [Spanned<T>], f: &dyn Fn(&mut Span), nested: Respan<T>) {
+    for spanned in spanneds { respan(spanned, f, nested); }
+}
+
+// Builders:
+// These construct `Spanned<AST>`s for writing readable test expectations.
+// Leaves have empty spans, and nodes span their children,
+// so the built tree usually only matches a parsed tree ignoring spans.
+
+/// Builds a symbol, like `x`.
+pub fn symbol(name: &str) -> Spanned<AST> {
+    Spanned::new(AST::Symbol(name.to_string()), Span::empty())
+}
+
+/// Builds a literal, like `true` or `3.14`.
+pub fn data(data: Data) -> Spanned<AST> {
+    Spanned::new(AST::Data(data), Span::empty())
+}
+
+/// Builds a block of expressions, `{ a; b }`.
+pub fn block(expressions: Vec<Spanned<AST>>) -> Spanned<AST> {
+    let span = Spanned::build(&expressions);
+    Spanned::new(AST::Block(expressions), span)
+}
+
+/// Builds a parenthesized group, `(a)`.
+pub fn group(expression: Spanned<AST>) -> Spanned<AST> {
+    let span = expression.span.clone();
+    Spanned::new(AST::group(expression), span)
+}
+
+/// Builds a tuple, `a, b`.
+pub fn tuple(items: Vec<Spanned<AST>>) -> Spanned<AST> {
+    let span = Spanned::build(&items);
+    Spanned::new(AST::Tuple(items), span)
+}
+
+/// Builds a function call, `f x`.
+/// Like the parser, calls are flattened into one form,
+/// so `call(call(f, x), y)` is `f x y`.
+pub fn call(fun: Spanned<AST>, arg: Spanned<AST>) -> Spanned<AST> {
+    let span = Span::combine(&fun.span, &arg.span);
+    let form = match fun.item {
+        AST::Form(mut f) => { f.push(arg); f },
+        _                => vec![fun, arg],
+    };
+    Spanned::new(AST::Form(form), span)
+}
+
+/// Builds an assignment to a symbol, `name = expression`.
+pub fn assign(name: &str, expression: Spanned<AST>) -> Spanned<AST> {
+    let pattern = Spanned::new(ASTPattern::Symbol(name.to_string()), Span::empty());
+    let span = expression.span.clone();
+    Spanned::new(AST::assign(pattern, expression, None), span)
+}
+
+/// Builds a function of one symbol, `name -> expression`.
+pub fn lambda(name: &str, expression: Spanned<AST>) -> Spanned<AST> {
+    let pattern = Spanned::new(ASTPattern::Symbol(name.to_string()), Span::empty());
+    let span = expression.span.clone();
+    Spanned::new(AST::lambda(pattern, expression), span)
+}
+
+/// Builds a labeled expression, `Name expression`.
+pub fn label(name: &str, expression: Spanned<AST>) -> Spanned<AST> {
+    let span = expression.span.clone();
+    Spanned::new(AST::label(name, expression), span)
+}
+
+/// Builds a call to a built-in binary operator, like `add` for `a + b`.
+pub fn binop(name: &str, left: Spanned<AST>, right: Spanned<AST>) -> Spanned<AST> {
+    let arguments = tuple(vec![left, right]);
+    let span = arguments.span.clone();
+    Spanned::new(AST::ffi(name, arguments), span)
 }
@@ -36,6 +36,9 @@ impl TryFrom<ASTPattern> for CSTPattern {
                 ASTPattern::Label(k, a) => CSTPattern::Label(k, Box::new(a.map(CSTPattern::try_from)?)),
                 ASTPattern::Tuple(t)    => CSTPattern::Tuple(t.into_iter().map(|i| i.map(CSTPattern::try_from)).collect::<Result<Vec<_>, _>>()?),
                 ASTPattern::Chain(_)    => Err("Unexpected chained construct inside pattern")?,
+                // types aren't checked yet
+                ASTPattern::Typed(p, _) => p.map(CSTPattern::try_from)?.item,
+                ASTPattern::Where(_, _) => Err("Guards on patterns can be parsed, but are not supported yet")?,
             }
         )
     }
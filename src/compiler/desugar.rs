@@ -3,7 +3,10 @@ use std::{
     collections::HashSet,
 };
 
-use crate::common::span::{Span, Spanned};
+use crate::common::{
+    span::{Span, Spanned},
+    data::Data,
+};
 
 use crate::compiler::{
     rule::Rule,
@@ -41,15 +44,54 @@ impl Transformer {
             AST::Block(b) => self.block(b)?,
             AST::Form(f) => self.form(f)?,
             AST::Group(a) => self.walk(*a)?.item,
+            // TODO: early return on failure
+            AST::Try(_) => return Err(Syntax::error(
+                "The '?' operator is not yet supported",
+                &ast.span,
+            )),
+            AST::Spread(_) => return Err(Syntax::error(
+                "Spreads are not yet supported",
+                &ast.span,
+            )),
+            // TODO: resolve labels and exit early
+            AST::LabeledBlock { .. } => return Err(Syntax::error(
+                "Labeled blocks are not yet supported",
+                &ast.span,
+            )),
+            AST::Break { .. } => return Err(Syntax::error(
+                "Breaking out of a labeled block is not yet supported",
+                &ast.span,
+            )),
             AST::Tuple(t) => self.tuple(t)?,
             AST::CSTPattern(_) => return Err(Syntax::error("Unexpected pattern", &ast.span)),
             AST::ArgPattern(_)  => return Err(Syntax::error("Unexpected argument pattern", &ast.span)),
             AST::Label(n, e) => CST::Label(n, Box::new(self.walk(*e)?)),
             AST::Syntax { arg_pat, expression } => self.rule(*arg_pat, *expression)?,
-            AST::Assign { pattern, expression } => self.assign(*pattern, *expression)?,
+            AST::Assign { pattern, expression, .. } => self.assign(*pattern, *expression)?,
             AST::Lambda { pattern, expression } => self.lambda(*pattern, *expression)?,
             AST::Composition { argument, function } => self.composition(*argument, *function)?,
             AST::FFI { name, expression } => self.ffi(name, *expression)?,
+            AST::Assert { expression, message } => self.assert(*expression, message.map(|m| *m))?,
+            // attributes are not yet used by the compiler, so are dropped
+            AST::Attributed { node, .. } => self.walk(*node)?.item,
+            // types are not yet checked, so type definitions are dropped
+            AST::TypeDef { .. } => CST::Block(vec![]),
+            // the parser already applied the declared fixity
+            AST::Fixity { .. } => CST::Block(vec![]),
+            // TODO: resolve and load modules
+            AST::Import(_) => return Err(Syntax::error(
+                "Imports are not yet supported",
+                &ast.span,
+            )),
+            AST::Quote(_) => return Err(Syntax::error(
+                "Quotes can only be used by macros, which do not yet support them",
+                &ast.span,
+            )),
+            // only produced while recovering, alongside an error of its own
+            AST::Error => return Err(Syntax::error(
+                "This could not be parsed",
+                &ast.span,
+            )),
         };
 
         return Ok(Spanned::new(cst, ast.span))
@@ -200,6 +242,30 @@ impl Transformer {
         Ok(CST::FFI { name, expression: Box::new(self.walk(expression)?) })
     }
 
+    /// Desugars an assertion into a call to the `assert` FFI,
+    /// passing along the source of the condition, so it can be shown on failure.
+    pub fn assert(&mut self, condition: Spanned<AST>, message: Option<Spanned<AST>>) -> Result<CST, Syntax> {
+        let source = if condition.span.is_empty() {
+            Data::Unit
+        } else {
+            Data::String(condition.span.contents().into())
+        };
+
+        let message = match message {
+            Some(m) => self.walk(m)?,
+            None    => Spanned::new(CST::Data(Data::Unit), Span::empty()),
+        };
+
+        let arguments = vec![
+            self.walk(condition)?,
+            Spanned::new(CST::Data(source), Span::empty()),
+            message,
+        ];
+        let span = Spanned::build(&arguments);
+
+        Ok(CST::ffi("assert", Spanned::new(CST::Tuple(arguments), span)))
+    }
+
     /// Desugars a block,
     /// i.e. a series of expressions that takes on the value of the last one.
     pub fn block(&mut self, block: Vec<Spanned<AST>>) -> Result<CST, Syntax> {
@@ -0,0 +1,876 @@
+use crate::common::{
+    data::Data,
+    span::{Span, Spanned},
+};
+
+use crate::compiler::{
+    ast::{AST, ASTPattern, ArgPattern, ASTType, ASTImport, Attribute},
+    parse::{Prec, FUNCTION_ARGUMENT, PLACEHOLDER},
+    token::Token,
+    operator::{Assoc, Operator, Operators},
+    syntax::Syntax,
+};
+
+/// The number of spaces each level of a block is indented by.
+const INDENT: usize = 4;
+
+/// Formats an `AST` as canonical Passerine source,
+/// the basis of a `fmt` tool.
+/// Blocks are indented, operators are surrounded by spaces,
+/// and groups are kept as they were written.
+/// Parens are only added where precedence requires them,
+/// which never happens for an `AST` produced by `parse`,
+/// so parsing the result produces the same `AST`, ignoring spans
+/// (see `eq_ignoring_spans`).
+/// Quoted symbols and labels are written out from their spans,
+/// so this fails if one has lost its span, e.g. through `without_spans`.
+pub fn format(ast: &Spanned<AST>) -> Result<String, Syntax> {
+    return Formatter::run(ast, true);
+}
+
+/// Formats an `AST` like `format`, but drops the groups that were written,
+/// so there are parens only where precedence or associativity needs them,
+/// e.g. `(a * b) + (c)` is printed as `a * b + c`, but `(a + b) * c` is left as is.
+/// Groups don't change what an expression means,
+/// so the result parses to the same `AST`, apart from its groups.
+pub fn pretty(ast: &Spanned<AST>) -> Result<String, Syntax> {
+    return Formatter::run(ast, false);
+}
+
+/// Builds up formatted source, keeping track of the indentation of the current block.
+struct Formatter {
+    output: String,
+    indent: usize,
+    // whether groups are printed as they were written,
+    // rather than being replaced by parens where they're needed
+    groups: bool,
+    // the first part of the AST that couldn't be formatted
+    error: Option<Syntax>,
+    // the fixities of built-in operators declared in the source
+    fixities: Operators,
+}
+
+impl Formatter {
+    /// Formats a whole program, or a lone expression.
+    fn run(ast: &Spanned<AST>, groups: bool) -> Result<String, Syntax> {
+        let fixities = match &ast.item {
+            AST::Block(statements) => declared_fixities(statements),
+            _ => Operators::new(),
+        };
+        let mut formatter = Formatter { output: String::new(), indent: 0, groups, error: None, fixities };
+
+        // the root is a block without brackets
+        match &ast.item {
+            AST::Block(statements) => for statement in statements {
+                formatter.statement(&statement.item);
+                formatter.output.push('\n');
+            },
+            other => formatter.expression(other, Prec::None, Prec::None),
+        }
+
+        return match formatter.error {
+            Some(error) => Err(error),
+            None        => Ok(formatter.output),
+        };
+    }
+
+    fn push(&mut self, text: &str) {
+        self.output.push_str(text);
+    }
+
+    /// Pushes source that may not have been formatted,
+    /// remembering the first error.
+    fn push_source(&mut self, source: Result<String, Syntax>) {
+        match source {
+            Ok(text) => self.push(&text),
+            Err(error) => { self.error.get_or_insert(error); },
+        }
+    }
+
+    /// Starts a new line at the current indentation.
+    fn newline(&mut self) {
+        self.output.push('\n');
+        self.output.push_str(&" ".repeat(self.indent * INDENT));
+    }
+
+    /// Formats a line of a block, including any doc comments and attributes,
+    /// each on a line of their own.
+    fn statement(&mut self, ast: &AST) {
+        let (attributes, declaration) = match ast {
+            AST::Attributed { attributes, node } => (&attributes[..], &node.item),
+            other => (&[][..], other),
+        };
+
+        if let AST::Assign { doc: Some(doc), .. } = declaration {
+            for line in doc.lines() {
+                self.push("--| ");
+                self.push(line);
+                self.newline();
+            }
+        }
+
+        for attribute in attributes {
+            self.attribute(&attribute.item);
+            self.newline();
+        }
+
+        self.expression(declaration, Prec::None, Prec::None);
+    }
+
+    /// Formats an attribute, `@name` or `@name(arguments)`.
+    fn attribute(&mut self, attribute: &Attribute) {
+        self.push("@");
+        self.push(&attribute.name);
+        if attribute.arguments.is_empty() { return; }
+
+        // arguments are written like a tuple,
+        // so a lone argument that is a tuple needs parens of its own
+        self.push("(");
+        match &attribute.arguments[..] {
+            [argument] => self.expression(&argument.item, Prec::Lambda, Prec::None),
+            arguments  => self.tuple(arguments, Prec::None),
+        }
+        self.push(")");
+    }
+
+    /// Formats an expression in a position where it must bind
+    /// at least as tightly as `min`,
+    /// and is followed by an operator that binds as tightly as `follow`
+    /// (`Prec::None` if nothing follows it).
+    /// Left operands are parsed before the operator is seen,
+    /// so they have no `min` - they only need parens if they'd swallow the operator.
+    /// Adds parens if the expression wouldn't be parsed back as a whole otherwise.
+    fn expression(&mut self, ast: &AST, min: Prec, follow: Prec) {
+        if let (AST::Group(inner), false) = (ast, self.groups) {
+            // a trailing comma would continue onto whatever follows,
+            // so tuples of one item keep their parens
+            if !matches!(&inner.item, AST::Tuple(t) if t.len() == 1) {
+                return self.expression(&inner.item, min, follow);
+            }
+        }
+
+        if precedence(ast, &self.fixities) < min || follow >= reach(ast, &self.fixities) {
+            self.push("(");
+            self.item(ast, Prec::None);
+            self.push(")");
+        } else {
+            self.item(ast, follow);
+        }
+    }
+
+    /// Formats an expression without parens around it.
+    fn item(&mut self, ast: &AST, follow: Prec) {
+        match ast {
+            AST::Symbol(name) => self.push(name),
+            AST::Data(data)   => self.push_source(literal(data)),
+            AST::Block(block) => self.block(block),
+            AST::LabeledBlock { label, block } => {
+                self.push(&format!("'{}: ", label));
+                self.expression(&block.item, Prec::End, follow);
+            },
+            AST::Break { label, expression } => {
+                self.push(&format!("break '{} ", label));
+                self.expression(&expression.item, Prec::Call, follow);
+            },
+            AST::Form(form)   => self.form(form, follow),
+            // the parens are part of the function
+            AST::Group(inner) if placeholder_body(&inner.item).is_some() => self.item(&inner.item, follow),
+            AST::Group(inner) => {
+                self.push("(");
+                self.expression(&inner.item, Prec::None, Prec::None);
+                self.push(")");
+            },
+            AST::CSTPattern(pattern) => self.push_source(pattern_source(pattern)),
+            AST::ArgPattern(pattern) => self.push(&arg_pattern_source(pattern, false)),
+            AST::Tuple(tuple) => self.tuple(tuple, follow),
+
+            AST::Assign { pattern, expression, .. } => {
+                self.push_source(pattern_source(&pattern.item));
+                self.push(" = ");
+                self.expression(&expression.item, Prec::Assign, follow);
+            },
+            AST::Lambda { pattern, expression } if function_arms(&pattern.item, &expression.item).is_some() => {
+                let arms = function_arms(&pattern.item, &expression.item).unwrap();
+                self.push("fn { ");
+                for (i, arm) in arms.iter().enumerate() {
+                    if i > 0 { self.push(", "); }
+                    self.expression(&arm.item, Prec::Lambda, Prec::Pair);
+                }
+                self.push(" }");
+            },
+            AST::Lambda { .. } if placeholder_body(ast).is_some() => {
+                self.push("(");
+                self.expression(placeholder_body(ast).unwrap(), Prec::None, Prec::None);
+                self.push(")");
+            },
+            AST::Lambda { pattern, expression } => {
+                match &pattern.item {
+                    ASTPattern::Where(guarded, guard) => {
+                        self.push_source(pattern_source(&guarded.item));
+                        self.push(" if ");
+                        self.expression(&guard.item, Prec::Lambda.associate_left(), Prec::Lambda);
+                    },
+                    other => self.push_source(pattern_source(other)),
+                }
+                self.push(" -> ");
+                self.expression(&expression.item, Prec::Lambda, follow);
+            },
+            AST::Composition { argument, function } => {
+                self.expression(&argument.item, Prec::None, Prec::Compose);
+                self.push(" . ");
+                self.expression(&function.item, Prec::Call, follow);
+            },
+            AST::Label(name, body) => {
+                self.push(name);
+                self.push(" ");
+                self.expression(&body.item, Prec::End, follow);
+            },
+            AST::Try(inner) => {
+                self.expression(&inner.item, Prec::None, Prec::Try);
+                self.push("?");
+            },
+            AST::Spread(inner) => {
+                self.push("...");
+                self.expression(&inner.item, Prec::End, follow);
+            },
+            AST::Syntax { arg_pat, expression } => {
+                self.push("syntax ");
+                self.push(&arg_pattern_source(&arg_pat.item, true));
+                self.push(" ");
+                self.expression(&expression.item, Prec::End, follow);
+            },
+            AST::Assert { expression, message } => self.assert(
+                &expression.item,
+                message.as_ref().map(|m| &m.item),
+                follow,
+            ),
+            AST::TypeDef { name, type_expr } => {
+                self.push("type ");
+                self.push(name);
+                self.push(" = ");
+                self.push(&type_source(&type_expr.item, false));
+            },
+            AST::Import(ASTImport::Module(path)) => {
+                self.push("use ");
+                self.push(&path.join("."));
+            },
+            AST::Import(ASTImport::File(path)) => {
+                self.push("import ");
+                self.push(&string_literal(path));
+            },
+            AST::Fixity { assoc, level, operator, function } => {
+                self.push(match assoc {
+                    Assoc::Left   => "infixl ",
+                    Assoc::Right  => "infixr ",
+                    Assoc::Prefix => "prefix ",
+                });
+                if let Some(l) = level { self.push(&format!("{} ", l)); }
+                self.push(&format!("({})", operator));
+                if let Some(f) = function { self.push(&format!(" {}", f)); }
+            },
+            AST::Quote(tokens) => {
+                self.push("`(");
+                for (index, token) in tokens.iter().enumerate() {
+                    let after_open = index == 0 || matches!(
+                        tokens[index - 1].item,
+                        Token::OpenParen | Token::Unquote | Token::Quote,
+                    );
+                    if !after_open && token.item != Token::CloseParen { self.push(" "); }
+                    self.push_source(token_source(token));
+                }
+                self.push(")");
+            },
+            AST::FFI { name, expression } => self.ffi(name, &expression.item, follow),
+            AST::Attributed { .. } => self.statement(ast),
+            // what couldn't be parsed isn't known, so is left out
+            AST::Error => (),
+        }
+    }
+
+    /// Formats a block, one expression per line.
+    fn block(&mut self, block: &[Spanned<AST>]) {
+        if block.is_empty() { return self.push("{}"); }
+
+        self.push("{");
+        self.indent += 1;
+        for statement in block {
+            self.newline();
+            self.statement(&statement.item);
+        }
+        self.indent -= 1;
+        self.newline();
+        self.push("}");
+    }
+
+    /// Formats a function call, i.e. a series of expressions.
+    /// Only the last argument may extend to the right, e.g. `f ~x y`.
+    fn form(&mut self, form: &[Spanned<AST>], follow: Prec) {
+        for (index, item) in form.iter().enumerate() {
+            let min = if index == 0 { Prec::None } else { self.push(" "); Prec::End };
+            let last = index == form.len() - 1;
+            self.expression(&item.item, min, if last { follow } else { Prec::Call });
+        }
+    }
+
+    /// Formats a tuple, `a, b, c`, or `a,` if it has a single item.
+    fn tuple(&mut self, tuple: &[Spanned<AST>], follow: Prec) {
+        if tuple.is_empty() { return self.push("()"); }
+
+        for (index, item) in tuple.iter().enumerate() {
+            let min = if index == 0 { Prec::None } else { self.push(", "); Prec::Lambda };
+            let last = index == tuple.len() - 1 && tuple.len() > 1;
+            self.expression(&item.item, min, if last { follow } else { Prec::Pair });
+        }
+
+        if tuple.len() == 1 { self.push(","); }
+    }
+
+    /// Formats an assertion.
+    /// A message follows the condition like the last argument of a call,
+    /// so a condition followed by a message is an atom or a group, e.g. `assert (f x) "message"`.
+    fn assert(&mut self, condition: &AST, message: Option<&AST>, follow: Prec) {
+        self.push("assert ");
+
+        match (condition, message) {
+            (condition, Some(message)) => {
+                self.expression(condition, Prec::None, Prec::Call);
+                self.push(" ");
+                self.expression(message, Prec::End, follow);
+            },
+            // otherwise the string would be parsed as the message
+            (AST::Form(form), None) if form.len() == 2 && matches!(
+                form[1].item,
+                AST::Data(Data::String(_)),
+            ) => {
+                self.push("(");
+                self.form(form, Prec::None);
+                self.push(")");
+            },
+            (condition, None) => self.expression(condition, Prec::Pair.associate_left(), follow),
+        }
+    }
+
+    /// Formats a call to an FFI function,
+    /// using the built-in syntax that lowers to it where possible.
+    fn ffi(&mut self, name: &str, argument: &AST, follow: Prec) {
+        match (binop(name, &self.fixities), argument) {
+            (Some((glyph, prec, assoc)), AST::Tuple(operands)) if operands.len() == 2 => {
+                let min = if assoc == Assoc::Right { prec } else { prec.associate_left() };
+                self.expression(&operands[0].item, Prec::None, prec);
+                self.push(" ");
+                self.push(glyph);
+                self.push(" ");
+                self.expression(&operands[1].item, min, follow);
+            },
+            _ if name == "println" => {
+                self.push("print ");
+                self.expression(argument, Prec::Call, follow);
+            },
+            _ if name == "bit_not" => {
+                self.push("~");
+                self.expression(argument, Prec::Call, follow);
+            },
+            _ if name == "negate" && negates(&self.fixities) => {
+                self.push("-");
+                self.expression(argument, Prec::Call, follow);
+            },
+            _ => {
+                self.push("magic ");
+                self.push(&string_literal(name));
+                self.push(" ");
+                self.expression(argument, Prec::End, follow);
+            },
+        }
+    }
+}
+
+/// The fixities of the built-in operators declared in a program,
+/// like `infixr 6 (-)`, which change where parens are needed.
+/// Other operators are called as functions, so their fixities don't matter here.
+fn declared_fixities(statements: &[Spanned<AST>]) -> Operators {
+    let mut fixities = Operators::new();
+
+    for statement in statements {
+        if let AST::Fixity { assoc, level, operator, function: None } = &statement.item {
+            let prec = level.and_then(Prec::from_level).unwrap_or(Prec::Call);
+            // a parsed program never declares an operator twice
+            let _ = fixities.add(operator, Operator::fixity(prec, *assoc));
+        }
+    }
+
+    return fixities;
+}
+
+/// Whether a prefix minus has been declared, i.e. `prefix (-)`,
+/// so negation can be written as `-x`.
+fn negates(fixities: &Operators) -> bool {
+    matches!(fixities.get("-"), Some(Operator { assoc: Assoc::Prefix, .. }))
+}
+
+/// The glyph, precedence, and associativity of the built-in binary operator
+/// that lowers to the FFI function `name`, if any,
+/// taking its declared fixity into account.
+fn binop(name: &str, fixities: &Operators) -> Option<(&'static str, Prec, Assoc)> {
+    let (glyph, prec) = match name {
+        "add"           => ("+",  Prec::AddSub),
+        "sub"           => ("-",  Prec::AddSub),
+        "mul"           => ("*",  Prec::MulDiv),
+        "div"           => ("/",  Prec::MulDiv),
+        "remainder"     => ("%",  Prec::MulDiv),
+        "equal"         => ("==", Prec::Logic),
+        "less"          => ("<",  Prec::Logic),
+        "greater"       => (">",  Prec::Logic),
+        "less_equal"    => ("<=", Prec::Logic),
+        "greater_equal" => (">=", Prec::Logic),
+        "shift_left"    => ("<<", Prec::Shift),
+        "shift_right"   => (">>", Prec::Shift),
+        "bit_and"       => ("&",  Prec::BitAnd),
+        "bit_or"        => ("|",  Prec::BitOr),
+        _ => return None,
+    };
+
+    let operator = match fixities.get(glyph) {
+        Some(Operator { prec, assoc: Assoc::Right, .. }) => (glyph, *prec, Assoc::Right),
+        Some(Operator { prec, assoc: Assoc::Left, .. }) => (glyph, *prec, Assoc::Left),
+        _ => (glyph, prec, Assoc::Left),
+    };
+
+    return Some(operator);
+}
+
+/// The arms of a lambda parsed from a function literal, `fn { arms... }`,
+/// i.e. `arg -> match arg (arms...)`, see `Parser::function`.
+fn function_arms<'a>(pattern: &ASTPattern, expression: &'a AST) -> Option<&'a [Spanned<AST>]> {
+    match (pattern, expression) {
+        (ASTPattern::Symbol(argument), AST::Form(form)) if argument == FUNCTION_ARGUMENT => match &form[..] {
+            [
+                Spanned { item: AST::Symbol(m), .. },
+                Spanned { item: AST::Symbol(a), .. },
+                Spanned { item: AST::Tuple(arms), .. },
+            ] if m == "match" && a == FUNCTION_ARGUMENT => Some(arms),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// The body of a function made from parens containing a placeholder,
+/// i.e. `# + 1` in `(# + 1)`, which is parsed as `# -> # + 1`, see `Parser::group`.
+fn placeholder_body(ast: &AST) -> Option<&AST> {
+    match ast {
+        AST::Lambda { pattern, expression } if pattern.item == ASTPattern::Symbol(PLACEHOLDER.to_string()) => {
+            Some(&expression.item)
+        },
+        _ => None,
+    }
+}
+
+/// How tightly an expression binds as a whole,
+/// i.e. the precedence of its outermost operator.
+/// Expressions that start with a prefix, like literals or `print`, bind the tightest.
+fn precedence(ast: &AST, fixities: &Operators) -> Prec {
+    match ast {
+        AST::Assign { .. }      => Prec::Assign,
+        AST::Tuple(_)           => Prec::Pair,
+        AST::Lambda { pattern, expression }
+            if function_arms(&pattern.item, &expression.item).is_some() => Prec::End,
+        AST::Lambda { .. } if placeholder_body(ast).is_some() => Prec::End,
+        AST::Lambda { .. }      => Prec::Lambda,
+        AST::Try(_)             => Prec::Try,
+        AST::Composition { .. } => Prec::Compose,
+        AST::Form(_)            => Prec::Call,
+        AST::FFI { name, expression } => match (binop(name, fixities), &expression.item) {
+            (Some((_, prec, _)), AST::Tuple(t)) if t.len() == 2 => prec,
+            // `f -x` is a subtraction
+            _ if name == "negate" && negates(fixities) => Prec::Call,
+            _ => Prec::End,
+        },
+        _ => Prec::End,
+    }
+}
+
+/// The loosest operator an expression would swallow if the operator followed it,
+/// e.g. `a + b * c` is `a + (b * c)`, and `~f x` is `~(f x)`.
+/// Calls and tuples swallow more arguments and items,
+/// which would flatten them, e.g. `(f x) y` into `f x y`.
+fn reach(ast: &AST, fixities: &Operators) -> Prec {
+    match ast {
+        AST::Assign { .. }      => Prec::Assign,
+        AST::Tuple(_)           => Prec::Pair,
+        AST::Lambda { pattern, expression }
+            if function_arms(&pattern.item, &expression.item).is_some() => Prec::End,
+        AST::Lambda { .. } if placeholder_body(ast).is_some() => Prec::End,
+        AST::Lambda { .. }      => Prec::Lambda,
+        AST::Assert { .. }      => Prec::Pair.associate_left(),
+        AST::Break { .. }       => Prec::Call,
+        AST::Composition { .. } => Prec::Call,
+        AST::Form(_)            => Prec::Call,
+        AST::FFI { name, expression } => match (binop(name, fixities), &expression.item) {
+            (Some((_, prec, Assoc::Right)), AST::Tuple(t)) if t.len() == 2 => prec,
+            (Some((_, prec, _)), AST::Tuple(t)) if t.len() == 2 => prec.associate_left(),
+            _ if name == "println" || name == "bit_not" => Prec::Call,
+            _ if name == "negate" && negates(fixities) => Prec::Call,
+            _ => Prec::End,
+        },
+        _ => Prec::End,
+    }
+}
+
+/// Formats a literal so that it is lexed as the same `Data`.
+/// Reals too large to write out overflow to infinity, so infinity is written as one,
+/// but there's no way to write out NaN.
+fn literal(data: &Data) -> Result<String, Syntax> {
+    let literal = match data {
+        Data::String(s) => string_literal(s),
+        Data::Real(r) if r.is_nan() => return Err(Syntax::error(
+            "Can not format NaN, it has no literal",
+            &Span::empty(),
+        )),
+        Data::Real(r) if r.is_infinite() => {
+            (if *r > 0.0 { "1e400" } else { "-1e400" }).to_string()
+        },
+        other => other.to_string(),
+    };
+
+    return Ok(literal);
+}
+
+/// Quotes and escapes a string.
+fn string_literal(string: &str) -> String {
+    let mut literal = "\"".to_string();
+
+    for c in string.chars() {
+        match c {
+            '"'  => literal.push_str("\\\""),
+            '\\' => literal.push_str("\\\\"),
+            '\n' => literal.push_str("\\n"),
+            '\t' => literal.push_str("\\t"),
+            '\r' => literal.push_str("\\r"),
+            '\0' => literal.push_str("\\0"),
+            c    => literal.push(c),
+        }
+    }
+
+    literal.push('"');
+    return literal;
+}
+
+/// Formats a pattern.
+/// Parens around patterns don't show up in the `AST`,
+/// so tuples and nested chains are always parenthesized.
+fn pattern_source(pattern: &ASTPattern) -> Result<String, Syntax> {
+    let nested = |p: &Spanned<ASTPattern>| match p.item {
+        ASTPattern::Chain(_) => Ok(format!("({})", pattern_source(&p.item)?)),
+        ref other => pattern_source(other),
+    };
+
+    // a type extends as far as it can,
+    // so annotations are parenthesized unless they're tuple items
+    let typed = |p: &Spanned<ASTPattern>, t: &Spanned<ASTType>| {
+        Ok(format!("{}: {}", nested(p)?, type_source(&t.item, false)))
+    };
+    let item = |p: &Spanned<ASTPattern>| match &p.item {
+        ASTPattern::Typed(p, t) => typed(p, t),
+        _ => nested(p),
+    };
+
+    let source = match pattern {
+        ASTPattern::Symbol(name) => name.clone(),
+        ASTPattern::Data(data)   => literal(data)?,
+        ASTPattern::Chain(chain) => chain.iter().map(nested).collect::<Result<Vec<_>, _>>()?.join(" "),
+        ASTPattern::Label(name, body) => format!("{} {}", name, nested(body)?),
+        ASTPattern::Tuple(tuple) if tuple.len() == 1 => format!("({},)", item(&tuple[0])?),
+        ASTPattern::Tuple(tuple) => format!(
+            "({})",
+            tuple.iter().map(item).collect::<Result<Vec<_>, _>>()?.join(", "),
+        ),
+        ASTPattern::Typed(p, t) => format!("({})", typed(p, t)?),
+        ASTPattern::Where(p, g) => format!("{} if {}", pattern_source(&p.item)?, pretty(g)?),
+    };
+
+    return Ok(source);
+}
+
+/// Formats a macro's argument pattern.
+/// The outermost group isn't parenthesized.
+fn arg_pattern_source(pattern: &ArgPattern, outermost: bool) -> String {
+    match pattern {
+        ArgPattern::Keyword(name) => format!("'{}", name),
+        ArgPattern::Symbol(name)  => name.clone(),
+        ArgPattern::Group(group)  => {
+            let items = group.iter()
+                .map(|p| arg_pattern_source(&p.item, false))
+                .collect::<Vec<_>>()
+                .join(" ");
+            if outermost { items } else { format!("({})", items) }
+        },
+    }
+}
+
+/// Formats a type expression.
+/// `argument` is true if the type is applied to another type,
+/// in which case an application or function type needs parens.
+fn type_source(type_expr: &ASTType, argument: bool) -> String {
+    let source = match type_expr {
+        ASTType::Name(name) => return name.clone(),
+        ASTType::Tuple(items) => return match items.len() {
+            0 => "()".to_string(),
+            1 => format!("({},)", type_source(&items[0].item, false)),
+            _ => format!(
+                "({})",
+                items.iter()
+                    .map(|t| type_source(&t.item, false))
+                    .collect::<Vec<_>>()
+                    .join(", "),
+            ),
+        },
+        ASTType::Apply(function, arg) => format!(
+            "{} {}",
+            type_source_function(&function.item),
+            type_source(&arg.item, true),
+        ),
+        ASTType::Function(arg, result) => format!(
+            "{} -> {}",
+            type_source_function(&arg.item),
+            type_source(&result.item, false),
+        ),
+    };
+
+    if argument { format!("({})", source) } else { source }
+}
+
+/// Formats a type on the left of an application or function type,
+/// where only function types need parens.
+fn type_source_function(type_expr: &ASTType) -> String {
+    match type_expr {
+        ASTType::Function(_, _) => format!("({})", type_source(type_expr, false)),
+        other => type_source(other, false),
+    }
+}
+
+/// Formats a quoted token as it appeared in the source.
+/// Symbols and labels are defined by their span,
+/// so they can only be formatted if they have one.
+fn token_source(token: &Spanned<Token>) -> Result<String, Syntax> {
+    if !token.span.is_empty() { return Ok(token.span.contents()); }
+
+    let glyph = match &token.item {
+        Token::Integer(d)
+        | Token::Real(d)
+        | Token::String(d)
+        | Token::Boolean(d) => return literal(d),
+        Token::Keyword(k)   => return Ok(format!("'{}", k)),
+        Token::BlockLabel(l) => return Ok(format!("'{}:", l)),
+        Token::Op(o)        => return Ok(o.clone()),
+        Token::Doc(d)       => return Ok(format!("--| {}\n", d)),
+
+        Token::OpenBracket  => "{",
+        Token::CloseBracket => "}",
+        Token::OpenParen    => "(",
+        Token::CloseParen   => ")",
+        Token::Sep          => "\n",
+        Token::Pair         => ",",
+        Token::Spread       => "...",
+        Token::Syntax       => "syntax",
+        Token::Type         => "type",
+        Token::Use          => "use",
+        Token::Import       => "import",
+        Token::Assign       => "=",
+        Token::Lambda       => "->",
+        Token::Colon        => ":",
+        Token::Compose      => ".",
+        Token::Question     => "?",
+        Token::Print        => "print",
+        Token::Magic        => "magic",
+        Token::Assert       => "assert",
+        Token::Break        => "break",
+        Token::Fn           => "fn",
+        Token::Placeholder  => "#",
+        Token::Quote        => "`",
+        Token::Unquote      => "~",
+        Token::At           => "@",
+        Token::Unit         => "()",
+        Token::Add          => "+",
+        Token::Sub          => "-",
+        Token::Mul          => "*",
+        Token::Div          => "/",
+        Token::Rem          => "%",
+        Token::Equal        => "==",
+        Token::Less         => "<",
+        Token::Greater      => ">",
+        Token::LessEqual    => "<=",
+        Token::GreaterEqual => ">=",
+        Token::Shl          => "<<",
+        Token::Shr          => ">>",
+        Token::BitAnd       => "&",
+        Token::BitOr        => "|",
+        Token::BitNot       => "~",
+        Token::End          => "",
+        Token::Symbol
+        | Token::Label
+        | Token::Error => return Err(Syntax::error(
+            &format!("Can not format a quoted {} without its span", token.item),
+            &token.span,
+        )),
+    };
+
+    return Ok(glyph.to_string());
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::common::source::Source;
+    use crate::compiler::{
+        lex::lex,
+        parse::parse,
+        ast::{self, eq_ignoring_spans},
+    };
+
+    fn parse_source(source: &str) -> Spanned<AST> {
+        parse(lex(Source::source(source)).unwrap()).expect(source)
+    }
+
+    #[test]
+    fn round_trip() {
+        for source in &[
+            "x = 1\ny = 2.5\nz = \"a \\\"quoted\\\"\\n string\"",
+            "--| Adds one.\n--| Really.\nincr = x -> x + 1\nincr 2",
+            "a + b * c - d / e % f",
+            "(a + b) * c\na - (b - c)\n(a == b) == c",
+            "a | b & c << d\n~a + b\n~f x\nf ~x",
+            "x < y\nx >= y << 2\nx <= y\nx > y",
+            "f (g x) y\n(f x) . g\nx . f y . g\nf x . g",
+            "f = a b -> {\n    c = a + b\n    print c\n\n    c\n}",
+            "Some (x, y) = Some (1, 2)\nf (a, b) -> a\n(x -> x) y",
+            "a, b, c\n(a, (b, c))\n(a,)\nx = y = ()",
+            "x = Some 1\ny = True\nf Some x",
+            "f = fn { 0 -> \"zero\", n -> \"nonzero\" }\ng = fn { x -> x }\nfn { x -> x } 1",
+            "fn { n if n > 0 -> n, (a, b) if (x -> x) a -> b, _ -> 0 }",
+            "f = (x: Number) -> x\ng = (x, y: List a, z: A -> B) -> x\nh = (x: A) (y: (A, B),) -> x",
+            "x = 'outer: {\n    y = 'inner: { break 'outer f y }\n    break 'inner 2\n}",
+            "--| Fast.\n@inline\n@deprecated(\"use g\", 2)\nf = x -> x\n@wrap((1, 2))\ntype T = Int",
+            "x?.y?\n(f x)?\nf x? + 1",
+            "map xs (# + 1)\n(f (# * #) x)",
+            "(x = 1; f x) + 1\n(a;\n b, c)",
+            "f ...xs y\n(...xs, y, ...(f z))",
+            "syntax 'if cond then 'else otherwise { magic \"if\" (cond, then, otherwise) }",
+            "assert x == 1\nassert (x == 1) \"x is one\"\nassert (f x) \"f x\"\nassert (f \"x\")\nassert f x \"x\"",
+            "type Pair = (A, List A, ()) -> ()\ntype F = (A -> B) -> List (A,)",
+            "use foo.bar\nimport \"lib/math.pn\"",
+            "infixl 6 (<+>) concat\ninfixr 0 (^^) power\nprefix (~~) negate\n1 <+> 2\n~~x",
+            "`(a + ~b (c))\nmagic \"add\" (1, 2)\nmagic \"sub\" x",
+            "1e300\n1e400\n1.0\n0.00001\ntrue\nfalse\n{}",
+        ] {
+            let ast = parse_source(source);
+            let formatted = format(&ast).unwrap();
+            let reparsed = parse_source(&formatted);
+            assert!(
+                eq_ignoring_spans(&ast, &reparsed),
+                "while formatting '{}' as '{}'", source, formatted,
+            );
+
+            let printed = pretty(&ast).unwrap();
+            assert_eq!(pretty(&parse_source(&printed)).unwrap(), printed, "while printing '{}'", source);
+        }
+    }
+
+    #[test]
+    fn canonical() {
+        let ast = parse_source("f=a->{x=a+1;print  x\nx}\nf(2 *3)");
+        assert_eq!(
+            format(&ast).unwrap(),
+            "f = a -> {\n    x = a + 1\n    print x\n    x\n}\nf (2 * 3)\n",
+        );
+
+        let ast = parse_source("Some (x,y)=z\n`(a ~b  (c))");
+        assert_eq!(format(&ast).unwrap(), "Some (x, y) = z\n`(a ~b (c))\n");
+    }
+
+    #[test]
+    fn infinity() {
+        let infinity = |r| Spanned::new(AST::Data(Data::Real(r)), Span::empty());
+        assert_eq!(format(&infinity(f64::INFINITY)).unwrap(), "1e400");
+        assert_eq!(format(&infinity(f64::NEG_INFINITY)).unwrap(), "-1e400");
+        assert!(format(&infinity(f64::NAN)).is_err());
+    }
+
+    #[test]
+    fn quote_without_spans() {
+        let ast = parse_source("`(a + ~b (C))");
+        let stripped = Spanned::new(ast.item.without_spans(), Span::empty());
+        assert!(format(&stripped).is_err());
+    }
+
+    #[test]
+    fn round_trip_snippets() {
+        for entry in std::fs::read_dir("./tests/snippets").unwrap() {
+            let path = entry.unwrap().path();
+            let source = std::fs::read_to_string(&path).unwrap();
+            // some snippets test syntax errors
+            let ast = match lex(Source::source(&source)).and_then(parse) {
+                Ok(ast) => ast,
+                Err(_) => continue,
+            };
+            let formatted = format(&ast).unwrap();
+            assert!(eq_ignoring_spans(&ast, &parse_source(&formatted)), "{:?}\n{}", path, formatted);
+        }
+    }
+
+    #[test]
+    fn minimal_parens() {
+        for (source, expected) in &[
+            ("a + b * c",           "a + b * c"),
+            ("a + (b * c)",         "a + b * c"),
+            ("(a + b) * c",         "(a + b) * c"),
+            ("((a)) * (c)",         "a * c"),
+            // operators associate left
+            ("(a - b) - c",         "a - b - c"),
+            ("a - (b - c)",         "a - (b - c)"),
+            ("(a == b) == c",       "a == b == c"),
+            ("a | (b & (c << d))",  "a | b & c << d"),
+            ("(a | b) & c",         "(a | b) & c"),
+            // calls bind tighter than operators, but not than their arguments
+            ("(f x) + (g y)",       "f x + g y"),
+            ("f (g x) (y)",         "f (g x) y"),
+            ("(f x) y",             "(f x) y"),
+            ("~(f x)",              "~f x"),
+            ("(~f) x",              "(~f) x"),
+            ("((x -> x) y)?",       "(x -> x) y?"),
+            ("(a, (b, c))",         "a, (b, c)"),
+            ("((a,))",              "(a,)"),
+            ("x = (y -> (y + 1))",  "x = y -> y + 1"),
+            ("f (# + 1) x",         "f (# + 1) x"),
+            // declared fixities of built-in operators are taken into account
+            ("infixr 6 (-)\n(a - b) - c", "infixr 6 (-)\n(a - b) - c"),
+            ("infixr 6 (-)\na - (b - c)", "infixr 6 (-)\na - b - c"),
+            ("infixl 8 (+)\n(a + b) * c", "infixl 8 (+)\na + b * c"),
+            ("prefix (-)\n(-a) - (-(f x))", "prefix (-)\n-a - -f x"),
+            ("prefix (-)\nf (-x) (g y)", "prefix (-)\nf (-x) (g y)"),
+        ] {
+            let ast = parse_source(source);
+            assert_eq!(pretty(&ast).unwrap(), format!("{}\n", expected), "while printing '{}'", source);
+            // printing is stable
+            assert_eq!(pretty(&parse_source(&pretty(&ast).unwrap())).unwrap(), pretty(&ast).unwrap());
+        }
+    }
+
+    #[test]
+    fn adds_needed_parens() {
+        use ast::{symbol, call, binop, lambda};
+
+        // built by hand, so there are no groups
+        let sum = binop("add", symbol("a"), symbol("b"));
+        let product = binop("mul", sum.clone(), symbol("c"));
+        assert_eq!(format(&product).unwrap(), "(a + b) * c");
+
+        let nested = call(symbol("f"), call(symbol("g"), symbol("x")));
+        assert_eq!(format(&nested).unwrap(), "f (g x)");
+
+        let applied = call(lambda("x", symbol("x")), symbol("y"));
+        assert_eq!(format(&applied).unwrap(), "(x -> x) y");
+
+        let not = Spanned::new(AST::ffi("bit_not", symbol("f")), sum.span.clone());
+        assert_eq!(format(&call(not, symbol("x"))).unwrap(), "(~f) x");
+    }
+}
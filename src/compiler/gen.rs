@@ -6,12 +6,16 @@ use crate::common::{
     lambda::{Captured, Lambda},
     opcode::Opcode,
     data::Data,
+    interner::Interner,
 };
 
 // TODO: do a pass where we hoist and resolve variables?
 // may work well for types too.
 
 use crate::compiler::{
+    ast::AST,
+    desugar::desugar,
+    hoist::hoist,
     sst::{UniqueSymbol, Scope, SST, SSTPattern},
     // TODO: pattern for where?
     syntax::Syntax,
@@ -24,6 +28,13 @@ use crate::core::{
 
 // TODO: namespaces for FFIs?
 
+/// Compiles a parsed `AST` all the way to bytecode,
+/// by desugaring, hoisting, then generating bytecode with the core FFI.
+/// The resulting `Lambda` can be wrapped in a `Closure` and run on the `VM`.
+pub fn compile(ast: Spanned<AST>) -> Result<Lambda, Syntax> {
+    return gen(hoist(desugar(ast)?)?);
+}
+
 /// Simple function that generates unoptimized bytecode from an `SST`.
 /// Exposes the functionality of the `Compiler`.
 pub fn gen(sst: (Spanned<SST>, Scope)) -> Result<Lambda, Syntax> {
@@ -36,6 +47,7 @@ pub fn gen(sst: (Spanned<SST>, Scope)) -> Result<Lambda, Syntax> {
 /// Then merge it with your ffi with `FFI::combine(...)`.
 pub fn gen_with_ffi(sst: (Spanned<SST>, Scope), ffi: FFI) -> Result<Lambda, Syntax> {
     let mut compiler = Compiler::base(ffi, sst.1);
+    compiler.heapify_captured(&sst.0);
     compiler.walk(&sst.0)?;
     return Ok(compiler.lambda);
 }
@@ -55,6 +67,8 @@ pub struct Compiler {
     ffi: FFI,
     /// The FFI functions that have been bound in this scope.
     ffi_names: Vec<String>,
+    /// String constants, shared between all scopes.
+    strings: Interner,
     // determined in hoisting
     scope: Scope,
 }
@@ -67,31 +81,35 @@ impl Compiler {
             lambda:    Lambda::empty(),
             ffi,
             ffi_names: vec![],
+            strings:   Interner::new(),
             scope,
         }
     }
 
     /// Replace the current compiler with a fresh one,
     /// keeping a reference to the old one in `self.enclosing`,
-    /// and moving the FFI into the current compiler.
+    /// and moving the FFI and interned strings into the current compiler.
     pub fn enter_scope(&mut self, scope: Scope) {
         let ffi        = mem::replace(&mut self.ffi, FFI::new());
-        let nested     = Compiler::base(ffi, scope);
+        let mut nested = Compiler::base(ffi, scope);
+        nested.strings = mem::take(&mut self.strings);
         let enclosing  = mem::replace(self, nested);
         self.enclosing = Some(Box::new(enclosing));
     }
 
     /// Restore the enclosing compiler,
     /// returning the nested one for data (Lambda) extraction,
-    /// and moving the FFI mappings back into the enclosing compiler.
+    /// and moving the FFI mappings and interned strings back into the enclosing compiler.
     pub fn exit_scope(&mut self) -> Compiler {
         let ffi       = mem::replace(&mut self.ffi, FFI::new());
+        let strings   = mem::take(&mut self.strings);
         let enclosing = mem::replace(&mut self.enclosing, None);
         let nested = match enclosing {
             Some(compiler) => mem::replace(self, *compiler),
             None => unreachable!("Can not go back past root copiler"),
         };
-        self.ffi = ffi;
+        self.ffi     = ffi;
+        self.strings = strings;
         return nested;
     }
 
@@ -108,8 +126,9 @@ impl Compiler {
 
         // push left, push right, push center
         return match sst.item.clone() {
-            SST::Data(data) => Ok(self.data(data)),
-            SST::Symbol(unique) => Ok(self.symbol(unique)),
+            SST::Data(data) => { self.data(data); Ok(()) },
+            SST::Symbol(unique) => { self.symbol(unique); Ok(()) },
+            SST::Global(index) => { self.global(index); Ok(()) },
             SST::Block(block) => self.block(block),
             SST::Label(name, expression) => self.label(name, *expression),
             SST::Tuple(tuple) => self.tuple(tuple),
@@ -123,6 +142,45 @@ impl Compiler {
     // TODO: closures are just lambdas + records
     // refactor as such?
 
+    /// Returns the indices of the locals of the current scope
+    /// that are captured by the lambdas nested in an `SST`.
+    /// A lambda captures the symbols hoisting found to be nonlocal to it,
+    /// which includes those captured by lambdas nested further in.
+    pub fn captured(&self, sst: &Spanned<SST>) -> Vec<usize> {
+        let mut captured = vec![];
+
+        let mut nested = vec![sst];
+        while let Some(node) = nested.pop() {
+            match &node.item {
+                SST::Lambda { scope, .. } => captured.extend(
+                    scope.nonlocals.iter().filter_map(|n| self.scope.local_index(*n))
+                ),
+                SST::Block(b) | SST::Tuple(b) => nested.extend(b.iter()),
+                SST::Label(_, e)
+                | SST::FFI { expression: e, .. }
+                | SST::Assign { expression: e, .. } => nested.push(e),
+                SST::Call { fun, arg } => { nested.push(fun); nested.push(arg); },
+                SST::Data(_) | SST::Symbol(_) | SST::Global(_) => (),
+            }
+        }
+
+        captured.sort_unstable();
+        captured.dedup();
+        return captured;
+    }
+
+    /// Moves the locals captured by nested lambdas to the heap,
+    /// once, as the scope is entered, so every closure shares them.
+    /// Locals that aren't captured are left on the stack, as they're faster to access.
+    /// Locals are declared before the scope is entered,
+    /// so this works even if they aren't assigned yet.
+    pub fn heapify_captured(&mut self, sst: &Spanned<SST>) {
+        for index in self.captured(sst) {
+            self.lambda.emit(Opcode::Capture);
+            self.lambda.emit_bytes(&mut split_number(index));
+        }
+    }
+
     /// Resovles a symbol lookup, e.g. something like `x`.
     pub fn symbol(&mut self, unique_symbol: UniqueSymbol) {
         let index = if let Some(i) = self.scope.local_index(unique_symbol) {
@@ -137,29 +195,40 @@ impl Compiler {
         self.lambda.emit_bytes(&mut split_number(index));
     }
 
-    /// Takes a `Data` leaf and and produces some code to load the constant
+    /// Loads a global, i.e. a symbol bound in the `Prelude`.
+    pub fn global(&mut self, index: usize) {
+        self.lambda.emit(Opcode::Global);
+        self.lambda.emit_bytes(&mut split_number(index));
+    }
+
+    /// Takes a `Data` leaf and and produces some code to load the constant.
+    /// String constants are interned, so identical literals share storage.
     pub fn data(&mut self, data: Data) {
+        let data = self.strings.intern_data(data);
         self.lambda.emit(Opcode::Con);
         let mut split = split_number(self.lambda.index_data(data));
         self.lambda.emit_bytes(&mut split);
     }
 
     /// A block is a series of expressions where the last is returned.
-    /// Each sup-expression is walked, the last value is left on the stack.
+    /// Each sup-expression is walked, the last value is left on the stack,
+    /// and the values of the others are discarded, see `AST::discarded`.
+    /// An empty block is `()`.
     pub fn block(&mut self, children: Vec<Spanned<SST>>) -> Result<(), Syntax> {
-        if children.is_empty() {
-            self.data(Data::Unit);
-            return Ok(());
-        }
+        let (last, discarded) = match children.split_last() {
+            Some(split) => split,
+            None => {
+                self.data(Data::Unit);
+                return Ok(());
+            },
+        };
 
-        for child in children {
-            self.walk(&child)?;
+        for child in discarded {
+            self.walk(child)?;
             self.lambda.emit(Opcode::Del);
         }
 
-        // remove the last delete instruction
-        self.lambda.demit();
-        Ok(())
+        self.walk(last)
     }
 
     /// Generates a print expression
@@ -221,7 +290,14 @@ impl Compiler {
             },
         };
 
+        // the operands of infix operators, like `a / b`, are tupled
+        let operands = match &expression.item {
+            SST::Tuple(t) => t.iter().map(|o| o.span.clone()).collect(),
+            _             => vec![expression.span.clone()],
+        };
+
         self.lambda.emit_span(&span);
+        self.lambda.emit_operands(operands);
         self.lambda.emit(Opcode::FFICall);
         self.lambda.emit_bytes(&mut split_number(index));
         Ok(())
@@ -294,14 +370,12 @@ impl Compiler {
         expression: Spanned<SST>,
         scope: Scope,
     ) -> Result<(), Syntax> {
-        // build a list of captures at the boundary
+        // build a list of captures at the boundary,
+        // locals were already moved to the heap, see `heapify_captured`
         let mut captures = vec![];
         for nonlocal in scope.nonlocals.iter() {
             let captured = if self.scope.is_local(*nonlocal) {
-                let index = self.scope.local_index(*nonlocal).unwrap();
-                self.lambda.emit(Opcode::Capture);
-                self.lambda.emit_bytes(&mut split_number(index));
-                Captured::Local(index)
+                Captured::Local(self.scope.local_index(*nonlocal).unwrap())
             } else {
                 Captured::Nonlocal(self.scope.nonlocal_index(*nonlocal).unwrap())
             };
@@ -313,6 +387,7 @@ impl Compiler {
         {
             // push locals and captures into lambda
             self.lambda.captures = captures;
+            self.heapify_captured(&expression);
 
             // match the argument against the pattern, binding variables
             self.destructure(pattern, true);
@@ -357,6 +432,35 @@ mod test {
     };
     use crate::common::source::Source;
 
+    #[test]
+    fn compile_locals() {
+        let source = Source::source("x = 1; x");
+        let lambda = compile(parse(lex(source).unwrap()).unwrap()).unwrap();
+
+        let result = vec![
+            (Opcode::Con as u8), 128, (Opcode::Save as u8), 128, // con 1, save to x
+                (Opcode::Con as u8), 129, (Opcode::Del as u8),   // load unit, delete
+            (Opcode::Load as u8), 128,                           // load x
+        ];
+
+        assert_eq!(lambda.code, result);
+        assert_eq!(lambda.constants, vec![Data::Integer(1), Data::Unit]);
+        assert_eq!(lambda.decls, 1);
+
+        // and it runs
+        use crate::{common::closure::Closure, vm::vm::VM};
+        let mut vm = VM::init(Closure::wrap(lambda));
+        vm.run().unwrap();
+        assert_eq!(vm.stack.pop_data(), Data::Integer(1));
+    }
+
+    #[test]
+    fn compile_error() {
+        // desugaring fails, as spreads aren't supported yet
+        let source = Source::source("f ...xs");
+        assert!(compile(parse(lex(source).unwrap()).unwrap()).is_err());
+    }
+
     #[test]
     fn constants() {
         let source = Source::source("heck = true; lol = 0.0; lmao = false; eyy = \"GOod MoRNiNg, SiR\"");
@@ -367,12 +471,31 @@ mod test {
             Data::Unit, // from assignment
             Data::Real(0.0),
             Data::Boolean(false),
-            Data::String("GOod MoRNiNg, SiR".to_string()),
+            Data::String("GOod MoRNiNg, SiR".into()),
         ];
 
         assert_eq!(lambda.constants, result);
     }
 
+    #[test]
+    fn deduplicated_constants() {
+        let source = Source::source("x = \"a\" + \"a\"\ny = 1 + 1");
+        let lambda = gen(hoist(desugar(parse(lex(source).unwrap()).unwrap()).unwrap()).unwrap()).unwrap();
+
+        let count = |data: Data| lambda.constants.iter().filter(|c| **c == data).count();
+        assert_eq!(count(Data::String("a".into())), 1);
+        assert_eq!(count(Data::Integer(1)), 1);
+
+        let mut lambda = Lambda::empty();
+        let zero = lambda.index_data(Data::Real(0.0));
+        assert_eq!(lambda.index_data(Data::Real(0.0)), zero);
+        assert_ne!(lambda.index_data(Data::Real(-0.0)), zero);
+
+        let nan = lambda.index_data(Data::Real(f64::NAN));
+        assert_eq!(lambda.index_data(Data::Real(f64::NAN)), nan);
+        assert_eq!(lambda.constants.len(), 3);
+    }
+
     #[test]
     fn bytecode() {
         let source = Source::source("heck = true; lol = heck; lmao = false");
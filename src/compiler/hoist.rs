@@ -1,11 +1,12 @@
 use std::collections::HashMap;
 
-use crate::common::span::Spanned;
+use crate::common::span::{Span, Spanned};
 use crate::compiler::{
     cst::{CST, CSTPattern},
     sst::{SST, SSTPattern, UniqueSymbol, Scope},
     syntax::Syntax,
 };
+use crate::core::prelude::Prelude;
 
 // TODO: hoisting before expansion.
 // TODO: hoist labels? how are labels declared? types?
@@ -21,10 +22,30 @@ use crate::compiler::{
 /// symbols by the same name in different scopes will get different identifiers.
 /// Also resolves closure captures and closure hoisting.
 pub fn hoist(cst: Spanned<CST>) -> Result<(Spanned<SST>, Scope), Syntax> {
-    let mut hoister = Hoister::new();
+    return hoist_with_prelude(cst, &Prelude::new());
+}
+
+/// Hoists a `CST` like `hoist`, given a `Prelude` of global bindings.
+/// Symbols that are not defined in any enclosing scope
+/// are resolved to global slots in the prelude.
+/// It's an error to define a local after using a global by the same name
+/// where the local would otherwise have been used.
+/// The resulting bytecode must be run by a `VM` with the same prelude.
+pub fn hoist_with_prelude(
+    cst: Spanned<CST>,
+    prelude: &Prelude,
+) -> Result<(Spanned<SST>, Scope), Syntax> {
+    let mut hoister = Hoister::with_prelude(prelude);
     let sst = hoister.walk(cst)?;
     let scope = hoister.scopes.pop().unwrap();
 
+    if let Some(shadowed) = hoister.shadowed_globals.first() {
+        return Err(Syntax::error(
+            &format!("'{}' was used as a global before being defined here", shadowed.item),
+            &shadowed.span,
+        ).with_help("Rename this definition, or define it before it's used"));
+    }
+
     if !hoister.unresolved_hoists.is_empty() {
         // TODO: Actual errors
         return Err(Syntax::error(
@@ -46,7 +67,7 @@ pub fn hoist(cst: Spanned<CST>) -> Result<(Spanned<SST>, Scope), Syntax> {
 /// 1. Local and nonlocal variables in each scope.
 /// 2. All variables declared.
 /// 3. Variables that have been used but not declared.
-pub struct Hoister {
+pub struct Hoister<'a> {
     /// The unique local symbols in the current scope.
     scopes: Vec<Scope>,
     // TODO: make it it's own type
@@ -54,19 +75,35 @@ pub struct Hoister {
     symbol_table: Vec<String>,
     /// Keeps track of variables that were referenced before assignment.
     unresolved_hoists: HashMap<String, UniqueSymbol>,
+    /// Global bindings, used when a symbol is not defined in any scope.
+    prelude: Option<&'a Prelude>,
+    /// Globals that have been used, each with the fewest scopes
+    /// that have been open since, see `declare_local`.
+    used_globals: Vec<(String, usize)>,
+    /// Locals defined after a global by the same name was used in their scope.
+    shadowed_globals: Vec<Spanned<String>>,
 }
 
-impl Hoister {
+impl<'a> Hoister<'a> {
     /// Creates a new hoisted in a root scope.
     /// Note that the hoister will always have a root scope.
-    pub fn new() -> Hoister {
+    pub fn new() -> Hoister<'a> {
         Hoister {
             scopes:            vec![Scope::new()],
             symbol_table:      vec![],
             unresolved_hoists: HashMap::new(),
+            prelude:           None,
+            used_globals:      vec![],
+            shadowed_globals:  vec![],
         }
     }
 
+    /// Creates a new hoister that resolves undefined symbols
+    /// against a `Prelude` of global bindings.
+    pub fn with_prelude(prelude: &'a Prelude) -> Hoister<'a> {
+        Hoister { prelude: Some(prelude), ..Hoister::new() }
+    }
+
     /// Enters a new scope, called when entering a new function.
     fn   enter_scope(&mut self) { self.scopes.push(Scope::new()); }
     /// Enters an existing scope, called when resolving variables.
@@ -115,6 +152,7 @@ impl Hoister {
     pub fn walk_pattern(&mut self, pattern: Spanned<CSTPattern>, declare: bool) -> Spanned<SSTPattern> {
         let item = match pattern.item {
             CSTPattern::Symbol(name) => {
+                self.declare_local(&name, &pattern.span);
                 SSTPattern::Symbol(self.resolve_assign(&name, declare))
             },
             CSTPattern::Data(d)     => SSTPattern::Data(d),
@@ -240,8 +278,37 @@ impl Hoister {
         return unique_symbol;
     }
 
+    /// Looks to see whether a name refers to a global in the prelude.
+    /// Note that globals are only used if the symbol hasn't been defined
+    /// in any enclosing scope by the time it is referenced,
+    /// so defining a local by that name later on is an error, see `declare_local`.
+    fn global_symbol(&mut self, name: &str) -> Option<usize> {
+        if self.unresolved_hoists.contains_key(name) { return None; }
+        if self.try_resolve(name).is_some()          { return None; }
+        let index = self.prelude?.index(name)?;
+        self.used_globals.push((name.to_string(), self.scopes.len()));
+        return Some(index);
+    }
+
+    /// Records a local defined after a global by the same name was used
+    /// in the scope of the local, i.e. while none of its scopes were closed.
+    /// Without the global, that use would have been hoisted to this local.
+    fn declare_local(&mut self, name: &str, span: &Span) {
+        let depth = self.scopes.len();
+        let shadows = self.used_globals.iter()
+            .any(|(global, open)| global == name && *open >= depth);
+
+        if shadows {
+            self.shadowed_globals.push(Spanned::new(name.to_string(), span.clone()));
+        }
+    }
+
     /// Replaces a symbol name with a unique identifier for that symbol
     pub fn symbol(&mut self, name: &str) -> SST {
+        if let Some(index) = self.global_symbol(name) {
+            return SST::Global(index);
+        }
+
         // if we are hoisting the variable,
         // mark the variable as being used before its lexical definition
         return SST::Symbol(self.resolve_symbol(name));
@@ -289,6 +356,12 @@ impl Hoister {
         let sst_expression = self.walk(expression)?;
         let scope = self.exit_scope().unwrap();
 
+        // globals used in this function can't be shadowed by its later siblings
+        let depth = self.scopes.len();
+        for (_, open) in self.used_globals.iter_mut() {
+            *open = (*open).min(depth);
+        }
+
         return Ok(SST::lambda(
             sst_pattern,
             sst_expression,
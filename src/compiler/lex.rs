@@ -13,9 +13,50 @@ use crate::common::{
 use crate::compiler::{
     token::Token,
     syntax::Syntax,
+    limits::Limits,
+    operator::Operators,
 };
 
 type Bite = (Token, usize);
+type Rule = Box<dyn Fn(&str) -> Result<Bite, Failure>>;
+
+/// Why a rule failed to lex a token, and where.
+/// `offset` and `length` locate the problem relative to the start of the token,
+/// e.g. a bad escape in a string,
+/// and are used to choose between the failures of several rules, see `Lexer::first`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Failure {
+    pub message: String,
+    pub offset:  usize,
+    pub length:  usize,
+}
+
+impl Failure {
+    pub fn new(message: &str, offset: usize, length: usize) -> Failure {
+        Failure { message: message.to_string(), offset, length }
+    }
+}
+
+/// Most rules don't know where they went wrong,
+/// so their failures are at the start of the token.
+impl From<String> for Failure {
+    fn from(message: String) -> Failure {
+        Failure { message, offset: 0, length: 0 }
+    }
+}
+
+/// Wraps a rule that fails with just a message, see `Failure`.
+fn rule(rule: fn(&str) -> Result<Bite, String>) -> Rule {
+    return Box::new(move |source| rule(source).map_err(Failure::from));
+}
+
+/// The error for a string literal without a closing quote,
+/// reported at the opening quote.
+pub const UNTERMINATED_STRING: &str = "Unterminated string literal";
+
+/// The error for a block comment without a closing `}-`,
+/// reported at the opening `-{`.
+pub const UNTERMINATED_COMMENT: &str = "Unterminated block comment";
 
 /// Simple function that lexes a source file into a token stream.
 /// Exposes the functionality of the `Lexer`.
@@ -24,6 +65,114 @@ pub fn lex(source: Rc<Source>) -> Result<Vec<Spanned<Token>>, Syntax> {
     return lexer.all();
 }
 
+/// Lexes a source file into a token stream like `lex`,
+/// but rather than stopping at the first error,
+/// leaves a `Token::Error` in place of what couldn't be lexed and keeps going,
+/// returning the tokens along with all the errors, e.g. for editor diagnostics.
+/// See `parse_recover`, which parses the recovery tokens.
+/// Errors that can't be recovered from, like exceeding a limit, are returned on their own.
+pub fn lex_recover(source: Rc<Source>) -> Result<(Vec<Spanned<Token>>, Vec<Syntax>), Syntax> {
+    let mut lexer = Lexer::new(&source);
+    return lexer.all_recover();
+}
+
+/// Lexes a source file into a token stream like `lex`,
+/// but returns an error rather than lexing past the provided `Limits`.
+pub fn lex_with_limits(source: Rc<Source>, limits: Limits) -> Result<Vec<Spanned<Token>>, Syntax> {
+    let mut lexer = Lexer::with_limits(&source, limits);
+    return lexer.all();
+}
+
+/// Finds the token at a byte offset, e.g. for hovering in an editor.
+/// If several tokens contain the offset (see `Span::contains`),
+/// the one with the smallest span is chosen; ties go to the earliest.
+pub fn token_at(tokens: &[Spanned<Token>], offset: usize) -> Option<&Spanned<Token>> {
+    return tokens.iter()
+        .filter(|token| token.span.contains(offset))
+        .min_by_key(|token| token.span.length);
+}
+
+/// Returns the `Span` covering a range of tokens,
+/// from the start of the first to the end of the last,
+/// e.g. to point at the expression a parser was working on.
+/// Tokens are assumed to be in order, as they are when lexed.
+/// An empty range has an empty `Span`.
+pub fn span_of(tokens: &[Spanned<Token>]) -> Span {
+    return match (tokens.first(), tokens.last()) {
+        (Some(first), Some(last)) => Span::combine(&first.span, &last.span),
+        _ => Span::empty(),
+    };
+}
+
+/// Splits the operator runs that aren't operators back into the tokens they're made of,
+/// once the user-defined operators are known, e.g. after fixity declarations are read.
+/// The lexer takes the longest run of operator characters,
+/// so `2*~3` lexes as `2 *~ 3` and `x?+1` as `x ?+ 1`.
+/// Unless the run is in `operators`, the leading postfix `?`s
+/// and trailing prefix `~`s are split off of it,
+/// until what's left is a built-in or registered operator.
+/// A run that is still unknown is left as it is, so it can be reported whole.
+pub fn split_operators(tokens: Vec<Spanned<Token>>, operators: &Operators) -> Vec<Spanned<Token>> {
+    let mut split = vec![];
+
+    for token in tokens {
+        let (glyph, source) = match (&token.item, &token.span.source) {
+            (Token::Op(glyph), Some(source)) if operators.get(glyph).is_none() => {
+                (glyph.clone(), Rc::clone(source))
+            },
+            _ => { split.push(token); continue; },
+        };
+
+        let known = |glyph: &str| builtin_operator(glyph)
+            .or_else(|| operators.get(glyph).map(|_| Token::Op(glyph.to_string())));
+
+        // a lone `?` or `~` is always known, so something is always left in the middle
+        let (mut start, mut end) = (0, glyph.len());
+        while glyph[start..end].starts_with('?') && known(&glyph[start..end]).is_none() { start += 1; }
+        while glyph[start..end].ends_with('~')   && known(&glyph[start..end]).is_none() { end   -= 1; }
+
+        let middle = match known(&glyph[start..end]) {
+            Some(middle) => middle,
+            None => { split.push(token); continue; },
+        };
+
+        let at = |offset: usize, length: usize| Span::new(&source, token.span.offset + offset, length);
+        for offset in 0..start { split.push(Spanned::new(Token::Question, at(offset, 1))); }
+        split.push(Spanned::new(middle, at(start, end - start)));
+        for offset in end..glyph.len() { split.push(Spanned::new(Token::BitNot, at(offset, 1))); }
+    }
+
+    return split;
+}
+
+/// The token of a built-in operator made of operator characters, like `+` or `->`,
+/// i.e. the glyphs an operator run may be split into, see `split_operators`.
+fn builtin_operator(glyph: &str) -> Option<Token> {
+    let token = match glyph {
+        "="  => Token::Assign,
+        "->" => Token::Lambda,
+        "?"  => Token::Question,
+        "+"  => Token::Add,
+        "-"  => Token::Sub,
+        "*"  => Token::Mul,
+        "/"  => Token::Div,
+        "%"  => Token::Rem,
+        "==" => Token::Equal,
+        "<"  => Token::Less,
+        ">"  => Token::Greater,
+        "<=" => Token::LessEqual,
+        ">=" => Token::GreaterEqual,
+        "<<" => Token::Shl,
+        ">>" => Token::Shr,
+        "&"  => Token::BitAnd,
+        "|"  => Token::BitOr,
+        "~"  => Token::BitNot,
+        _    => return None,
+    };
+
+    return Some(token);
+}
+
 /// This represents a lexer object.
 /// A lexer takes a source file and lexes it into tokens.
 /// Note that this struct should not be controlled manually,
@@ -33,17 +182,47 @@ pub struct Lexer {
     source: Rc<Source>,
     /// The current lexing offset.
     offset: usize,
+    /// Limits on the size of the source and token stream.
+    limits: Limits,
 }
 
 impl Lexer {
     /// Create a new empty lexer.
     pub fn new(source: &Rc<Source>) -> Lexer {
-        Lexer { source: Rc::clone(source), offset: 0 }
+        Lexer::with_limits(source, Limits::unlimited())
+    }
+
+    /// Create a new empty lexer that respects some `Limits`.
+    pub fn with_limits(source: &Rc<Source>, limits: Limits) -> Lexer {
+        Lexer { source: Rc::clone(source), offset: 0, limits }
     }
 
     /// Run the lexer, generating the entire token stream.
+    /// Returns the first error, if there is one.
     pub fn all(&mut self) -> Result<Vec<Spanned<Token>>, Syntax> {
+        let (tokens, mut errors) = self.all_recover()?;
+        if !errors.is_empty() { return Err(errors.remove(0)); }
+        return Ok(tokens);
+    }
+
+    /// Run the lexer, generating the entire token stream,
+    /// along with every error encountered along the way.
+    /// On an error, the lexer leaves a `Token::Error` in place of what it skipped:
+    /// a whole malformed string literal, an unterminated block comment,
+    /// or otherwise a single character.
+    pub fn all_recover(&mut self) -> Result<(Vec<Spanned<Token>>, Vec<Syntax>), Syntax> {
         let mut tokens = vec![];
+        let mut errors = vec![];
+
+        // don't even start if the source is too long
+        self.limits.check_source(self.source.contents.len())
+            .map_err(|e| Syntax::error(&e, &Span::point(&self.source, 0)))?;
+
+        // skip the byte-order mark and shebang, if there are any
+        if self.offset == 0 {
+            self.offset += Lexer::bom(self.remaining());
+            self.offset += Lexer::shebang(self.remaining());
+        }
 
         while self.remaining().len() != 0 {
             // strip preceeding whitespace
@@ -51,90 +230,201 @@ impl Lexer {
 
             // clear out comments
             self.offset += Lexer::comment(&self.remaining());
-            self.offset += Lexer::multi_comment(&self.remaining());
+            match Lexer::multi_comment(&self.remaining()) {
+                Ok(len) => self.offset += len,
+                // an unterminated comment runs to the end of the source
+                Err(e) => {
+                    let skipped = self.remaining().len();
+                    errors.push(Syntax::error(&e, &Span::point(&self.source, self.offset)));
+                    tokens.push(Spanned::new(Token::Error, Span::new(&self.source, self.offset, skipped)));
+                    self.offset += skipped;
+                    break;
+                },
+            }
 
             // strip trailing whitespace
             self.strip();
 
+            // whitespace or a comment may end the source
+            if self.remaining().is_empty() { break; }
+
             // get next token kind, build token
             let (kind, consumed) = match self.step() {
                 Ok(k)  => k,
-                Err(e) => return Err(
-                    Syntax::error(&e, &Span::point(&self.source, self.offset))
-                ),
+                Err(failure) => {
+                    // errors are reported where they went wrong, e.g. at a bad escape
+                    let at = Span::point(&self.source, self.offset + failure.offset);
+                    errors.push(Syntax::error(&failure.message, &at));
+
+                    // malformed strings are skipped as a whole,
+                    // so their contents aren't lexed as code
+                    let skipped = if self.remaining().starts_with('"') {
+                        Lexer::string_end(self.remaining())
+                    } else {
+                        self.remaining().chars().next().map_or(0, char::len_utf8)
+                    };
+                    (Token::Error, skipped)
+                },
             };
 
             // annotate it
-            tokens.push(Spanned::new(
-                kind,
-                Span::new(&self.source, self.offset, consumed),
-            ));
+            let span = Span::new(&self.source, self.offset, consumed);
+            self.limits.check_tokens(tokens.len() + 1)
+                .map_err(|e| Syntax::error(&e, &span))?;
+            tokens.push(Spanned::new(kind, span));
             self.offset += consumed;
         }
 
+        Lexer::unquotes(&mut tokens);
         tokens.push(Spanned::new(Token::End, Span::empty()));
+        return Ok((tokens, errors));
+    }
 
-        return Ok(tokens);
+    /// Inside of a quote, `` `( ... ) ``, `~` unquotes an expression
+    /// rather than being a bitwise not.
+    /// Rules don't know where they are in the source,
+    /// so `BitNot`s within quotes are turned into `Unquote`s after lexing.
+    pub fn unquotes(tokens: &mut [Spanned<Token>]) {
+        // the paren depth at which each enclosing quote was opened
+        let mut quotes = vec![];
+        let mut depth: usize = 0;
+        let mut quoting = false;
+
+        for token in tokens.iter_mut() {
+            match token.item {
+                Token::OpenParen => {
+                    if quoting { quotes.push(depth); }
+                    depth += 1;
+                },
+                Token::CloseParen => {
+                    depth = depth.saturating_sub(1);
+                    if quotes.last() == Some(&depth) { quotes.pop(); }
+                },
+                Token::BitNot if !quotes.is_empty() => token.item = Token::Unquote,
+                _ => (),
+            }
+
+            quoting = token.item == Token::Quote;
+        }
     }
 
     /// Step the lexer, returning the next token.
-    pub fn step(&self) -> Result<Bite, String> {
+    /// Every rule is tried against the remaining source,
+    /// and the outcome is chosen by `Lexer::first`.
+    pub fn step(&self) -> Result<Bite, Failure> {
         let source = self.remaining();
 
-        let rules: Vec<Box<dyn Fn(&str) -> Result<Bite, String>>> = vec![
+        // a rule commits to the source if it is the only rule
+        // that could match a token starting with its first character,
+        // e.g. only numbers start with a digit,
+        // so a malformed number is reported as such,
+        // rather than as an unexpected token.
+        let digit = source.starts_with(|c: char| c.is_ascii_digit());
+        let quote = source.starts_with('"');
+
+        let rules: Vec<(Rule, bool)> = vec![
             // higher up in order = higher precedence
             // think 'or' as literal or 'or' as operator
 
             // static
-            Box::new(Lexer::unit),
-            Box::new(Lexer::open_bracket),
-            Box::new(Lexer::close_bracket),
-            Box::new(Lexer::open_paren),
-            Box::new(Lexer::close_paren),
-            Box::new(Lexer::syntax),
-            Box::new(Lexer::assign),
-            Box::new(Lexer::lambda),
-            Box::new(Lexer::compose),
-            Box::new(Lexer::pair),
-            Box::new(Lexer::add),
-            Box::new(Lexer::sub),
-            Box::new(Lexer::mul),
-            Box::new(Lexer::div),
-            Box::new(Lexer::equal),
-            Box::new(Lexer::remainder),
-            Box::new(Lexer::magic),
-            Box::new(Lexer::print), // remove print statements after FFI
+            (rule(Lexer::unit),              false),
+            (rule(Lexer::open_bracket),      false),
+            (rule(Lexer::close_bracket),     false),
+            (rule(Lexer::open_paren),        false),
+            (rule(Lexer::close_paren),       false),
+            (rule(Lexer::syntax),            false),
+            (rule(Lexer::type_def),          false),
+            (rule(Lexer::use_),              false),
+            (rule(Lexer::import),            false),
+            (rule(Lexer::assign),            false),
+            (rule(Lexer::lambda),            false),
+            (rule(Lexer::colon),             false),
+            (rule(Lexer::compose),           false),
+            (rule(Lexer::spread),            false),
+            (rule(Lexer::question),          false),
+            (rule(Lexer::pair),              false),
+            (rule(Lexer::add),               false),
+            (rule(Lexer::sub),               false),
+            (rule(Lexer::mul),               false),
+            (rule(Lexer::div),               false),
+            (rule(Lexer::equal),             false),
+            (rule(Lexer::less),              false),
+            (rule(Lexer::greater),           false),
+            (rule(Lexer::less_equal),        false),
+            (rule(Lexer::greater_equal),     false),
+            (rule(Lexer::remainder),         false),
+            (rule(Lexer::shl),               false),
+            (rule(Lexer::shr),               false),
+            (rule(Lexer::bit_and),           false),
+            (rule(Lexer::bit_or),            false),
+            (rule(Lexer::bit_not),           false),
+            (rule(Lexer::quote_),            false),
+            (rule(Lexer::at),                false),
+            (rule(Lexer::magic),             false),
+            (rule(Lexer::assert),            false),
+            (rule(Lexer::break_),            false),
+            (rule(Lexer::fn_),               false),
+            (rule(Lexer::placeholder),       false),
+            (rule(Lexer::print),             false), // remove print statements after FFI
 
             // variants
-            Box::new(Lexer::sep),
-            Box::new(Lexer::boolean),
+            (rule(Lexer::sep),               false),
+            (rule(Lexer::doc),               false),
+            (rule(Lexer::boolean),           false),
 
             // dynamic
-            Box::new(Lexer::real),
-            Box::new(Lexer::integer),
-            Box::new(Lexer::string),
+            (rule(Lexer::number),            digit),
+            (Box::new(Lexer::string_at),     quote),
 
             // keep this @ the bottom, lmao
-            Box::new(Lexer::keyword),
-            Box::new(Lexer::label),
-            Box::new(Lexer::symbol),
+            (rule(Lexer::keyword),           false),
+            (rule(Lexer::block_label),       false),
+            (rule(Lexer::label),             false),
+            (rule(Lexer::symbol),            false),
+            (rule(Lexer::operator),          false),
         ];
 
-        // maybe some sort of map reduce?
-        let mut best = Err("Unexpected token".to_string());
-
-        // check longest
-        for rule in &rules {
-            if let Ok((k, c)) = rule(source) {
-                match best {
-                    Err(_)              => best = Ok((k, c)),
-                    Ok((_, o)) if c > o => best = Ok((k, c)),
-                    Ok(_)               => (),
-                }
+        let outcomes = rules.iter()
+            .map(|(rule, committed)| (rule(source), *committed))
+            .collect();
+
+        return Lexer::first(outcomes);
+    }
+
+    /// Chooses a single outcome from the outcomes of all rules,
+    /// each paired with whether that rule committed to the source.
+    /// Outcomes are listed in rule declaration order.
+    /// The choice is made by the following total order:
+    /// 1. If any rule matched, the longest match is chosen;
+    ///    ties go to the rule declared first.
+    /// 2. Otherwise, of the failures of committed rules,
+    ///    the one farthest into the source is chosen,
+    ///    then the one with the widest span,
+    ///    then the one of the rule declared first.
+    /// 3. Otherwise, the source is reported as an unexpected token.
+    pub fn first(outcomes: Vec<(Result<Bite, Failure>, bool)>) -> Result<Bite, Failure> {
+        let mut best:  Option<Bite>    = None;
+        let mut error: Option<Failure> = None;
+
+        for (outcome, committed) in outcomes {
+            match outcome {
+                Ok((token, len)) => match best {
+                    Some((_, longest)) if len <= longest => (),
+                    _ => best = Some((token, len)),
+                },
+                Err(failure) if committed => match error {
+                    Some(ref e) if (failure.offset, failure.length) <= (e.offset, e.length) => (),
+                    _ => error = Some(failure),
+                },
+                Err(_) => (),
             }
         }
 
-        return best;
+        return match (best, error) {
+            (Some(bite), _)       => Ok(bite),
+            (None, Some(failure)) => Err(failure),
+            (None, None)          => Err(Failure::from("Unexpected token".to_string())),
+        };
     }
 
     // helpers
@@ -225,6 +515,21 @@ impl Lexer {
         Lexer::literal(source, "syntax", Token::Syntax)
     }
 
+    /// Matches the `type` keyword.
+    pub fn type_def(source: &str) -> Result<Bite, String> {
+        Lexer::literal(source, "type", Token::Type)
+    }
+
+    /// Matches the `use` keyword.
+    pub fn use_(source: &str) -> Result<Bite, String> {
+        Lexer::literal(source, "use", Token::Use)
+    }
+
+    /// Matches the `import` keyword.
+    pub fn import(source: &str) -> Result<Bite, String> {
+        Lexer::literal(source, "import", Token::Import)
+    }
+
     /// Matches a literal assignment equal sign `=`.
     pub fn assign(source: &str) -> Result<Bite, String> {
         Lexer::literal(source, "=", Token::Assign)
@@ -235,11 +540,26 @@ impl Lexer {
         Lexer::literal(source, "->", Token::Lambda)
     }
 
+    /// Matches a literal colon `:`, which precedes a type annotation.
+    pub fn colon(source: &str) -> Result<Bite, String> {
+        Lexer::literal(source, ":", Token::Colon)
+    }
+
     /// Matches a literal function application ".".
     pub fn compose(source: &str) -> Result<Bite, String> {
         Lexer::literal(source, ".", Token::Compose)
     }
 
+    /// Matches a literal spread "...".
+    pub fn spread(source: &str) -> Result<Bite, String> {
+        Lexer::literal(source, "...", Token::Spread)
+    }
+
+    /// Matches a literal postfix question mark "?".
+    pub fn question(source: &str) -> Result<Bite, String> {
+        Lexer::literal(source, "?", Token::Question)
+    }
+
     /// Matches a literal tuple pair ",".
     pub fn pair(source: &str) -> Result<Bite, String> {
         Lexer::literal(source, ",", Token::Pair)
@@ -270,25 +590,136 @@ impl Lexer {
         Lexer::literal(source, "==", Token::Equal)
     }
 
+    /// Matches a literal less than comparison "<".
+    pub fn less(source: &str) -> Result<Bite, String> {
+        Lexer::literal(source, "<", Token::Less)
+    }
+
+    /// Matches a literal greater than comparison ">".
+    pub fn greater(source: &str) -> Result<Bite, String> {
+        Lexer::literal(source, ">", Token::Greater)
+    }
+
+    /// Matches a literal less than or equal comparison "<=".
+    pub fn less_equal(source: &str) -> Result<Bite, String> {
+        Lexer::literal(source, "<=", Token::LessEqual)
+    }
+
+    /// Matches a literal greater than or equal comparison ">=".
+    pub fn greater_equal(source: &str) -> Result<Bite, String> {
+        Lexer::literal(source, ">=", Token::GreaterEqual)
+    }
+
     pub fn remainder(source: &str) -> Result<Bite, String> {
         Lexer::literal(source, "%", Token::Rem)
     }
+
+    /// Matches a literal left shift "<<".
+    pub fn shl(source: &str) -> Result<Bite, String> {
+        Lexer::literal(source, "<<", Token::Shl)
+    }
+
+    /// Matches a literal right shift ">>".
+    pub fn shr(source: &str) -> Result<Bite, String> {
+        Lexer::literal(source, ">>", Token::Shr)
+    }
+
+    /// Matches a literal bitwise and "&".
+    /// Note that logical and is spelled out, i.e. `and`.
+    pub fn bit_and(source: &str) -> Result<Bite, String> {
+        Lexer::literal(source, "&", Token::BitAnd)
+    }
+
+    /// Matches a literal bitwise or "|".
+    /// Note that logical or is spelled out, i.e. `or`.
+    pub fn bit_or(source: &str) -> Result<Bite, String> {
+        Lexer::literal(source, "|", Token::BitOr)
+    }
+
+    /// Matches a literal bitwise not "~".
+    pub fn bit_not(source: &str) -> Result<Bite, String> {
+        Lexer::literal(source, "~", Token::BitNot)
+    }
+
+    /// Matches a quote, `` ` ``.
+    pub fn quote_(source: &str) -> Result<Bite, String> {
+        Lexer::literal(source, "`", Token::Quote)
+    }
+
+    /// Matches the start of an attribute, `@`.
+    pub fn at(source: &str) -> Result<Bite, String> {
+        Lexer::literal(source, "@", Token::At)
+    }
+
     /// Matches a `print` expression.
     pub fn print(source: &str) -> Result<Bite, String> {
         Lexer::literal(source, "print", Token::Print)
     }
 
+    /// Matches the `break` keyword.
+    pub fn break_(source: &str) -> Result<Bite, String> {
+        Lexer::literal(source, "break", Token::Break)
+    }
+
+    /// Matches the `fn` keyword of a function literal.
+    pub fn fn_(source: &str) -> Result<Bite, String> {
+        Lexer::literal(source, "fn", Token::Fn)
+    }
+
+    /// Matches a placeholder, `#`, as in `(# + 1)`.
+    /// A shebang, `#!`, is not a placeholder, as it's only allowed on the first line.
+    pub fn placeholder(source: &str) -> Result<Bite, String> {
+        if source.starts_with("#!") {
+            return Err("A shebang is only allowed on the first line".to_string());
+        }
+        Lexer::literal(source, "#", Token::Placeholder)
+    }
+
     /// Matches an external FFI call, which takes the form:
     /// `magic "String Name of Function" data`.
     pub fn magic(source: &str) -> Result<Bite, String> {
         Lexer::literal(source, "magic", Token::Magic)
     }
 
+    /// Matches an assertion, `assert`.
+    pub fn assert(source: &str) -> Result<Bite, String> {
+        Lexer::literal(source, "assert", Token::Assert)
+    }
+
     // TODO: refactor comment and multi-line for doc-comments
 
+    /// Parses a byte-order mark, U+FEFF,
+    /// which some editors put at the start of UTF-8 files.
+    /// Only skipped at the very start of a source file;
+    /// anywhere else it's not a valid character.
+    pub fn bom(source: &str) -> usize {
+        return Lexer::expect(source, "\u{FEFF}").unwrap_or(0);
+    }
+
+    /// Parses a shebang, e.g. `#!/usr/bin/env passerine`,
+    /// which ignores from "#!" until and including the next newline.
+    /// Only valid as the very first line of a source file;
+    /// anywhere else `#!` is not a valid token.
+    pub fn shebang(source: &str) -> usize {
+        let mut len = match Lexer::expect(source, "#!") {
+            Ok(n) => n,
+            Err(_) => { return 0; },
+        };
+
+        for char in source[len..].chars() {
+            len += char.len_utf8();
+            if char == '\n' { break; }
+        }
+
+        return len;
+    }
+
     /// Parses a single-line comment,
     /// which ignores from "--" until the next newline.
+    /// Doc comments, which start with "--|", are not ignored.
     pub fn comment(source: &str) -> usize {
+        if Lexer::expect(source, "--|").is_ok() { return 0; }
+
         let mut len = match Lexer::expect(source, "--") {
             Ok(n) => n,
             Err(_) => { return 0; },
@@ -302,25 +733,43 @@ impl Lexer {
         return len;
     }
 
+    /// Matches a doc comment,
+    /// which documents from "--|" until the next newline.
+    /// A single leading space is not part of the documentation.
+    pub fn doc(source: &str) -> Result<Bite, String> {
+        let start = Lexer::expect(source, "--|")?;
+        let mut len = start;
+
+        for char in source[len..].chars() {
+            if char == '\n' { break; }
+            len += char.len_utf8();
+        }
+
+        let doc = &source[start..len];
+        let doc = doc.strip_prefix(' ').unwrap_or(doc).trim_end();
+        return Ok((Token::Doc(doc.to_string()), len));
+    }
+
     /// Parses a nestable multi-line comment,
     /// Which begins with `-{` and ends with `}-`.
-    pub fn multi_comment(source: &str) -> usize {
+    /// A comment that is never closed is an error.
+    pub fn multi_comment(source: &str) -> Result<usize, String> {
         let mut len: usize = match Lexer::expect(source, "-{") {
             Ok(n) => n,
-            Err(_) => { return 0; },
+            Err(_) => { return Ok(0); },
         };
 
-        for char in source[len..].chars() {
+        while let Some(char) = source[len..].chars().next() {
             if let Ok(_) = Lexer::expect(&source[len..], "-{") {
-                len += Lexer::multi_comment(&source[len..]);
+                len += Lexer::multi_comment(&source[len..])?;
             } else if let Ok(end) = Lexer::expect(&source[len..], "}-") {
-                len += end; break;
+                return Ok(len + end);
             } else {
                 len += char.len_utf8();
             }
         }
 
-        return len;
+        return Err(UNTERMINATED_COMMENT.to_string());
     }
 
     /// Classifies a symbol or a label.
@@ -385,72 +834,237 @@ impl Lexer {
         }
     }
 
-    /// Matches a number with a decimal point.
-    pub fn real(source: &str) -> Result<Bite, String> {
-        // TODO: NaNs, Infinity, the whole shebang
-        // look at how f64::from_str is implemented, maybe?
+    /// Matches the label of a block, a pseudokeyword followed by a colon,
+    /// e.g. `'outer:` in `'outer: { ... }`.
+    pub fn block_label(source: &str) -> Result<Bite, String> {
+        match Lexer::keyword(source)? {
+            (Token::Keyword(name), len) if source[len..].starts_with(':') => {
+                Ok((Token::BlockLabel(name), len + 1))
+            },
+            _ => Err("Expected a block label".to_string()),
+        }
+    }
+
+    /// Classifies a user-defined operator,
+    /// the longest run of one or more operator characters, like `++`, `<$>`, or `>>=`.
+    /// Built-in operators, like `+` or `->`, take precedence,
+    /// because they're checked first and the longest match wins.
+    /// A `-` ending a longer run and touching what follows is left as its own token,
+    /// so `2*-3` lexes like `2 * -3` and `x=-1` like `x = -1`,
+    /// rather than as the unknown operators `*-` and `=-`;
+    /// put a space after an operator that ends in `-`, like `a <- b`.
+    /// Likewise, a run like `*~` or `?+` is split by `split_operators`
+    /// if it turns out not to be an operator.
+    pub fn operator(source: &str) -> Result<Bite, String> {
         let mut len = 0;
 
-        // one or more digits followed by a '.' followed by 1 or more digits
-        len += Lexer::eat_digits(source)?;
-        len += Lexer::expect(&source[len..], ".")?;
-        len += Lexer::eat_digits(&source[len..])?;
+        for char in source.chars() {
+            if !"+-*/%<>=!&|^$~?".contains(char) { break; }
+            len += char.len_utf8();
+        }
 
-        let number = match f64::from_str(&source[..len]) {
-            Ok(n)  => n,
-            Err(_) => panic!("Could not convert source to supposed real")
-        };
+        if len == 0 {
+            return Err("Expected an operator".to_string());
+        }
 
-        return Ok((Token::Number(Data::Real(number)), len));
+        let negates = source[len..].starts_with(|c: char| !c.is_whitespace());
+        if len > 1 && source[..len].ends_with('-') && negates {
+            len -= 1;
+        }
+
+        return Ok((Token::Op(source[..len].to_string()), len));
     }
 
-    pub fn integer(source: &str) -> Result<Bite, String> {
-        let mut len = 0;
+    /// Matches a number, either an integer or a real.
+    /// A number is one or more digits,
+    /// optionally followed by a `.` and one or more digits,
+    /// optionally followed by an exponent, `e`, an optional sign, and one or more digits.
+    /// If either of the latter two are present, the number is a real.
+    /// Reals too large or too small to be represented become
+    /// infinity or zero, following IEEE 754.
+    /// Note that a `.` not followed by a digit is not part of the number,
+    /// so `1.x` is the composition `1 . x`.
+    pub fn number(source: &str) -> Result<Bite, String> {
+        let mut len  = 0;
+        let mut real = false;
+
         len += Lexer::eat_digits(source)?;
 
-        let number = match i64::from_str(&source[..len]) {
-            Ok(n) => n,
-            Err(_) => panic!("Could not convert source to supposed integer"),
+        // fractional part
+        if let Ok(dot) = Lexer::expect(&source[len..], ".") {
+            if let Ok(digits) = Lexer::eat_digits(&source[len + dot..]) {
+                len += dot + digits;
+                real = true;
+            }
+        }
+
+        // exponent
+        if let Some('e') | Some('E') = source[len..].chars().next() {
+            len += 1;
+            if let Some('-') | Some('+') = source[len..].chars().next() { len += 1; }
+            len += Lexer::eat_digits(&source[len..])
+                .map_err(|_| "Expected digits in the exponent of a real literal".to_string())?;
+            real = true;
+        }
+
+        // something like `1.2.3`
+        if let Ok(dot) = Lexer::expect(&source[len..], ".") {
+            if real && Lexer::eat_digits(&source[len + dot..]).is_ok() {
+                return Err("Real literals may only have one decimal point".to_string());
+            }
+        }
+
+        let token = if real {
+            match f64::from_str(&source[..len]) {
+                Ok(n)  => Token::Real(Data::Real(n)),
+                Err(_) => unreachable!("Could not convert source to supposed real"),
+            }
+        } else {
+            match i64::from_str(&source[..len]) {
+                Ok(n)  => Token::Integer(Data::Integer(n)),
+                Err(_) => Token::Integer(Lexer::big_integer(&source[..len])?),
+            }
         };
 
-        // TODO: introduce new token?
-        return Ok((Token::Number(Data::Integer(number)), len));
+        return Ok((token, len));
+    }
+
+    /// Converts the digits of an integer literal
+    /// too large to fit in 64 bits to a `Data::BigInt`.
+    #[cfg(feature = "bignum")]
+    fn big_integer(digits: &str) -> Result<Data, String> {
+        match num_bigint::BigInt::from_str(digits) {
+            Ok(n)  => Ok(Data::BigInt(n)),
+            Err(_) => unreachable!("Could not convert source to supposed integer"),
+        }
+    }
+
+    /// Integer literals too large to fit in 64 bits are an error,
+    /// unless the `bignum` feature is enabled.
+    #[cfg(not(feature = "bignum"))]
+    fn big_integer(_digits: &str) -> Result<Data, String> {
+        Err("Integer literal is too large".to_string())
     }
 
     /// Matches a string, converting escapes.
     pub fn string(source: &str) -> Result<Bite, String> {
-        // TODO: read through the rust compiler and figure our how they do this
-        // look into parse_str_lit
+        return Lexer::string_at(source).map_err(|failure| failure.message);
+    }
 
-        let mut len    = 0;
-        let mut escape = false;
+    /// Matches a string like `Lexer::string`,
+    /// but a malformed string also reports where the problem is,
+    /// so a bad escape can be pointed at directly.
+    pub fn string_at(source: &str) -> Result<Bite, Failure> {
+        let mut len    = Lexer::expect(source, "\"")?;
         let mut string = "".to_string();
 
-        len += Lexer::expect(source, "\"")?;
+        while let Some(c) = source[len..].chars().next() {
+            match c {
+                '\\' if source.len() == len + 1 => break,
+                '\\' => {
+                    let (escaped, consumed) = Lexer::escape(&source[len + 1..])
+                        .map_err(|e| Failure::new(&e, len, 1))?;
+                    string.push(escaped);
+                    len += 1 + consumed;
+                },
+                '\"' => return Ok((Token::String(Data::String(string.into())), len + 1)),
+                c    => {
+                    string.push(c);
+                    len += c.len_utf8();
+                },
+            }
+        }
 
-        for c in source[len..].chars() {
-            len += c.len_utf8();
-            if escape {
-                escape = false;
-                // TODO: add more escape codes
-                string.push(match c {
-                    '"'  => '"',
-                    '\\' => '\\',
-                    'n'  => '\n',
-                    't'  => '\t',
-                    'r'  => '\r',
-                    o    => return Err(format!("Unknown escape code '\\{}'", o)),
-                })
-            } else {
-                match c {
-                    '\\' => escape = true,
-                    '\"' => return Ok((Token::String(Data::String(string)), len)),
-                    c    => string.push(c),
-                }
+        // reported at the opening quote
+        return Err(Failure::new(UNTERMINATED_STRING, 0, 1));
+    }
+
+    /// The length of a string literal up to and including its closing quote,
+    /// or to the end of the source if it has none.
+    /// Escapes aren't decoded, a backslash just skips the character after it,
+    /// so a malformed string can be skipped as a whole.
+    pub fn string_end(source: &str) -> usize {
+        let mut chars = source.char_indices().skip(1);
+
+        while let Some((i, c)) = chars.next() {
+            match c {
+                '\\' => { chars.next(); },
+                '"'  => return i + 1,
+                _    => (),
             }
         }
 
-        return Err("Unexpected EOF while parsing string literal".to_string());
+        return source.len();
+    }
+
+    /// Decodes the escape code following a backslash,
+    /// returning the character and the length of the code.
+    /// `\xNN` escapes must be ASCII,
+    /// and `\u{N...}` escapes must be a single unicode scalar value.
+    pub fn escape(source: &str) -> Result<(char, usize), String> {
+        let c = source.chars().next()
+            .ok_or("Unexpected EOF while parsing string literal")?;
+
+        let simple = match c {
+            '"'  => '"',
+            '\'' => '\'',
+            '\\' => '\\',
+            'n'  => '\n',
+            't'  => '\t',
+            'r'  => '\r',
+            '0'  => '\0',
+            'x'  => return Lexer::hex_escape(source),
+            'u'  => return Lexer::unicode_escape(source),
+            o    => return Err(format!("Unknown escape code '\\{}'", o)),
+        };
+
+        return Ok((simple, 1));
+    }
+
+    /// Decodes an ASCII escape, like `\x7F`.
+    fn hex_escape(source: &str) -> Result<(char, usize), String> {
+        let digits = source.get(1..3)
+            .filter(|d| d.chars().all(|c| c.is_ascii_hexdigit()))
+            .ok_or("Expected two hexadecimal digits in escape '\\x'")?;
+
+        let code = u8::from_str_radix(digits, 16).unwrap();
+        if code > 0x7F {
+            return Err(format!("Escape '\\x{}' is not ASCII, use '\\u{{{:X}}}' instead", digits, code));
+        }
+
+        return Ok((code as char, 3));
+    }
+
+    /// Decodes a unicode escape, like `\u{1F600}`.
+    /// The `}` must directly follow the digits,
+    /// rather than being found somewhere later on in the source.
+    fn unicode_escape(source: &str) -> Result<(char, usize), String> {
+        if !source[1..].starts_with('{') {
+            return Err("Expected '{' after unicode escape '\\u'".to_string());
+        }
+
+        // however many digits there are, so too many are reported as such
+        let close = source[2..].find(|c: char| !c.is_ascii_hexdigit())
+            .map(|i| i + 2)
+            .filter(|close| source[*close..].starts_with('}'))
+            .ok_or("Unterminated unicode escape, expected '}'")?;
+        let digits = &source[2..close];
+
+        if digits.is_empty() || digits.len() > 6 {
+            return Err(format!(
+                "Unicode escape '\\u{{{}}}' must be one to six hexadecimal digits",
+                digits,
+            ));
+        }
+
+        let code = u32::from_str_radix(digits, 16).unwrap();
+        return match char::from_u32(code) {
+            Some(c) => Ok((c, close + 1)),
+            None    => Err(format!(
+                "Unicode escape '\\u{{{}}}' is not a valid unicode scalar value",
+                digits,
+            )),
+        };
     }
 
     /// Matches a literal boolean.
@@ -502,6 +1116,7 @@ impl Lexer {
 mod test {
     use super::*;
     use crate::common::data::Data;
+    use crate::compiler::{operator::{Operator, Assoc}, parse::Prec};
 
     // NOTE: lexing individual tokens is tested in pipeline::token
 
@@ -578,7 +1193,7 @@ mod test {
             Spanned::new(Token::Symbol,                                   Span::new(&source, 18, 8)),
             Spanned::new(Token::OpenParen,                                Span::new(&source, 27, 1)),
             Spanned::new(Token::Symbol,                                   Span::new(&source, 28, 8)),
-            Spanned::new(Token::String(Data::String("heck".to_string())), Span::new(&source, 37, 6)),
+            Spanned::new(Token::String(Data::String("heck".into())), Span::new(&source, 37, 6)),
             Spanned::new(Token::CloseParen,                               Span::new(&source, 43, 1)),
             Spanned::new(Token::End,                          Span::empty()),
         ];
@@ -588,6 +1203,11 @@ mod test {
 
     // helper function for the following tests
 
+    fn test_split(source: &str, operators: &Operators) -> Vec<Token> {
+        let tokens = split_operators(lex(Source::source(source)).unwrap(), operators);
+        return tokens.into_iter().map(|t| t.item).collect();
+    }
+
     fn test_literal(literal: &str, token: Token, length: usize) -> bool {
         let result = Lexer::new(&Source::source(literal)).step();
 
@@ -634,41 +1254,596 @@ mod test {
     fn real() {
         if !test_literal(
             "2.0",
-            Token::Number(Data::Real(2.0)),
+            Token::Real(Data::Real(2.0)),
             3,
         ) { panic!() }
 
         if !test_literal(
             "210938.2221",
-            Token::Number(Data::Real(210938.2221)),
+            Token::Real(Data::Real(210938.2221)),
             11,
         ) { panic!() }
     }
 
+    #[test]
+    fn real_exponent() {
+        if !test_literal("5e2",   Token::Real(Data::Real(500.0)),         3) { panic!() }
+        if !test_literal("1.5E3", Token::Real(Data::Real(1500.0)),        5) { panic!() }
+        if !test_literal("1e400", Token::Real(Data::Real(f64::INFINITY)), 5) { panic!() }
+        if !test_literal("25e-1", Token::Real(Data::Real(2.5)),           5) { panic!() }
+        if !test_literal("1E+2",  Token::Real(Data::Real(100.0)),         4) { panic!() }
+        if !test_literal("1e-10", Token::Real(Data::Real(1e-10)),         5) { panic!() }
+        if !test_literal("1e+10", Token::Real(Data::Real(1e10)),          5) { panic!() }
+        if !test_literal("1.5e3", Token::Real(Data::Real(1500.0)),        5) { panic!() }
+        if !test_literal("1.5e-3", Token::Real(Data::Real(0.0015)),       6) { panic!() }
+        // the sign is part of the exponent, not a subtraction
+        if !test_literal("1e-1-1", Token::Real(Data::Real(0.1)),          4) { panic!() }
+
+        if !test_literal("1e-400", Token::Real(Data::Real(0.0)),          6) { panic!() }
+        let tiny = format!("0.{}1", "0".repeat(400));
+        if !test_literal(&tiny, Token::Real(Data::Real(0.0)), tiny.len()) { panic!() }
+    }
+
+    #[test]
+    fn real_display_round_trip() {
+        for n in &[1e300, 1e-300, 123456.789, 5.0, 0.1, 1e16, 1e17, 0.00001, f64::MAX, f64::MIN_POSITIVE] {
+            let displayed = Data::Real(*n).to_string();
+            if !test_literal(&displayed, Token::Real(Data::Real(*n)), displayed.len()) {
+                panic!("{} was displayed as {}", n, displayed);
+            }
+        }
+
+        assert_eq!(Data::Real(1e300).to_string(), "1e300");
+        assert_eq!(Data::Real(1e-300).to_string(), "1e-300");
+        assert_eq!(Data::Real(123456.789).to_string(), "123456.789");
+    }
+
+    #[test]
+    fn integer_or_real() {
+        if !test_literal("5",   Token::Integer(Data::Integer(5)), 1) { panic!() }
+        if !test_literal("5.0", Token::Real(Data::Real(5.0)),     3) { panic!() }
+        if !test_literal("5e2", Token::Real(Data::Real(500.0)),   3) { panic!() }
+    }
+
+    #[test]
+    fn integer() {
+        if !test_literal("42",   Token::Integer(Data::Integer(42)), 2) { panic!() }
+        // not a decimal point, but a composition
+        if !test_literal("42.x", Token::Integer(Data::Integer(42)), 2) { panic!() }
+    }
+
+    #[test]
+    fn malformed_numbers() {
+        for (malformed, offset) in &[
+            ("1.2.3",                0),
+            ("x = 1e",               4),
+            ("x = 1e-",              4),
+            ("x = 1e+ 1",            4),
+            ("x = 1.5e-y",           4),
+            #[cfg(not(feature = "bignum"))]
+            ("99999999999999999999", 0),
+        ] {
+            let source = Source::source(malformed);
+            match lex(source.clone()) {
+                Err(Syntax { span, .. }) => assert_eq!(span, Span::point(&source, *offset)),
+                Ok(_) => panic!("Lexed malformed number '{}'", malformed),
+            }
+        }
+    }
+
+    #[test]
+    fn first_longest_then_declared() {
+        let outcomes = vec![
+            (Ok((Token::Symbol, 2)),              false),
+            (Ok((Token::Label,  3)),              false),
+            (Ok((Token::Print,  3)),              false),
+            (Err(Failure::new("Nope", 0, 0)),     true),
+        ];
+        assert_eq!(Lexer::first(outcomes), Ok((Token::Label, 3)));
+    }
+
+    #[test]
+    fn first_committed_error() {
+        let outcomes = vec![
+            (Err(Failure::new("Uncommitted", 0, 0)), false),
+            (Err(Failure::new("First", 0, 0)),       true),
+            (Err(Failure::new("Second", 0, 0)),      true),
+        ];
+        assert_eq!(Lexer::first(outcomes), Err(Failure::new("First", 0, 0)));
+
+        let outcomes = vec![(Err(Failure::new("Uncommitted", 0, 0)), false)];
+        assert_eq!(Lexer::first(outcomes), Err(Failure::new("Unexpected token", 0, 0)));
+    }
+
+    #[test]
+    fn first_farthest_then_widest_error() {
+        let narrow = Failure::new("Narrow", 3, 1);
+        let wide   = Failure::new("Wide",   3, 4);
+        let early  = Failure::new("Early",  1, 9);
+
+        // the wider of two errors at the same offset wins, whichever comes first
+        for (a, b) in &[(&narrow, &wide), (&wide, &narrow)] {
+            let outcomes = vec![
+                (Err((*a).clone()),    true),
+                (Err(early.clone()),   true),
+                (Err((*b).clone()),    true),
+            ];
+            assert_eq!(Lexer::first(outcomes), Err(wide.clone()));
+        }
+
+        // an error farther in wins over a wider one
+        let outcomes = vec![(Err(early.clone()), true), (Err(narrow.clone()), true)];
+        assert_eq!(Lexer::first(outcomes), Err(narrow));
+    }
+
+    #[test]
+    fn escapes() {
+        for (escape, expected) in [
+            ("\\\"",       '"'),
+            ("\\'",        '\''),
+            ("\\\\",       '\\'),
+            ("\\n",        '\n'),
+            ("\\t",        '\t'),
+            ("\\r",        '\r'),
+            ("\\0",        '\0'),
+            ("\\x41",      'A'),
+            ("\\x7F",      '\x7F'),
+            ("\\u{e9}",    'é'),
+            ("\\u{1F600}", '😀'),
+        ].iter() {
+            let literal = format!("\"{}\"", escape);
+            if !test_literal(
+                &literal,
+                Token::String(Data::String(expected.to_string().into())),
+                literal.len(),
+            ) { panic!("escape {} did not decode to {:?}", escape, expected) }
+        }
+    }
+
+    #[test]
+    fn malformed_escapes() {
+        for (literal, message) in [
+            ("\"\\q\"",         "Unknown escape code '\\q'"),
+            ("\"\\xFF\"",       "Escape '\\xFF' is not ASCII, use '\\u{FF}' instead"),
+            ("\"\\x4\"",        "Expected two hexadecimal digits in escape '\\x'"),
+            ("\"\\u41\"",       "Expected '{' after unicode escape '\\u'"),
+            ("\"\\u{41\"",      "Unterminated unicode escape, expected '}'"),
+            ("\"\\u{41\" + \"}\"", "Unterminated unicode escape, expected '}'"),
+            ("\"\\u{000000041}\"", "Unicode escape '\\u{000000041}' must be one to six hexadecimal digits"),
+            ("\"\\u{41 }\"",    "Unterminated unicode escape, expected '}'"),
+            ("\"\\u{}\"",       "Unicode escape '\\u{}' must be one to six hexadecimal digits"),
+            ("\"\\u{1234567}\"", "Unicode escape '\\u{1234567}' must be one to six hexadecimal digits"),
+            ("\"\\u{D800}\"",   "Unicode escape '\\u{D800}' is not a valid unicode scalar value"),
+        ].iter() {
+            assert_eq!(Lexer::string(literal), Err(message.to_string()));
+        }
+    }
+
+    #[test]
+    fn over_long_escape() {
+        // reported at the escape, not the start of the string
+        let source = Source::source("x = \"\\n\\u{0000000041}\"");
+        assert_eq!(
+            lex(source.clone()),
+            Err(Syntax::error(
+                "Unicode escape '\\u{0000000041}' must be one to six hexadecimal digits",
+                &Span::point(&source, 7),
+            )),
+        );
+    }
+
+    #[test]
+    fn out_of_range_escape() {
+        let source = Source::source("x = \"ok \\u{110000}\"");
+        assert_eq!(
+            lex(source.clone()),
+            Err(Syntax::error(
+                "Unicode escape '\\u{110000}' is not a valid unicode scalar value",
+                &Span::point(&source, 8),
+            )),
+        );
+    }
+
+    #[test]
+    fn token_at_offset() {
+        let source = Source::source("print foo bar");
+        let tokens = lex(source.clone()).unwrap();
+
+        let found = token_at(&tokens, 7).unwrap();
+        assert_eq!(found.item, Token::Symbol);
+        assert_eq!(found.span, Span::new(&source, 6, 3));
+
+        // a boundary belongs to the token that starts there
+        assert_eq!(token_at(&tokens, 6).unwrap().span, Span::new(&source, 6, 3));
+        assert_eq!(token_at(&tokens, 9), None);
+        assert_eq!(token_at(&tokens, 13), None);
+
+        // a zero-width span is smaller than anything else
+        let mut with_point = tokens.clone();
+        with_point.push(Spanned::new(Token::Sep, Span::point(&source, 7)));
+        assert_eq!(token_at(&with_point, 7).unwrap().item, Token::Sep);
+    }
+
+    #[test]
+    fn span_of_tokens() {
+        let source = Source::source("x = f (1, 2)\ny");
+        let tokens = lex(source.clone()).unwrap();
+
+        // `f (1, 2)`
+        let call = &tokens[2..8];
+        assert_eq!(call.first().unwrap().item, Token::Symbol);
+        assert_eq!(call.last().unwrap().item, Token::CloseParen);
+        assert_eq!(span_of(call), Span::new(&source, 4, 8));
+        assert_eq!(span_of(call).contents(), "f (1, 2)");
+
+        assert_eq!(span_of(&tokens[0..1]), Span::new(&source, 0, 1));
+        assert_eq!(span_of(&tokens[..0]), Span::empty());
+        assert!(span_of(&[]).is_empty());
+    }
+
+    #[test]
+    fn at() {
+        if !test_literal("@", Token::At, 1) { panic!() }
+        let tokens = lex(Source::source("@inline")).unwrap();
+        assert_eq!(tokens[0].item, Token::At);
+        assert_eq!(tokens[1].item, Token::Symbol);
+    }
+
+    #[test]
+    fn block_labels() {
+        let tokens = lex(Source::source("'outer: { break 'outer x }\nf 'if")).unwrap()
+            .into_iter()
+            .map(|t| t.item)
+            .collect::<Vec<Token>>();
+
+        assert_eq!(tokens, vec![
+            Token::BlockLabel("outer".to_string()),
+            Token::OpenBracket,
+            Token::Break,
+            Token::Keyword("outer".to_string()),
+            Token::Symbol,
+            Token::CloseBracket,
+            Token::Sep,
+            Token::Symbol,
+            Token::Keyword("if".to_string()),
+            Token::End,
+        ]);
+
+        // a symbol that starts with `break` is still a symbol
+        if !test_literal("breaking", Token::Symbol, 8) { panic!() }
+    }
+
+    #[test]
+    fn unterminated_string() {
+        let result = Lexer::new(&Source::source("\"Hello")).step();
+        assert_eq!(result.map_err(|f| f.message), Err(UNTERMINATED_STRING.to_string()));
+
+        // strings may span lines, so a newline doesn't end one either
+        for (source, quote) in &[
+            ("x = \"Hello", 4),
+            ("x = \"Hello\ny = 2\n", 4),
+            ("print \"a\" + \"b\\", 12),
+            ("\"", 0),
+        ] {
+            let source = Source::source(source);
+            assert_eq!(
+                lex(source.clone()),
+                Err(Syntax::error(UNTERMINATED_STRING, &Span::point(&source, *quote))),
+            );
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "bignum")]
+    fn big_integer() {
+        use num_bigint::BigInt;
+
+        let big = "99999999999999999999";
+        let expected = Data::BigInt(BigInt::from_str(big).unwrap());
+        if !test_literal(big, Token::Integer(expected), big.len()) { panic!() }
+    }
+
+    #[test]
+    fn shebang() {
+        let tokens = |source| lex(Source::source(source)).unwrap()
+            .into_iter()
+            .map(|t| t.item)
+            .collect::<Vec<Token>>();
+
+        assert_eq!(
+            tokens("#!/usr/bin/env passerine\nx = 1\nprint x"),
+            tokens("x = 1\nprint x"),
+        );
+
+        let source = Source::source("x = 1\n#!/usr/bin/env passerine");
+        match lex(source.clone()) {
+            Err(Syntax { span, .. }) => assert_eq!(span, Span::point(&source, 6)),
+            Ok(_) => panic!("Lexed a shebang that is not on the first line"),
+        }
+    }
+
+    #[test]
+    fn trailing_whitespace() {
+        for source in ["x = ", "x = 1 \t", "x -- comment", "x -{ comment }-  "] {
+            let tokens = lex(Source::source(source)).unwrap();
+            assert_eq!(tokens.last().unwrap().item, Token::End);
+        }
+
+        // the comment is skipped, not lexed
+        assert_eq!(lex(Source::source("x -{ comment }-  ")).unwrap().len(), 2);
+    }
+
+    #[test]
+    fn byte_order_mark() {
+        let tokens = |source| lex(Source::source(source)).unwrap()
+            .into_iter()
+            .map(|t| t.item)
+            .collect::<Vec<Token>>();
+
+        assert_eq!(tokens("\u{FEFF}x = 1\nprint x"), tokens("x = 1\nprint x"));
+        assert_eq!(
+            tokens("\u{FEFF}#!/usr/bin/env passerine\nx = 1"),
+            tokens("x = 1"),
+        );
+
+        let source = Source::source("x = 1\n\u{FEFF}");
+        match lex(source.clone()) {
+            Err(Syntax { span, .. }) => assert_eq!(span, Span::point(&source, 6)),
+            Ok(_) => panic!("Lexed a byte-order mark that is not at the start"),
+        }
+    }
+
+    #[test]
+    fn type_keyword() {
+        if !test_literal("type",  Token::Type,   4) { panic!() }
+        if !test_literal("types", Token::Symbol, 5) { panic!() }
+    }
+
+    #[test]
+    fn doc_comment() {
+        let source = Source::source("-- ignored\n--| Documented.\nx");
+
+        let result = vec![
+            Spanned::new(Token::Sep,                          Span::new(&source, 10, 1)),
+            Spanned::new(Token::Doc("Documented.".to_string()), Span::new(&source, 11, 15)),
+            Spanned::new(Token::Sep,                          Span::new(&source, 26, 1)),
+            Spanned::new(Token::Symbol,                       Span::new(&source, 27, 1)),
+            Spanned::new(Token::End,                          Span::empty()),
+        ];
+
+        assert_eq!(lex(source), Ok(result));
+    }
+
+    #[test]
+    fn assert_keyword() {
+        if !test_literal("assert",  Token::Assert, 6) { panic!() }
+        if !test_literal("asserts", Token::Symbol, 7) { panic!() }
+    }
+
+    #[test]
+    fn import_keywords() {
+        if !test_literal("use",     Token::Use,    3) { panic!() }
+        if !test_literal("import",  Token::Import, 6) { panic!() }
+        if !test_literal("user",    Token::Symbol, 4) { panic!() }
+        if !test_literal("imports", Token::Symbol, 7) { panic!() }
+    }
+
+    #[test]
+    fn unquote_only_in_quotes() {
+        let kinds = |source| lex(Source::source(source)).unwrap()
+            .into_iter().map(|t| t.item).collect::<Vec<_>>();
+
+        assert_eq!(
+            kinds("`(a ~(b ~c)) ~d"),
+            vec![
+                Token::Quote, Token::OpenParen,
+                Token::Symbol, Token::Unquote,
+                Token::OpenParen, Token::Symbol, Token::Unquote, Token::Symbol, Token::CloseParen,
+                Token::CloseParen,
+                Token::BitNot, Token::Symbol,
+                Token::End,
+            ]
+        );
+
+        // a paren that doesn't follow a quote doesn't start one
+        assert_eq!(
+            kinds("(~a)"),
+            vec![Token::OpenParen, Token::BitNot, Token::Symbol, Token::CloseParen, Token::End]
+        );
+    }
+
+    #[test]
+    fn spread() {
+        let source = Source::source("f ...xs . g");
+        let tokens = lex(source.clone()).unwrap();
+        assert_eq!(tokens[1], Spanned::new(Token::Spread, Span::new(&source, 2, 3)));
+        assert_eq!(tokens[3], Spanned::new(Token::Compose, Span::new(&source, 8, 1)));
+    }
+
+    #[test]
+    fn limits() {
+        let source = Source::source("x = 1 + 2");
+        let limits = |source_bytes, tokens| Limits { source_bytes, tokens };
+
+        assert!(lex_with_limits(source.clone(), limits(Some(9), Some(5))).is_ok());
+        assert!(lex_with_limits(source.clone(), Limits::unlimited()).is_ok());
+
+        let too_long = lex_with_limits(source.clone(), limits(Some(8), None)).unwrap_err();
+        assert_eq!(too_long.span, Span::point(&source, 0));
+
+        // errors on the first token past the limit
+        let too_many = lex_with_limits(source.clone(), limits(None, Some(3))).unwrap_err();
+        assert_eq!(too_many.span, Span::new(&source, 6, 1));
+    }
+
+    #[test]
+    fn recover() {
+        let source = Source::source("x = 1 \\ 2\ny = § true");
+        let (tokens, errors) = lex_recover(source.clone()).unwrap();
+
+        let spans = errors.into_iter().map(|e| e.span).collect::<Vec<Span>>();
+        assert_eq!(spans, vec![Span::point(&source, 6), Span::point(&source, 14)]);
+
+        // what couldn't be lexed is left in place
+        let recovery = tokens.iter()
+            .filter(|t| t.item == Token::Error)
+            .map(|t| t.span.clone())
+            .collect::<Vec<Span>>();
+        assert_eq!(recovery, vec![Span::new(&source, 6, 1), Span::new(&source, 14, 2)]);
+        assert_eq!(tokens.len(), 11);
+
+        // lex still stops at the first
+        assert_eq!(lex(source.clone()).unwrap_err().span, Span::point(&source, 6));
+    }
+
+    #[test]
+    fn recover_after_string() {
+        // the whole string is skipped, rather than lexing its contents as code
+        let source = Source::source("x = \"a \\q b\" + 1");
+        let (tokens, errors) = lex_recover(source.clone()).unwrap();
+
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].message, "Unknown escape code '\\q'");
+        assert_eq!(tokens[2], Spanned::new(Token::Error, Span::new(&source, 4, 8)));
+        assert_eq!(tokens[3].item, Token::Add);
+    }
+
+    #[test]
+    fn unterminated_comment() {
+        let source = Source::source("x -{ c");
+        let (tokens, errors) = lex_recover(source.clone()).unwrap();
+
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].message, UNTERMINATED_COMMENT);
+        assert_eq!(errors[0].span, Span::point(&source, 2));
+        assert_eq!(tokens[1], Spanned::new(Token::Error, Span::new(&source, 2, 4)));
+        assert!(lex(source).is_err());
+
+        // nested comments have to be closed too
+        assert!(lex(Source::source("x -{ a -{ b }- c")).is_err());
+        assert!(lex(Source::source("x -{ a -{ b }- c }-")).is_ok());
+    }
+
+    #[test]
+    fn placeholder() {
+        if !test_literal("#", Token::Placeholder, 1) { panic!() }
+        if !test_literal("#+", Token::Placeholder, 1) { panic!() }
+    }
+
+    #[test]
+    fn question() {
+        if !test_literal("?", Token::Question, 1) { panic!() }
+    }
+
+    #[test]
+    fn lone_dot() {
+        if !test_literal(".", Token::Compose, 1) { panic!() }
+    }
+
     #[test]
     fn string() {
         let source = "\"heck\"";
         if !test_literal(
             source,
-            Token::String(Data::String("heck".to_string())),
+            Token::String(Data::String("heck".into())),
             source.len(),
         ) { panic!() }
 
         let escape = "\"I said, \\\"Hello, world!\\\" didn't I?\"";
         if !test_literal(
             escape,
-            Token::String(Data::String("I said, \"Hello, world!\" didn't I?".to_string())),
+            Token::String(Data::String("I said, \"Hello, world!\" didn't I?".into())),
             escape.len(),
         ) { panic!() }
 
         let unicode = "\"Yo 👋! Ünícode µ works just fine 🚩! うん、気持ちいい！\"";
         if !test_literal(
             unicode,
-            Token::String(Data::String("Yo 👋! Ünícode µ works just fine 🚩! うん、気持ちいい！".to_string())),
+            Token::String(Data::String("Yo 👋! Ünícode µ works just fine 🚩! うん、気持ちいい！".into())),
             unicode.len(),
         ) { panic!() }
     }
 
+    #[test]
+    fn comparison() {
+        if !test_literal("<",   Token::Less,         1) { panic!() }
+        if !test_literal(">",   Token::Greater,      1) { panic!() }
+        if !test_literal("<=",  Token::LessEqual,    2) { panic!() }
+        if !test_literal(">=",  Token::GreaterEqual, 2) { panic!() }
+        if !test_literal("<>",  Token::Op("<>".to_string()), 2) { panic!() }
+    }
+
+    #[test]
+    fn bitwise() {
+        if !test_literal("<<",  Token::Shl,    2) { panic!() }
+        if !test_literal(">>",  Token::Shr,    2) { panic!() }
+        if !test_literal("&",   Token::BitAnd, 1) { panic!() }
+        if !test_literal("|",   Token::BitOr,  1) { panic!() }
+        if !test_literal("~",   Token::BitNot, 1) { panic!() }
+        // longer user-defined operators still win
+        if !test_literal("&&",  Token::Op("&&".to_string()),  2) { panic!() }
+        if !test_literal("|>",  Token::Op("|>".to_string()),  2) { panic!() }
+        if !test_literal("<<=", Token::Op("<<=".to_string()), 3) { panic!() }
+        // logical and/or are not operators
+        if !test_literal("or",  Token::Symbol, 2) { panic!() }
+    }
+
+    #[test]
+    fn operator() {
+        if !test_literal("++",  Token::Op("++".to_string()), 2)  { panic!() }
+        if !test_literal("<>",  Token::Op("<>".to_string()), 2)  { panic!() }
+        if !test_literal("->",  Token::Lambda, 2)                { panic!() }
+        if !test_literal(":",   Token::Colon, 1)                 { panic!() }
+        if !test_literal("+ +", Token::Add, 1)                   { panic!() }
+        if !test_literal("+",   Token::Add, 1)                   { panic!() }
+        if !test_literal(">>=", Token::Op(">>=".to_string()), 3) { panic!() }
+        if !test_literal("<$>", Token::Op("<$>".to_string()), 3) { panic!() }
+        if !test_literal("<~>", Token::Op("<~>".to_string()), 3) { panic!() }
+        if !test_literal("??",  Token::Op("??".to_string()), 2)  { panic!() }
+        // lone built-ins are still built-in
+        if !test_literal("~",   Token::BitNot, 1)                { panic!() }
+        if !test_literal("?",   Token::Question, 1)              { panic!() }
+        // a trailing minus touching what follows is split off
+        if !test_literal("*-3", Token::Mul, 1)                   { panic!() }
+        if !test_literal("=-1", Token::Assign, 1)                { panic!() }
+        if !test_literal("<>-x", Token::Op("<>".to_string()), 2) { panic!() }
+        if !test_literal("<- x", Token::Op("<-".to_string()), 2) { panic!() }
+
+        // a trailing `~` or leading `?` is split off a run that isn't an operator
+        let none = Operators::new();
+        let int = |n| Token::Integer(Data::Integer(n));
+        assert_eq!(test_split("2*~3", &none), vec![int(2), Token::Mul, Token::BitNot, int(3), Token::End]);
+        assert_eq!(test_split("x=~1", &none), vec![Token::Symbol, Token::Assign, Token::BitNot, int(1), Token::End]);
+        assert_eq!(test_split("~~x",  &none), vec![Token::BitNot, Token::BitNot, Token::Symbol, Token::End]);
+        assert_eq!(test_split("x?+1", &none), vec![Token::Symbol, Token::Question, Token::Add, int(1), Token::End]);
+        assert_eq!(test_split("x??",  &none), vec![Token::Symbol, Token::Question, Token::Question, Token::End]);
+        // but not off of a registered operator, or one that stays unknown
+        let mut negate = Operators::new();
+        negate.add("~~", Operator::new(Prec::Call, Assoc::Prefix, "negate")).unwrap();
+        assert_eq!(test_split("~~x",  &negate), vec![Token::Op("~~".to_string()), Token::Symbol, Token::End]);
+        assert_eq!(test_split("a<~>b", &none), vec![Token::Symbol, Token::Op("<~>".to_string()), Token::Symbol, Token::End]);
+    }
+
+    #[test]
+    fn split_spans() {
+        let source = Source::source("x?+~1");
+        let tokens = split_operators(lex(source.clone()).unwrap(), &Operators::new());
+        assert_eq!(tokens[1], Spanned::new(Token::Question, Span::new(&source, 1, 1)));
+        assert_eq!(tokens[2], Spanned::new(Token::Add,      Span::new(&source, 2, 1)));
+        assert_eq!(tokens[3], Spanned::new(Token::BitNot,   Span::new(&source, 3, 1)));
+    }
+
+    #[test]
+    fn builtin_operators() {
+        // the glyphs runs are split into lex to the same tokens
+        for glyph in &["=", "->", "?", "+", "-", "*", "/", "%", "==", "<", ">", "<=", ">=", "<<", ">>", "&", "|", "~"] {
+            let lexed = Lexer::new(&Source::source(glyph)).step().unwrap();
+            assert_eq!(builtin_operator(glyph), Some(lexed.0), "while lexing '{}'", glyph);
+            assert_eq!(lexed.1, glyph.len());
+        }
+        assert_eq!(builtin_operator("<>"), None);
+    }
+
     #[test]
     fn comma() {
         let source = Source::source("heck\\ man");
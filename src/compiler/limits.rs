@@ -0,0 +1,42 @@
+/// Limits on the size of the input to the compiler,
+/// to guard against resource exhaustion when compiling untrusted source.
+/// A limit of `None` means unlimited, which is the default.
+/// Exceeding a limit is reported as a `Syntax` error.
+/// Only bytes and tokens are counted;
+/// neither the number of `AST` nodes nor how deeply they nest is limited.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Limits {
+    /// The maximum length of a source file, in bytes.
+    pub source_bytes: Option<usize>,
+    /// The maximum number of tokens, not counting the final `End` token.
+    pub tokens: Option<usize>,
+}
+
+impl Limits {
+    /// No limits at all.
+    pub fn unlimited() -> Limits {
+        Limits { source_bytes: None, tokens: None }
+    }
+
+    /// Returns an error message if the source is too long.
+    pub fn check_source(&self, bytes: usize) -> Result<(), String> {
+        match self.source_bytes {
+            Some(max) if bytes > max => Err(format!(
+                "The source is {} bytes long, but may be at most {} bytes long",
+                bytes, max,
+            )),
+            _ => Ok(()),
+        }
+    }
+
+    /// Returns an error message if there are too many tokens.
+    pub fn check_tokens(&self, tokens: usize) -> Result<(), String> {
+        match self.tokens {
+            Some(max) if tokens > max => Err(format!(
+                "The source may contain at most {} tokens",
+                max,
+            )),
+            _ => Ok(()),
+        }
+    }
+}
@@ -21,15 +21,19 @@ pub mod hoist;
 pub mod gen;
 
 pub mod token;
+pub mod operator; // user-defined infix operators
+pub mod limits; // input size limits
 pub mod ast; // high level pre-macro IR
 pub mod rule; // macro transformation
 pub mod cst; // post-macro IR
 pub mod sst; // hoisted IR
+pub mod format; // AST to source
 
 pub mod syntax;
 
-pub use lex::lex;
-pub use parse::parse;
+pub use lex::{lex, lex_recover, lex_with_limits, token_at, span_of};
+pub use parse::{parse, parse_with_operators, parse_with_warnings, parse_with_limits, parse_recover, parse_incremental, reparse, node_at};
 pub use desugar::desugar;
-pub use hoist::hoist;
-pub use gen::gen;
+pub use hoist::{hoist, hoist_with_prelude};
+pub use gen::{gen, compile};
+pub use format::{format, pretty};
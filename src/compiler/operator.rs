@@ -0,0 +1,75 @@
+use std::collections::HashMap;
+
+use crate::compiler::parse::Prec;
+
+#[cfg(feature = "serde")]
+use serde::Serialize;
+
+/// The direction in which a chain of the same infix operator groups.
+/// `a <> b <> c` is `(a <> b) <> c` when left-associative,
+/// and `a <> (b <> c)` when right-associative.
+/// A prefix operator isn't infix at all, `<> a` is a call with one operand.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+pub enum Assoc {
+    Left,
+    Right,
+    Prefix,
+}
+
+/// A user-defined infix operator.
+/// When parsed, `a <op> b` becomes a call to `function`,
+/// i.e. the form `function a b`.
+/// A prefix operator ignores `prec`, as it binds as tightly as a call.
+/// An operator without a `function` only has a fixity:
+/// either it's a built-in operator, which calls its own function,
+/// or it was declared without naming one, and can't be used.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Operator {
+    pub prec:     Prec,
+    pub assoc:    Assoc,
+    pub function: Option<String>,
+}
+
+impl Operator {
+    /// Creates a new operator that calls `function` with both operands.
+    pub fn new(prec: Prec, assoc: Assoc, function: &str) -> Operator {
+        Operator { prec, assoc, function: Some(function.to_string()) }
+    }
+
+    /// Creates a new operator with a fixity, but no function to call.
+    pub fn fixity(prec: Prec, assoc: Assoc) -> Operator {
+        Operator { prec, assoc, function: None }
+    }
+}
+
+/// A table of operators, mapping glyphs (like `++` or `<>`) to `Operator`s,
+/// passed to the parser to extend the set of infix operators.
+/// Built-in operators, like `+` or `==`, are always available,
+/// and always call their own function,
+/// but their fixity may be declared under their glyph, see `Parser::fixities`.
+#[derive(Debug, Clone, Default)]
+pub struct Operators(HashMap<String, Operator>);
+
+impl Operators {
+    /// Creates a new empty operator table.
+    pub fn new() -> Operators {
+        Operators(HashMap::new())
+    }
+
+    /// Registers an operator under a glyph.
+    /// Returns an error if the glyph has already been registered.
+    pub fn add(&mut self, glyph: &str, operator: Operator) -> Result<(), String> {
+        if self.0.contains_key(glyph) {
+            return Err(format!("The operator '{}' has already been defined", glyph));
+        }
+
+        self.0.insert(glyph.to_string(), operator);
+        Ok(())
+    }
+
+    /// Returns the `Operator` registered under the provided glyph.
+    pub fn get(&self, glyph: &str) -> Option<&Operator> {
+        self.0.get(glyph)
+    }
+}
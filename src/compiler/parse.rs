@@ -1,28 +1,210 @@
 use std::{
     mem,
+    rc::Rc,
+    ops::Range,
     convert::TryFrom,
 };
 
 use crate::common::{
+    source::Source,
     span::{Span, Spanned},
     data::Data,
 };
 
 use crate::compiler::{
-    syntax::Syntax,
+    syntax::{Syntax, Severity},
     token::Token,
-    ast::{AST, ASTPattern, ArgPattern},
+    lex::split_operators,
+    ast::{AST, ASTPattern, ArgPattern, ASTType, ASTImport, Attribute},
+    operator::{Operators, Operator, Assoc},
+    limits::Limits,
 };
 
+/// The name of the parameter of a function literal, see `Parser::function`.
+/// Symbols can't contain a `#`, so it never shadows a user's binding.
+pub const FUNCTION_ARGUMENT: &str = "#arg";
+
+/// The parameter of a function made from parens containing a placeholder,
+/// i.e. `(# + 1)` is `(# -> # + 1)`, see `Parser::group`.
+/// The placeholder is lexed as its own token, so it never clashes with a symbol.
+pub const PLACEHOLDER: &str = "#";
+
 /// Simple function that parses a token stream into an AST.
 /// Exposes the functionality of the `Parser`.
 pub fn parse(tokens: Vec<Spanned<Token>>) -> Result<Spanned<AST>, Syntax> {
-    let mut parser = Parser::new(tokens);
-    let ast = parser.body(Token::End)?;
-    parser.consume(Token::End)?;
+    return parse_with_operators(tokens, Operators::new());
+}
+
+/// Parses a token stream into an AST,
+/// given a table of user-defined infix operators.
+/// Note that the built-in operators are always available,
+/// See `Operators` for more.
+pub fn parse_with_operators(
+    tokens: Vec<Spanned<Token>>,
+    operators: Operators,
+) -> Result<Spanned<AST>, Syntax> {
+    let mut parser = Parser::with_operators(tokens, operators);
+    let ast = parser.root()?;
     return Ok(Spanned::new(ast, Span::empty()));
 }
 
+/// Parses a token stream into an AST like `parse`,
+/// but returns an error rather than parsing more tokens than the `Limits` allow.
+/// Only the tokens are counted, not the `AST` nodes they parse to.
+/// Use `lex_with_limits` to limit the size of the source as well.
+pub fn parse_with_limits(
+    tokens: Vec<Spanned<Token>>,
+    limits: Limits,
+) -> Result<Spanned<AST>, Syntax> {
+    // the last token is always `End`
+    let count = tokens.len().saturating_sub(1);
+    if let Err(e) = limits.check_tokens(count) {
+        let first_past = limits.tokens.unwrap_or(0);
+        return Err(Syntax::error(&e, &tokens[first_past].span));
+    }
+
+    return parse(tokens);
+}
+
+/// Parses a token stream into an AST like `parse`,
+/// also returning any warnings, e.g. a doc comment that documents nothing.
+/// Unlike errors, warnings do not stop compilation.
+pub fn parse_with_warnings(
+    tokens: Vec<Spanned<Token>>,
+) -> Result<(Spanned<AST>, Vec<Syntax>), Syntax> {
+    let mut parser = Parser::new(tokens);
+    let ast = parser.root()?;
+    return Ok((Spanned::new(ast, Span::empty()), parser.warnings));
+}
+
+/// Parses a token stream into an AST like `parse`,
+/// but rather than stopping at some errors, e.g. a missing right side of `x =`,
+/// reports them and leaves an `AST::Error` in place of what's missing,
+/// returning the AST along with all the errors, e.g. for editor diagnostics.
+/// The `Token::Error`s left by `lex_recover` are parsed as `AST::Error`s as well,
+/// without reporting them again.
+/// Errors that can't be recovered from are returned on their own.
+pub fn parse_recover(
+    tokens: Vec<Spanned<Token>>,
+) -> Result<(Spanned<AST>, Vec<Syntax>), Syntax> {
+    let mut parser = Parser::new(tokens);
+    parser.recovered = Some(vec![]);
+    let ast = parser.root()?;
+    return Ok((Spanned::new(ast, Span::empty()), parser.recovered.unwrap()));
+}
+
+/// Finds the innermost node at a byte offset, e.g. for hovering in an editor.
+/// The node with the smallest span containing the offset (see `Span::contains`)
+/// is chosen; ties go to the deepest node.
+/// Nodes with empty spans, like the root block, are searched through,
+/// but never chosen themselves.
+/// Patterns and types aren't `AST` nodes, so aren't searched.
+pub fn node_at(ast: &Spanned<AST>, offset: usize) -> Option<&Spanned<AST>> {
+    let containing = ast.span.contains(offset);
+    if !containing && !ast.span.is_empty() { return None; }
+
+    let mut best = if containing { Some(ast) } else { None };
+    for child in ast.item.children() {
+        if let Some(node) = node_at(child, offset) {
+            match best {
+                Some(b) if b.span.length < node.span.length => (),
+                _ => best = Some(node),
+            }
+        }
+    }
+
+    return best;
+}
+
+/// The outcome of parsing a token stream that may still be incomplete,
+/// e.g. a line typed into a REPL.
+#[derive(Debug, PartialEq)]
+pub enum Incremental {
+    /// The token stream parsed successfully.
+    Complete(Spanned<AST>),
+    /// The token stream ended inside an open construct, like `x = {`.
+    /// More input is needed; `consumed` tokens were parsed before running out.
+    Incomplete { consumed: usize },
+    /// The token stream is invalid, no matter what follows.
+    Error(Syntax),
+}
+
+/// Parses a token stream, distinguishing between input that is
+/// incomplete (i.e. the `End` token was reached in an open construct)
+/// and input that is definitely an error.
+/// Useful for a REPL, which may ask for another line to join
+/// rather than reporting an error.
+pub fn parse_incremental(tokens: Vec<Spanned<Token>>) -> Incremental {
+    let mut parser = Parser::new(tokens);
+
+    let result = parser.root();
+
+    return match result {
+        Ok(ast) => Incremental::Complete(Spanned::new(ast, Span::empty())),
+        Err(_) if parser.draw().item == Token::End => {
+            Incremental::Incomplete { consumed: parser.index }
+        },
+        Err(error) => Incremental::Error(error),
+    };
+}
+
+/// Parses a token stream after an edit to its source,
+/// reusing the statements of the `AST` from before the edit that it couldn't have changed.
+/// `edit` is the range of the old source that was replaced,
+/// `tokens` are those of the whole new source,
+/// and `old` must have been parsed by `parse`.
+/// Statements are reparsed from the one before the edit,
+/// until one lines up with a statement after the edit;
+/// the statements after that are reused, moved by the change in length.
+/// If nothing can be reused, e.g. because of a syntax error,
+/// the whole token stream is parsed,
+/// so the result (or error) is always the same as that of `parse`.
+/// Fixity declarations change how every statement is parsed,
+/// so if the old or new source has any, nothing is reused.
+///
+/// Reused statements are moved out of `old` rather than copied,
+/// so their nodes are the very same allocations;
+/// only their spans are updated in place, to point into the new source.
+pub fn reparse(
+    old: Spanned<AST>,
+    tokens: Vec<Spanned<Token>>,
+    edit: Range<usize>,
+) -> Result<Spanned<AST>, Syntax> {
+    let declared = (0..tokens.len()).any(|i| Parser::fixity_len(&tokens[i..]).is_some());
+    if declared || declares_fixity(&old.item) {
+        return parse(tokens);
+    }
+
+    let mut parser = Parser::new(split_operators(tokens, &Operators::new()));
+    if let Some(ast) = parser.reuse(old.item, &edit) {
+        return Ok(Spanned::new(ast, Span::empty()));
+    }
+
+    return parse(parser.tokens);
+}
+
+/// Whether a parsed `AST` has a fixity declaration,
+/// which may only be a statement at the top level, see `Parser::fixities`.
+fn declares_fixity(ast: &AST) -> bool {
+    return match ast {
+        AST::Block(statements) => statements.iter().any(|s| matches!(s.item, AST::Fixity { .. })),
+        _ => false,
+    };
+}
+
+/// Moves a statement along by some number of bytes, into another source,
+/// updating every span in it in place.
+fn move_statement(statement: &mut Spanned<AST>, source: &Rc<Source>, by: isize) {
+    let f = |span: &mut Span| *span = moved_span(span, source, by);
+    f(&mut statement.span);
+    statement.item.respan(&f);
+}
+
+fn moved_span(span: &Span, source: &Rc<Source>, by: isize) -> Span {
+    if span.is_empty() { return span.clone(); }
+    return Span::new(source, (span.offset as isize + by) as usize, span.length);
+}
+
 /// We're using a Pratt parser, so this little enum
 /// defines different precedence levels.
 /// Each successive level is higher, so, for example,
@@ -37,9 +219,14 @@ pub enum Prec {
 
     Logic,
 
+    BitOr,
+    BitAnd,
+    Shift,
+
     AddSub,
     MulDiv,
 
+    Try, // postfix `?`
     Compose, // TODO: where should this be, precedence-wise?
     Call,
     End,
@@ -58,21 +245,73 @@ impl Prec {
         if let Prec::End = self { panic!("Can not associate further left") }
         return unsafe { mem::transmute(self.clone() as u8 + 1) };
     }
+
+    /// The precedence of a fixity declaration's level, from 0 to 9,
+    /// where `+` is at 6 and `*` is at 7, as in Haskell.
+    /// There are fewer precedences than levels,
+    /// so levels past either end share a precedence.
+    /// Returns `None` if the level is out of range.
+    pub fn from_level(level: u8) -> Option<Prec> {
+        let prec = match level {
+            0..=2 => Prec::Logic,
+            3     => Prec::BitOr,
+            4     => Prec::BitAnd,
+            5     => Prec::Shift,
+            6     => Prec::AddSub,
+            7..=9 => Prec::MulDiv,
+            _     => return None,
+        };
+        return Some(prec);
+    }
+}
+
+/// A slice of the tokens left to parse, see `peek` and `advance`.
+type Tokens<'a> = &'a [Spanned<Token>];
+
+/// Returns the first of some tokens, if there are any.
+fn peek<'a>(tokens: Tokens<'a>) -> Option<&'a Spanned<Token>> {
+    return tokens.first();
+}
+
+/// Splits the first token off of some tokens,
+/// returning it, if there is one, and the tokens after it.
+fn advance<'a>(tokens: Tokens<'a>) -> (Option<&'a Spanned<Token>>, Tokens<'a>) {
+    return match tokens.split_first() {
+        Some((first, rest)) => (Some(first), rest),
+        None => (None, tokens),
+    };
 }
 
 /// Constructs an `AST` from a token stream.
 /// Note that this struct should not be controlled manually,
 /// use the `parse` function instead.
+/// All parsing state lives here and is passed to each rule through `self`,
+/// there are no globals, so parsing is reentrant,
+/// and separate parsers may run on separate threads.
 #[derive(Debug)]
 pub struct Parser {
-    tokens: Vec<Spanned<Token>>,
-    index:  usize,
+    tokens:    Vec<Spanned<Token>>,
+    index:     usize,
+    /// User-defined infix operators.
+    operators: Operators,
+    /// Problems that do not stop parsing.
+    warnings:  Vec<Syntax>,
+    /// Whether the pattern of a function arm is being parsed,
+    /// in which case an `if` starts the arm's guard.
+    in_arm:    bool,
+    /// Errors that were recovered from, if recovering, see `parse_recover`.
+    recovered: Option<Vec<Syntax>>,
 }
 
 impl Parser {
     /// Create a new `parser`.
     pub fn new(tokens: Vec<Spanned<Token>>) -> Parser {
-        Parser { tokens, index: 0 }
+        Parser::with_operators(tokens, Operators::new())
+    }
+
+    /// Create a new `parser` that recognizes some user-defined operators.
+    pub fn with_operators(tokens: Vec<Spanned<Token>>, operators: Operators) -> Parser {
+        Parser { tokens, index: 0, operators, warnings: vec![], in_arm: false, recovered: None }
     }
 
     // Cookie Monster's Helper Functions:
@@ -80,12 +319,11 @@ impl Parser {
     // NOTE: Maybe don't return bool?
     /// Consumes all seperator tokens, returning whether there were any.
     pub fn sep(&mut self) -> bool {
-        if self.tokens[self.index].item != Token::Sep { false } else {
-            while self.tokens[self.index].item == Token::Sep {
-                self.index += 1;
-            };
-            true
+        let start = self.index;
+        while let Some(Spanned { item: Token::Sep, .. }) = peek(self.remaining()) {
+            self.index += 1;
         }
+        return self.index != start;
     }
 
     // TODO: merge with sep?
@@ -101,6 +339,11 @@ impl Parser {
         return &self.tokens[self.index + offset];
     }
 
+    /// The tokens left to parse, starting with the current one.
+    pub fn remaining(&self) -> Tokens<'_> {
+        return self.tokens.get(self.index..).unwrap_or_default();
+    }
+
     /// Returns the current token then advances the parser.
     pub fn advance(&mut self) -> &Spanned<Token> {
         self.index += 1;
@@ -132,14 +375,13 @@ impl Parser {
     /// Consumes a specific token then advances the parser.
     /// Can be used to consume Sep tokens, which are normally skipped.
     pub fn consume(&mut self, token: Token) -> Result<&Spanned<Token>, Syntax> {
-        self.index += 1;
-        let current = &self.tokens[self.index - 1];
-        if current.item != token {
-            self.index -= 1;
-            Err(Syntax::error(&format!("Expected {}, found {}", token, current.item), &current.span))
-        } else {
-            Ok(current)
+        match peek(self.remaining()) {
+            Some(current) if current.item == token => (),
+            other => return Err(Parser::expected(&token.to_string(), other)),
         }
+
+        self.index += 1;
+        return Ok(&self.tokens[self.index - 1]);
     }
 
     // Core Pratt Parser:
@@ -150,18 +392,51 @@ impl Parser {
             Token::End         => Ok(Spanned::new(AST::Block(vec![]), Span::empty())),
 
             Token::Syntax      => self.syntax(),
+            Token::Type        => Err(Syntax::error(
+                "A type definition is only allowed as a statement",
+                &self.current().span,
+            )),
+            Token::Use
+            | Token::Import    => Err(Syntax::error(
+                "An import is only allowed as a statement",
+                &self.current().span,
+            )),
+            Token::Doc(_)      => Err(Syntax::error(
+                "A doc comment must be on its own line, before a binding",
+                &self.current().span,
+            )),
             Token::OpenParen   => self.group(),
             Token::OpenBracket => self.block(),
             Token::Symbol      => self.symbol(),
             Token::Print       => self.print(),
             Token::Magic       => self.magic(),
+            Token::Assert      => self.assert(),
             Token::Label       => self.label(),
             Token::Keyword(_)  => self.keyword(),
+            Token::BitNot      => self.bit_not(),
+            Token::Sub         => self.negate(),
+            Token::Op(_)       => self.prefix_operator(),
+            Token::Spread      => self.spread(),
+            Token::BlockLabel(_) => self.labeled_block(),
+            Token::Break       => self.break_(),
+            Token::Fn          => self.function(),
+            Token::Placeholder => self.placeholder(),
+            Token::Quote       => self.quote(),
+            Token::Unquote     => Err(Syntax::error(
+                "An unquote is only allowed inside of a quote",
+                &self.current().span,
+            )),
+            Token::At          => Err(Syntax::error(
+                "An attribute is only allowed before a declaration",
+                &self.current().span,
+            )),
 
             Token::Unit
-            | Token::Number(_)
+            | Token::Integer(_)
+            | Token::Real(_)
             | Token::String(_)
             | Token::Boolean(_) => self.literal(),
+            Token::Error        => self.lex_error(),
 
             Token::Sep => unreachable!(),
             _          => Err(Syntax::error("Expected an expression", &self.current().span)),
@@ -173,8 +448,10 @@ impl Parser {
         match self.skip().item {
             Token::Assign  => self.assign(left),
             Token::Lambda  => self.lambda(left),
+            Token::Colon   => self.annotation(left),
             Token::Pair    => self.pair(left),
             Token::Compose => self.compose(left),
+            Token::Question => self.try_(left),
 
             Token::Add => self.add(left),
             Token::Sub => self.sub(left),
@@ -182,7 +459,18 @@ impl Parser {
             Token::Div => self.div(left),
             Token::Rem => self.remainder(left),
 
-            Token::Equal => self.equal(left),
+            Token::Equal        => self.equal(left),
+            Token::Less         => self.less(left),
+            Token::Greater      => self.greater(left),
+            Token::LessEqual    => self.less_equal(left),
+            Token::GreaterEqual => self.greater_equal(left),
+
+            Token::Shl    => self.shl(left),
+            Token::Shr    => self.shr(left),
+            Token::BitAnd => self.bit_and(left),
+            Token::BitOr  => self.bit_or(left),
+
+            Token::Op(_) => self.operator(left),
 
             Token::End => Err(self.unexpected()),
             Token::Sep => unreachable!(),
@@ -192,18 +480,47 @@ impl Parser {
 
     /// Looks at the current operator token and determines the precedence
     pub fn prec(&mut self) -> Result<Prec, Syntax> {
-        let next = self.draw().item.clone();
+        let Spanned { item: next, span } = self.draw().clone();
         let current = self.current().item.clone();
         let sep = next != current;
 
+        if Parser::is_builtin_infix(&next) {
+            if let Some(operator) = self.declared_infix(&span) { return Ok(operator.prec); }
+        }
+
         let prec = match next {
+            // ends the pattern of a guarded arm
+            Token::Symbol if self.in_arm && span.contents() == "if" => Prec::End,
+
             // infix
             Token::Assign  => Prec::Assign,
             Token::Lambda  => Prec::Lambda,
+            // binds like a lambda, so it can annotate any item of a tuple
+            Token::Colon   => Prec::Lambda,
             Token::Pair    => Prec::Pair,
             Token::Compose => Prec::Compose,
-
-            Token::Equal => Prec::Logic,
+            Token::Question => Prec::Try,
+
+              Token::Equal
+            | Token::Less
+            | Token::Greater
+            | Token::LessEqual
+            | Token::GreaterEqual => Prec::Logic,
+
+            Token::BitOr  => Prec::BitOr,
+            Token::BitAnd => Prec::BitAnd,
+              Token::Shl
+            | Token::Shr => Prec::Shift,
+
+            Token::Op(ref glyph) => match self.operators.get(glyph) {
+                // an operand, so it's an argument of a call
+                Some(Operator { assoc: Assoc::Prefix, .. }) => Prec::Call,
+                Some(operator) => operator.prec,
+                None => return Err(Syntax::error(
+                    &format!("Unknown operator '{}'", glyph),
+                    &span,
+                )),
+            },
 
               Token::Add
             | Token::Sub => Prec::AddSub,
@@ -215,21 +532,37 @@ impl Parser {
             // postfix
               Token::End
             | Token::CloseParen
-            | Token::CloseBracket => Prec::End,
+            | Token::CloseBracket
+            | Token::Doc(_) => Prec::End,
 
             // prefix
               Token::OpenParen
             | Token::OpenBracket
             | Token::Unit
             | Token::Syntax
+            | Token::Type
+            | Token::Use
+            | Token::Import
             | Token::Print
             | Token::Magic
+            | Token::Assert
             | Token::Symbol
             | Token::Keyword(_)
             | Token::Label
-            | Token::Number(_)
+            | Token::BitNot
+            | Token::Spread
+            | Token::Quote
+            | Token::Unquote
+            | Token::At
+            | Token::BlockLabel(_)
+            | Token::Break
+            | Token::Fn
+            | Token::Placeholder
+            | Token::Integer(_)
+            | Token::Real(_)
             | Token::String(_)
-            | Token::Boolean(_) => Prec::Call,
+            | Token::Boolean(_)
+            | Token::Error => Prec::Call,
 
             Token::Sep => unreachable!(),
         };
@@ -246,11 +579,13 @@ impl Parser {
     /// It's essentially a fold-left over tokens
     /// based on the precedence and content.
     /// Cool stuff.
+    /// Where separators are skipped, semicolons still aren't,
+    /// as they separate statements in parens, see `group`.
     pub fn expression(&mut self, prec: Prec, skip_sep: bool) -> Result<Spanned<AST>, Syntax> {
         let mut left = self.rule_prefix()?;
 
         while {
-            if skip_sep { self.sep(); }
+            if skip_sep && !self.semicolon() { self.sep(); }
             let p = self.prec()?;
             p >= prec && p != Prec::End
         } {
@@ -283,11 +618,15 @@ impl Parser {
 
     /// Constructs the AST for a literal, such as a number or string.
     pub fn literal(&mut self) -> Result<Spanned<AST>, Syntax> {
-        let Spanned { item: token, span } = self.advance();
+        let Spanned { item: token, span } = match peek(self.remaining()) {
+            Some(token) => token,
+            None => return Err(Syntax::error("Expected a literal, found end of source", &Span::empty())),
+        };
 
         let leaf = match token {
             Token::Unit       => AST::Data(Data::Unit),
-            Token::Number(n)  => AST::Data(n.clone()),
+            Token::Integer(n) => AST::Data(n.clone()),
+            Token::Real(n)    => AST::Data(n.clone()),
             Token::String(s)  => AST::Data(s.clone()),
             Token::Boolean(b) => AST::Data(b.clone()),
             unexpected => return Err(Syntax::error(
@@ -296,18 +635,465 @@ impl Parser {
             )),
         };
 
-        Ok(Spanned::new(leaf, span.clone()))
+        let literal = Spanned::new(leaf, span.clone());
+        self.index += 1;
+        Ok(literal)
+    }
+
+    /// Parses a `Token::Error`, left where `lex_recover` couldn't lex something.
+    /// The lexer has already reported it, so when recovering it's just an `AST::Error`.
+    pub fn lex_error(&mut self) -> Result<Spanned<AST>, Syntax> {
+        let span = self.consume(Token::Error)?.span.clone();
+        return match self.recovered {
+            Some(_) => Ok(Spanned::new(AST::Error, span)),
+            None    => Err(Syntax::error("Can not parse something that couldn't be lexed", &span)),
+        };
     }
 
     /// Constructs the ast for a group,
     /// i.e. an expression between parenthesis.
+    /// Several statements separated by semicolons, `(a; b)`,
+    /// are a block, evaluating to the last one, whereas `(a, b)` is a tuple.
+    /// Newlines inside parens are just whitespace, so they don't separate statements.
+    /// If the expression contains placeholders, `#`,
+    /// it becomes a function of them, so `(# + 1)` is `(# -> # + 1)`.
+    /// Every placeholder refers to the same parameter,
+    /// so `(# * #)` squares its argument - there are no numbered placeholders.
+    /// Placeholders belong to the innermost parens they're in,
+    /// so `(f (# + 1))` is `(f (# -> # + 1))`.
     pub fn group(&mut self) -> Result<Spanned<AST>, Syntax> {
         let start = self.consume(Token::OpenParen)?.span.clone();
-        let ast   = self.expression(Prec::None.associate_left(), true)?;
+        let mut ast = self.expression(Prec::None.associate_left(), true)?;
+
+        // statements separated by semicolons, like `(a; b)`,
+        // are a block that evaluates to the last one, like `{ a; b }`
+        if self.semicolon() {
+            let mut statements = vec![ast];
+            while self.semicolon() && self.skip().item != Token::CloseParen {
+                statements.push(self.expression(Prec::None.associate_left(), true)?);
+            }
+
+            // a trailing semicolon doesn't make a block, `(a;)` is `(a)`
+            ast = if statements.len() == 1 { statements.remove(0) } else {
+                let span = Spanned::build(&statements);
+                Spanned::new(AST::Block(statements), span)
+            };
+        }
+
         let end   = self.consume(Token::CloseParen)?.span.clone();
+
+        if let Some(placeholder) = Parser::find_placeholder(&ast) {
+            let parameter = Spanned::new(ASTPattern::Symbol(PLACEHOLDER.to_string()), placeholder.clone());
+            let span = ast.span.clone();
+            ast = Spanned::new(AST::lambda(parameter, ast), span);
+        }
+
         Ok(Spanned::new(AST::group(ast), Span::combine(&start, &end)))
     }
 
+    /// Whether the current token is a separator containing a semicolon,
+    /// rather than just newlines, see `group`.
+    fn semicolon(&self) -> bool {
+        let current = self.current();
+        return current.item == Token::Sep && current.span.contents().contains(';');
+    }
+
+    /// Parses a placeholder, `#`, which is only allowed inside of parens.
+    pub fn placeholder(&mut self) -> Result<Spanned<AST>, Syntax> {
+        let span = self.consume(Token::Placeholder)?.span.clone();
+        return Ok(Spanned::new(AST::Symbol(PLACEHOLDER.to_string()), span));
+    }
+
+    /// Returns the span of the first placeholder in an `AST`
+    /// that isn't already the parameter of a function made from parens.
+    fn find_placeholder(ast: &Spanned<AST>) -> Option<&Span> {
+        match &ast.item {
+            AST::Symbol(name) if name == PLACEHOLDER => Some(&ast.span),
+            AST::Lambda { pattern, .. }
+                if pattern.item == ASTPattern::Symbol(PLACEHOLDER.to_string()) => None,
+            other => other.children().into_iter().find_map(Parser::find_placeholder),
+        }
+    }
+
+    /// Parses a whole token stream, which is the body of a block ending in `End`.
+    pub fn root(&mut self) -> Result<AST, Syntax> {
+        self.fixities()?;
+        self.tokens = split_operators(mem::take(&mut self.tokens), &self.operators);
+        let ast = self.body(Token::End)?;
+        self.consume(Token::End)?;
+
+        if let AST::Block(statements) = &ast {
+            for statement in statements {
+                Parser::check_statement(statement)?;
+            }
+        }
+
+        return Ok(ast);
+    }
+
+    /// Registers the operators of every fixity declaration, like `infixl 6 (<>) join`,
+    /// before anything is parsed, so operators may be used anywhere in the source.
+    /// A declared operator calls the function the declaration names, i.e. `a <> b` is `join a b`.
+    /// A built-in operator may be declared too, like `infixr 6 (-)`,
+    /// changing its precedence and associativity but not the function it calls.
+    /// Of the built-in operators, only `-` and `~` may be declared prefix,
+    /// and declaring `prefix (-)` makes `-x` a negation.
+    /// Operators aren't scoped, so declarations are only allowed at the top level,
+    /// where they apply to the whole source.
+    /// Returns an error if an operator is declared twice,
+    /// was already in the table the parser was created with,
+    /// or is declared inside of a block or group.
+    pub fn fixities(&mut self) -> Result<(), Syntax> {
+        let mut depth: usize = 0;
+
+        for index in 0..self.tokens.len() {
+            let start = index == 0 || matches!(
+                self.tokens[index - 1].item,
+                Token::Sep | Token::OpenBracket,
+            );
+
+            match self.tokens[index].item {
+                Token::OpenBracket | Token::OpenParen => depth += 1,
+                Token::CloseBracket | Token::CloseParen => depth = depth.saturating_sub(1),
+                _ => (),
+            }
+
+            if !start || Parser::fixity_len(&self.tokens[index..]).is_none() { continue; }
+
+            if depth > 0 {
+                return Err(Syntax::error(
+                    "A fixity declaration is only allowed at the top level",
+                    &self.tokens[index].span,
+                ).with_help("Move it out of the block, operators are declared for the whole source"));
+            }
+
+            let (span, declared) = Parser::declared(&self.tokens[index..])?;
+            let (assoc, level, glyph, function) = match declared {
+                AST::Fixity { assoc, level, operator, function } => (assoc, level, operator, function),
+                _ => unreachable!("Expected a fixity declaration"),
+            };
+            let prec = match level {
+                Some(l) => Prec::from_level(l).expect("Checked the level"),
+                None    => Prec::Call,
+            };
+            let operator = match function {
+                Some(function) => Operator::new(prec, assoc, &function),
+                None           => Operator::fixity(prec, assoc),
+            };
+
+            self.operators.add(&glyph, operator)
+                .map_err(|message| Syntax::error(&message, &span))?;
+        }
+
+        return Ok(());
+    }
+
+    /// Returns the number of tokens in the fixity declaration
+    /// at the start of `tokens`, if there is one.
+    /// Built-in operators and missing function names are recognized too,
+    /// so they can be reported.
+    fn fixity_len(tokens: Tokens) -> Option<usize> {
+        let (keyword, rest) = advance(tokens);
+        let keyword = keyword.filter(|k| k.item == Token::Symbol)?;
+
+        let rest = match keyword.span.contents().as_str() {
+            "infixl" | "infixr" => match advance(rest) {
+                (Some(Spanned { item: Token::Integer(_), .. }), rest) => rest,
+                _ => return None,
+            },
+            "prefix" => rest,
+            _ => return None,
+        };
+
+        let (open, rest)     = advance(rest);
+        let (operator, rest) = advance(rest);
+        let (close, rest)    = advance(rest);
+        let shaped = open?.item == Token::OpenParen
+            && Parser::is_operator(&operator?.item)
+            && close?.item == Token::CloseParen;
+        if !shaped { return None; }
+
+        let rest = match advance(rest) {
+            (Some(Spanned { item: Token::Symbol, .. }), after) => after,
+            _ => rest,
+        };
+        return Some(tokens.len() - rest.len());
+    }
+
+    /// Whether a token is a built-in infix operator that calls out to FFI,
+    /// the fixity of which may be declared, see `fixities`.
+    fn is_builtin_infix(token: &Token) -> bool {
+        matches!(token,
+            Token::Add | Token::Sub
+            | Token::Mul | Token::Div | Token::Rem
+            | Token::Equal
+            | Token::Less | Token::Greater
+            | Token::LessEqual | Token::GreaterEqual
+            | Token::Shl | Token::Shr
+            | Token::BitAnd | Token::BitOr
+        )
+    }
+
+    /// The declared fixity of the built-in infix operator at a span, if any.
+    fn declared_infix(&self, span: &Span) -> Option<&Operator> {
+        self.operators.get(&span.contents()).filter(|o| o.assoc != Assoc::Prefix)
+    }
+
+    /// The error for expecting something, like a token or a fixity declaration,
+    /// pointing at the token found instead, if there is one.
+    fn expected(what: &str, found: Option<&Spanned<Token>>) -> Syntax {
+        match found {
+            Some(found) => Syntax::error(&format!("Expected {}, found {}", what, found.item), &found.span),
+            None => Syntax::error(&format!("Expected {}, found {}", what, Token::End), &Span::empty()),
+        }
+    }
+
+    /// Whether a token is an infix or prefix operator, built-in or not.
+    fn is_operator(token: &Token) -> bool {
+        matches!(token,
+            Token::Op(_)
+            | Token::Add | Token::Sub
+            | Token::Mul | Token::Div | Token::Rem
+            | Token::Equal
+            | Token::Less | Token::Greater
+            | Token::LessEqual | Token::GreaterEqual
+            | Token::Shl | Token::Shr
+            | Token::BitAnd | Token::BitOr | Token::BitNot
+            | Token::Compose
+        )
+    }
+
+    /// Reads the fixity declaration at the start of `tokens`, see `fixity_len`,
+    /// returning the span of its operator and the `AST::Fixity` it declares.
+    fn declared(tokens: Tokens) -> Result<(Span, AST), Syntax> {
+        let (keyword, rest) = match advance(tokens) {
+            (Some(keyword), rest) if keyword.item == Token::Symbol => (keyword, rest),
+            (other, _) => return Err(Parser::expected("a fixity declaration", other)),
+        };
+
+        let (assoc, level, rest) = match (keyword.span.contents().as_str(), advance(rest)) {
+            ("infixl", (level, rest)) => (Assoc::Left, Some(level), rest),
+            ("infixr", (level, rest)) => (Assoc::Right, Some(level), rest),
+            ("prefix", _)             => (Assoc::Prefix, None, rest),
+            _ => return Err(Parser::expected("a fixity declaration", Some(keyword))),
+        };
+
+        let level = match level {
+            None => None,
+            Some(Some(Spanned { item: Token::Integer(Data::Integer(n)), .. }))
+                if u8::try_from(*n).ok().and_then(Prec::from_level).is_some() => Some(*n as u8),
+            Some(Some(Spanned { span, .. })) => return Err(Syntax::error(
+                "The precedence of an operator must be from 0 to 9",
+                span,
+            )),
+            Some(None) => return Err(Parser::expected("the precedence of the operator", None)),
+        };
+
+        let rest = match advance(rest) {
+            (Some(Spanned { item: Token::OpenParen, .. }), rest) => rest,
+            (other, _) => return Err(Parser::expected("an open paren", other)),
+        };
+        let (operator, rest) = match advance(rest) {
+            (Some(operator), rest) if Parser::is_operator(&operator.item) => (operator, rest),
+            (other, _) => return Err(Parser::expected("an operator", other)),
+        };
+        let glyph = operator.span.contents();
+        let rest = match advance(rest) {
+            (Some(Spanned { item: Token::CloseParen, .. }), rest) => rest,
+            (other, _) => return Err(Parser::expected("a close paren", other)),
+        };
+
+        let function = match peek(rest) {
+            Some(Spanned { item: Token::Symbol, span }) => Some(span),
+            _ => None,
+        };
+
+        if !matches!(operator.item, Token::Op(_)) {
+            let (declarable, fixity) = match assoc {
+                Assoc::Prefix => (matches!(operator.item, Token::Sub | Token::BitNot), "prefix"),
+                _             => (Parser::is_builtin_infix(&operator.item), "infix"),
+            };
+            if !declarable {
+                return Err(Syntax::error(
+                    &format!("The built-in operator '{}' can not be declared {}", glyph, fixity),
+                    &operator.span,
+                ).with_help("Declare a new operator instead, like `infixl 6 (<+>) concat`"));
+            }
+            if let Some(span) = function {
+                return Err(Syntax::error(
+                    &format!("The built-in operator '{}' always calls its own function", glyph),
+                    span,
+                ).with_help(&format!("Remove the name of the function, i.e. `({})`", glyph)));
+            }
+        }
+
+        let function = function.map(Span::contents);
+        let declared = AST::fixity(assoc, level, &glyph, function.as_deref());
+        return Ok((operator.span.clone(), declared));
+    }
+
+    /// Parses a fixity declaration, like `infixr 5 (^^) power`,
+    /// the operator of which was registered before parsing, see `fixities`.
+    pub fn fixity(&mut self) -> Result<Spanned<AST>, Syntax> {
+        let len = Parser::fixity_len(&self.tokens[self.index..])
+            .expect("Expected a fixity declaration");
+        let (_, declared) = Parser::declared(&self.tokens[self.index..])?;
+
+        let start = self.current().span.clone();
+        self.index += len;
+        let end = &self.tokens[self.index - 1].span;
+
+        let span = Span::combine(&start, end);
+        return Ok(Spanned::new(declared, span));
+    }
+
+    /// Converts an expression that turned out to be a pattern, like the left side of `=`.
+    /// A placeholder can't be a parameter, only the parens containing it make a function.
+    fn pattern(left: Spanned<AST>) -> Result<Spanned<ASTPattern>, Syntax> {
+        if let Some(placeholder) = Parser::find_placeholder(&left) {
+            return Err(Syntax::error(
+                "A placeholder can not be used in a pattern, parens containing it become a function of it, like (# + 1)",
+                placeholder,
+            ));
+        }
+
+        let left_span = left.span.clone();
+        return left.map(ASTPattern::try_from)
+            .map_err(|e| Syntax::error(&e, &left_span));
+    }
+
+    /// Checks what can only be checked once a top-level statement is parsed,
+    /// i.e. that spreads and placeholders are where they're allowed.
+    fn check_statement(statement: &Spanned<AST>) -> Result<(), Syntax> {
+        Parser::check_spreads(statement, false)?;
+        if let Some(placeholder) = Parser::find_placeholder(statement) {
+            return Err(Syntax::error(
+                "A placeholder must be inside of parens, which become a function of it, like (# + 1)",
+                placeholder,
+            ));
+        }
+        return Ok(());
+    }
+
+    /// Parses the statements of a source that an edit could have changed,
+    /// reusing the rest from the `AST` of the source before the edit, see `reparse`.
+    /// Returns `None` if the statements can't be reused,
+    /// e.g. if there's a syntax error.
+    fn reuse(&mut self, old: AST, edit: &Range<usize>) -> Option<AST> {
+        let mut statements = match old {
+            AST::Block(statements) if !statements.is_empty() => statements,
+            _ => return None,
+        };
+
+        let source = self.tokens.iter().find_map(|t| t.span.source.clone())?;
+        let old_source = statements.iter().find_map(|s| s.span.source.clone())?;
+        if edit.start > edit.end || edit.end > old_source.contents.len() { return None; }
+        let delta = source.contents.len() as isize - old_source.contents.len() as isize;
+
+        // the last statement before the edit is reparsed,
+        // as it may continue onto the next line, e.g. `a\n+ b`
+        let before = statements.iter().take_while(|s| s.span.end() < edit.start).count();
+        let kept = before.saturating_sub(1);
+
+        // everything after the kept statements, including doc comments, is reparsed
+        let start = if kept == 0 { 0 } else { statements[kept - 1].span.end() };
+        self.index = self.tokens.iter()
+            .position(|t| t.item == Token::End || t.span.offset >= start)?;
+
+        // until a statement is found where it was before the edit,
+        // after which the old statements are moved along
+        let after = statements.iter().position(|s| s.span.offset > edit.end)
+            .unwrap_or(statements.len());
+        let mut rest = statements.split_off(after);
+        statements.truncate(kept);
+
+        let mut result = statements;
+        for statement in result.iter_mut() { move_statement(statement, &source, 0); }
+
+        while self.skip().item != Token::End {
+            let statement = match self.statement(&Token::End).ok()? {
+                Some(statement) => statement,
+                None => break,
+            };
+            Parser::check_statement(&statement).ok()?;
+
+            let synced = rest.iter()
+                .position(|s| moved_span(&s.span, &source, delta) == statement.span);
+            result.push(statement);
+
+            if let Some(index) = synced {
+                for mut statement in rest.drain(index + 1..) {
+                    move_statement(&mut statement, &source, delta);
+                    result.push(statement);
+                }
+                return Some(AST::Block(result));
+            }
+
+            if self.consume(Token::Sep).is_err() { break; }
+        }
+
+        self.consume(Token::End).ok()?;
+        return Some(AST::Block(result));
+    }
+
+    /// Checks that spreads are only used as call arguments or tuple items.
+    /// `allowed` is true if `ast` is one of these.
+    fn check_spreads(ast: &Spanned<AST>, allowed: bool) -> Result<(), Syntax> {
+        let check = |a: &Spanned<AST>| Parser::check_spreads(a, false);
+
+        match &ast.item {
+            AST::Spread(_) if !allowed => return Err(Syntax::error(
+                "A spread is only allowed as a call argument or a tuple item",
+                &ast.span,
+            )),
+            AST::Spread(e) => check(e)?,
+
+            AST::Form(form) => {
+                check(&form[0])?;
+                for argument in &form[1..] { Parser::check_spreads(argument, true)?; }
+            },
+            AST::Tuple(tuple) => for item in tuple { Parser::check_spreads(item, true)?; },
+            // operators pass their operands to FFI as a tuple
+            AST::FFI { expression, .. } => match &expression.item {
+                AST::Tuple(operands) => for operand in operands { check(operand)?; },
+                _ => check(expression)?,
+            },
+
+            AST::Block(block) => for statement in block { check(statement)?; },
+            AST::Group(e)
+            | AST::Label(_, e)
+            | AST::Try(e)
+            | AST::LabeledBlock { block: e, .. }
+            | AST::Break { expression: e, .. }
+            | AST::Assign { expression: e, .. }
+            | AST::Lambda { expression: e, .. }
+            | AST::Syntax { expression: e, .. } => check(e)?,
+            AST::Composition { argument, function } => { check(argument)?; check(function)?; },
+            AST::Attributed { attributes, node } => {
+                for attribute in attributes {
+                    for argument in &attribute.item.arguments { check(argument)?; }
+                }
+                check(node)?;
+            },
+            AST::Assert { expression, message } => {
+                check(expression)?;
+                if let Some(m) = message { check(m)?; }
+            },
+
+            AST::Symbol(_)
+            | AST::Data(_)
+            | AST::CSTPattern(_)
+            | AST::ArgPattern(_)
+            | AST::TypeDef { .. }
+            | AST::Import(_)
+            | AST::Fixity { .. }
+            | AST::Quote(_)
+            | AST::Error => (),
+        }
+
+        return Ok(());
+    }
+
     /// Parses the body of a block.
     /// A block is one or more expressions, separated by separators.
     /// This is more of a helper function, as it serves as both the
@@ -316,8 +1102,11 @@ impl Parser {
         let mut expressions = vec![];
 
         while self.skip().item != end {
-            let ast = self.expression(Prec::None, false)?;
-            expressions.push(ast);
+            match self.statement(&end)? {
+                Some(ast) => expressions.push(ast),
+                None      => break,
+            }
+
             if let Err(_) = self.consume(Token::Sep) {
                 break;
             }
@@ -326,6 +1115,145 @@ impl Parser {
         return Ok(AST::Block(expressions));
     }
 
+    /// Parses a single statement of a body,
+    /// along with the doc comments and attributes before it.
+    /// Returns `None` if there's only a dangling doc comment before the `end`.
+    fn statement(&mut self, end: &Token) -> Result<Option<Spanned<AST>>, Syntax> {
+        let doc = self.doc();
+        if let Some(ref d) = doc {
+            if self.skip().item == *end {
+                self.dangling_doc(d);
+                return Ok(None);
+            }
+        }
+
+        let attributes = self.attributes()?;
+        if !attributes.is_empty() && self.skip().item == *end {
+            return Err(Parser::dangling_attributes(&attributes));
+        }
+
+        // type definitions and imports are statements, not expressions
+        let mut ast = match self.current().item {
+            Token::Type   => self.type_def()?,
+            Token::Symbol if Parser::fixity_len(&self.tokens[self.index..]).is_some() => self.fixity()?,
+            Token::Use    => self.use_()?,
+            Token::Import => self.import()?,
+            _             => self.expression(Prec::None, false)?,
+        };
+
+        if let Some(d) = doc {
+            match ast.item {
+                AST::Assign { ref mut doc, .. } => *doc = Some(d.item),
+                _ => self.dangling_doc(&d),
+            }
+        }
+
+        if !attributes.is_empty() {
+            ast = Parser::attributed(attributes, ast)?;
+        }
+
+        return Ok(Some(ast));
+    }
+
+    /// Collects consecutive doc comments, joining them line by line.
+    /// Returns `None` if there are no doc comments.
+    pub fn doc(&mut self) -> Option<Spanned<String>> {
+        let mut lines = vec![];
+        let mut spans = vec![];
+
+        while let Spanned { item: Token::Doc(line), span } = self.skip().clone() {
+            self.advance();
+            lines.push(line);
+            spans.push(span);
+        }
+
+        if lines.is_empty() { return None; }
+        return Some(Spanned::new(lines.join("\n"), Span::join(spans)));
+    }
+
+    /// Parses the attributes preceding a declaration, if there are any.
+    /// Attributes may be stacked, on one line or on lines of their own.
+    pub fn attributes(&mut self) -> Result<Vec<Spanned<Attribute>>, Syntax> {
+        let mut attributes = vec![];
+        while self.skip().item == Token::At {
+            attributes.push(self.attribute()?);
+        }
+        return Ok(attributes);
+    }
+
+    /// Parses a single attribute, `@name` or `@name(arguments)`.
+    /// Arguments must directly follow the name,
+    /// so `@name (a, b) = pair` is an attribute on a binding.
+    pub fn attribute(&mut self) -> Result<Spanned<Attribute>, Syntax> {
+        let start = self.consume(Token::At)?.span.clone();
+
+        let Spanned { item: token, span: name } = self.advance().clone();
+        if token != Token::Symbol {
+            return Err(Syntax::error(
+                &format!("Expected the name of an attribute after '@', found {}", token),
+                &name,
+            ));
+        }
+
+        let adjacent = self.current().span.offset == name.end();
+        let (arguments, end) = match self.current().item {
+            Token::Unit if adjacent => (vec![], self.advance().span.clone()),
+            Token::OpenParen if adjacent => {
+                let group = self.group()?;
+                let arguments = match group.item {
+                    AST::Group(inner) => match inner.item {
+                        AST::Tuple(items) => items,
+                        _ => vec![*inner],
+                    },
+                    _ => unreachable!("Expected a group"),
+                };
+                (arguments, group.span)
+            },
+            _ => (vec![], name.clone()),
+        };
+
+        let attribute = Attribute { name: name.contents(), arguments };
+        return Ok(Spanned::new(attribute, Span::combine(&start, &end)));
+    }
+
+    /// Attaches attributes to the declaration that follows them.
+    fn attributed(
+        attributes: Vec<Spanned<Attribute>>,
+        node: Spanned<AST>,
+    ) -> Result<Spanned<AST>, Syntax> {
+        match node.item {
+            AST::Assign { .. }
+            | AST::TypeDef { .. }
+            | AST::Syntax { .. } => (),
+            _ => return Err(Parser::dangling_attributes(&attributes)),
+        }
+
+        let spans = attributes.iter().map(|a| a.span.clone()).collect::<Vec<_>>();
+        let combined = Span::combine(&Span::join(spans), &node.span);
+        return Ok(Spanned::new(AST::attributed(attributes, node), combined));
+    }
+
+    /// The error for attributes that aren't followed by a declaration.
+    fn dangling_attributes(attributes: &[Spanned<Attribute>]) -> Syntax {
+        let spans = attributes.iter().map(|a| a.span.clone()).collect();
+        Syntax::error(
+            "An attribute must be followed by a declaration, like a binding or type definition",
+            &Span::join(spans),
+        )
+    }
+
+    /// Warns about a doc comment that isn't followed by a binding.
+    fn dangling_doc(&mut self, doc: &Spanned<String>) {
+        self.warnings.push(
+            Syntax::error(
+                "This doc comment is not followed by a binding, so it documents nothing",
+                &doc.span,
+            )
+            .with_help("Use a regular comment, '--', instead")
+            .with_severity(Severity::Warning)
+        );
+    }
+
     /// Parse a block as an expression,
     /// Building the appropriate `AST`.
     /// Just a body between curlies.
@@ -372,42 +1300,397 @@ impl Parser {
         return Ok(Spanned::new(AST::syntax(arg_pat, block), span));
     }
 
-    /// Parse a print statement.
-    /// A print statement takes the form `print <expression>`
-    /// Where expression is exactly one expression
-    /// Note that this is just a temporaty workaround;
-    /// Once the FFI is solidified, printing will be a function like any other.
-    pub fn print(&mut self) -> Result<Spanned<AST>, Syntax> {
-        let start = self.consume(Token::Print)?.span.clone();
-        let ast = self.expression(Prec::Call, false)?;
-        let end = ast.span.clone();
-
-        return Ok(
-            Spanned::new(AST::ffi("println", ast),
-            Span::combine(&start, &end))
-        );
-    }
-
-    /// Parse an `extern` statement.
-    /// used for compiler magic and other glue.
-    /// takes the form:
-    /// ```ignore
-    /// magic "FFI String Name" data_to_pass_out
-    /// ```
-    /// and evaluates to the value returned by the ffi function.
-    pub fn magic(&mut self) -> Result<Spanned<AST>, Syntax> {
-        let start = self.consume(Token::Magic)?.span.clone();
+    /// Parse a type definition.
+    /// `type`, followed by a Label, followed by `=`, followed by a type expression.
+    pub fn type_def(&mut self) -> Result<Spanned<AST>, Syntax> {
+        let start = self.consume(Token::Type)?.span.clone();
 
-        let Spanned { item: token, span } = self.advance();
-        let name = match token {
-            Token::String(Data::String(s))  => s.clone(),
-            unexpected => return Err(Syntax::error(
-                &format!("Expected a string, found {}", unexpected),
-                &span
+        let name = match self.current().clone() {
+            Spanned { item: Token::Label, span } => { self.advance(); span.contents() },
+            Spanned { item, span } => return Err(Syntax::error(
+                &format!("Expected a type name, found {}", item),
+                &span,
             )),
         };
 
-        let ast = self.expression(Prec::End, false)?;
+        self.consume(Token::Assign)?;
+        let type_expr = self.type_expr()?;
+        let combined  = Span::combine(&start, &type_expr.span);
+        return Ok(Spanned::new(AST::type_def(&name, type_expr), combined));
+    }
+
+    /// Parse a module reference by dotted path.
+    /// `use`, followed by one or more symbols separated by `.`,
+    /// e.g. `use foo.bar`.
+    pub fn use_(&mut self) -> Result<Spanned<AST>, Syntax> {
+        let start = self.consume(Token::Use)?.span.clone();
+        let mut path = vec![];
+        let mut end;
+
+        loop {
+            match self.current().clone() {
+                Spanned { item: Token::Symbol, span } => {
+                    self.advance();
+                    path.push(span.contents());
+                    end = span;
+                },
+                Spanned { item, span } => return Err(Syntax::error(
+                    &if path.is_empty() {
+                        format!("Expected a module path after 'use', found {}", item)
+                    } else {
+                        format!("Expected a module name after '.', found {}", item)
+                    },
+                    &span,
+                )),
+            }
+
+            if self.current().item != Token::Compose { break; }
+            self.advance();
+        }
+
+        let combined = Span::combine(&start, &end);
+        return Ok(Spanned::new(AST::import(ASTImport::Module(path)), combined));
+    }
+
+    /// Parse a module reference by file path.
+    /// `import`, followed by a non-empty string, e.g. `import "path"`.
+    pub fn import(&mut self) -> Result<Spanned<AST>, Syntax> {
+        let start = self.consume(Token::Import)?.span.clone();
+
+        let (path, end) = match self.current().clone() {
+            Spanned { item: Token::String(Data::String(p)), span } => { self.advance(); (p, span) },
+            Spanned { item, span } => return Err(Syntax::error(
+                &format!("Expected a file path after 'import', found {}", item),
+                &span,
+            )),
+        };
+
+        if path.is_empty() {
+            return Err(Syntax::error("The import path must not be empty", &end));
+        }
+
+        let combined = Span::combine(&start, &end);
+        return Ok(Spanned::new(AST::import(ASTImport::File(path.to_string())), combined));
+    }
+
+    /// Parses a type expression, i.e. the right side of a type definition.
+    /// Function types, like `A -> B`, associate right,
+    /// and bind looser than type application.
+    pub fn type_expr(&mut self) -> Result<Spanned<ASTType>, Syntax> {
+        let argument = self.type_apply()?;
+        if self.current().item != Token::Lambda { return Ok(argument); }
+
+        self.consume(Token::Lambda)?;
+        let result   = self.type_expr()?;
+        let combined = Span::combine(&argument.span, &result.span);
+        return Ok(Spanned::new(ASTType::function(argument, result), combined));
+    }
+
+    /// Parses a type application, like `List a`, which associates left.
+    fn type_apply(&mut self) -> Result<Spanned<ASTType>, Syntax> {
+        let mut left = self.type_atom()?;
+
+        while let Token::Label
+            | Token::Symbol
+            | Token::Unit
+            | Token::OpenParen = self.current().item
+        {
+            let right    = self.type_atom()?;
+            let combined = Span::combine(&left.span, &right.span);
+            left = Spanned::new(ASTType::apply(left, right), combined);
+        }
+
+        return Ok(left);
+    }
+
+    /// Parses a single type, i.e. a name,
+    /// or a type or tuple of types between parenthesis.
+    fn type_atom(&mut self) -> Result<Spanned<ASTType>, Syntax> {
+        let Spanned { item, span } = self.current().clone();
+
+        let item = match item {
+            Token::Label | Token::Symbol => { self.advance(); ASTType::Name(span.contents()) },
+            Token::Unit => { self.advance(); ASTType::Tuple(vec![]) },
+            Token::OpenParen => return self.type_group(),
+            other => return Err(Syntax::error(
+                &format!("Expected a type, found {}", other),
+                &span,
+            )),
+        };
+
+        return Ok(Spanned::new(item, span));
+    }
+
+    /// Parses a type between parenthesis,
+    /// which is a tuple type if it contains a comma, like `(A, B)`.
+    fn type_group(&mut self) -> Result<Spanned<ASTType>, Syntax> {
+        let start = self.consume(Token::OpenParen)?.span.clone();
+        let mut items = vec![];
+        let mut tuple = false;
+
+        loop {
+            self.sep();
+            items.push(self.type_expr()?);
+            self.sep();
+            if self.consume(Token::Pair).is_err() { break; }
+
+            // allow a trailing comma
+            tuple = true;
+            if self.skip().item == Token::CloseParen { break; }
+        }
+
+        let end = self.consume(Token::CloseParen)?.span.clone();
+
+        return Ok(if tuple {
+            Spanned::new(ASTType::Tuple(items), Span::combine(&start, &end))
+        } else {
+            items.pop().unwrap()
+        });
+    }
+
+    /// Parse a print statement.
+    /// A print statement takes the form `print <expression>`
+    /// Where expression is exactly one expression
+    /// Note that this is just a temporaty workaround;
+    /// Once the FFI is solidified, printing will be a function like any other.
+    pub fn print(&mut self) -> Result<Spanned<AST>, Syntax> {
+        let start = self.consume(Token::Print)?.span.clone();
+        let ast = self.expression(Prec::Call, false)?;
+        let end = ast.span.clone();
+
+        return Ok(
+            Spanned::new(AST::ffi("println", ast),
+            Span::combine(&start, &end))
+        );
+    }
+
+    /// Parses a labeled block, `'name: { ... }`.
+    pub fn labeled_block(&mut self) -> Result<Spanned<AST>, Syntax> {
+        let Spanned { item: token, span: start } = self.advance().clone();
+        let label = match token {
+            Token::BlockLabel(label) => label,
+            _ => unreachable!("Expected a block label"),
+        };
+
+        if self.current().item != Token::OpenBracket {
+            return Err(Syntax::error(
+                &format!("Expected a block after the label '{}:", label),
+                &self.current().span,
+            ));
+        }
+
+        let block = self.block()?;
+        let combined = Span::combine(&start, &block.span);
+        return Ok(Spanned::new(AST::labeled_block(&label, block), combined));
+    }
+
+    /// Parses a function literal made of arms, `fn { pattern -> expression, ... }`.
+    /// Arms are separated by commas or newlines, and may be guarded, see `Parser::arm`.
+    /// This is a lambda that immediately matches on its argument,
+    /// so it's parsed as `arg -> match arg (arms...)`,
+    /// where `arg` is a fresh parameter,
+    /// and `match` is whatever is bound in scope, like `if`.
+    pub fn function(&mut self) -> Result<Spanned<AST>, Syntax> {
+        let start = self.consume(Token::Fn)?.span.clone();
+
+        if self.current().item != Token::OpenBracket {
+            return Err(Syntax::error(
+                "Expected the arms of the function in a block, like fn { x -> x }",
+                &self.current().span,
+            ));
+        }
+
+        let open = self.consume(Token::OpenBracket)?.span.clone();
+        let mut arms = vec![];
+        while self.skip().item != Token::CloseBracket {
+            arms.push(self.arm()?);
+            if self.consume(Token::Pair).is_err() && self.consume(Token::Sep).is_err() {
+                break;
+            }
+        }
+        let close = self.consume(Token::CloseBracket)?.span.clone();
+        let arms_span = Span::combine(&open, &close);
+
+        if arms.is_empty() {
+            return Err(Syntax::error("A function literal must have at least one arm", &arms_span));
+        }
+
+        // the generated nodes are attributed to the `fn` keyword
+        let argument = Spanned::new(ASTPattern::Symbol(FUNCTION_ARGUMENT.to_string()), start.clone());
+        let body = Spanned::new(
+            AST::Form(vec![
+                Spanned::new(AST::Symbol("match".to_string()), start.clone()),
+                Spanned::new(AST::Symbol(FUNCTION_ARGUMENT.to_string()), start.clone()),
+                Spanned::new(AST::Tuple(arms), arms_span.clone()),
+            ]),
+            arms_span.clone(),
+        );
+
+        let combined = Span::combine(&start, &arms_span);
+        return Ok(Spanned::new(AST::lambda(argument, body), combined));
+    }
+
+    /// Parses an arm of a function literal, `pattern -> expression`,
+    /// which may have a guard, `pattern if condition -> expression`.
+    /// `if` is otherwise just a symbol, so it only ends the pattern here.
+    fn arm(&mut self) -> Result<Spanned<AST>, Syntax> {
+        self.in_arm = true;
+        let left = self.expression(Prec::Lambda.associate_left(), false);
+        self.in_arm = false;
+        let left = left?;
+
+        let guard = if self.is_guard() {
+            self.advance();
+            Some(self.expression(Prec::Lambda.associate_left(), false)?)
+        } else {
+            None
+        };
+
+        if self.current().item != Token::Lambda {
+            let span = match &guard {
+                Some(guard) => Span::combine(&left.span, &guard.span),
+                None => left.span.clone(),
+            };
+            return Err(Syntax::error(
+                "Expected an arm of the function, like `pattern -> expression`",
+                &span,
+            ));
+        }
+
+        let mut pattern = Parser::pattern(left)?;
+        if let Some(guard) = guard {
+            let span = Span::combine(&pattern.span, &guard.span);
+            pattern = Spanned::new(ASTPattern::guarded(pattern, guard), span);
+        }
+
+        self.consume(Token::Lambda)?;
+        let expression = self.expression(Prec::Lambda, false)?;
+        let combined   = Span::combine(&pattern.span, &expression.span);
+        return Ok(Spanned::new(AST::lambda(pattern, expression), combined));
+    }
+
+    /// Whether the next token starts the guard of an arm, `if condition`.
+    fn is_guard(&self) -> bool {
+        let next = self.draw();
+        return next.item == Token::Symbol && next.span.contents() == "if";
+    }
+
+    /// Parses an early exit from a labeled block, `break 'name value`.
+    /// The value is parsed like the expression of a print statement.
+    pub fn break_(&mut self) -> Result<Spanned<AST>, Syntax> {
+        let start = self.consume(Token::Break)?.span.clone();
+
+        let label = match self.current().item.clone() {
+            Token::Keyword(label) => { self.advance(); label },
+            other => return Err(Syntax::error(
+                &format!("Expected the label of a block to break out of, like 'name, found {}", other),
+                &self.current().span,
+            )),
+        };
+
+        let ast = self.expression(Prec::Call, false)?;
+        let combined = Span::combine(&start, &ast.span);
+        return Ok(Spanned::new(AST::break_(&label, ast), combined));
+    }
+
+    /// Parses a bitwise not, i.e. `~x`, calls out to FFI.
+    pub fn bit_not(&mut self) -> Result<Spanned<AST>, Syntax> {
+        let start = self.consume(Token::BitNot)?.span.clone();
+        let ast = self.expression(Prec::Call, false)?;
+        let combined = Span::combine(&start, &ast.span);
+        return Ok(Spanned::new(AST::ffi("bit_not", ast), combined));
+    }
+
+    /// Parses a negation, i.e. `-x`, calls out to FFI.
+    /// There's no prefix minus unless it's declared, i.e. `prefix (-)`.
+    pub fn negate(&mut self) -> Result<Spanned<AST>, Syntax> {
+        let start = self.current().span.clone();
+        if !matches!(self.operators.get("-"), Some(Operator { assoc: Assoc::Prefix, .. })) {
+            return Err(Syntax::error("Expected an expression", &start)
+                .with_help("Declare a prefix minus to negate, i.e. `prefix (-)`"));
+        }
+
+        self.advance();
+        let ast = self.expression(Prec::Call, false)?;
+        let combined = Span::combine(&start, &ast.span);
+        return Ok(Spanned::new(AST::ffi("negate", ast), combined));
+    }
+
+    /// Parses a spread, i.e. `...xs`.
+    /// Like a label, a spread applies to a single expression,
+    /// so `f ...xs y` passes `y` as another argument.
+    pub fn spread(&mut self) -> Result<Spanned<AST>, Syntax> {
+        let start = self.consume(Token::Spread)?.span.clone();
+        let ast = self.expression(Prec::End, false)?;
+        let combined = Span::combine(&start, &ast.span);
+        return Ok(Spanned::new(AST::spread(ast), combined));
+    }
+
+    /// Parses a quasiquote, `` `( ... ) ``,
+    /// capturing the tokens between the parens without parsing them.
+    /// Parens within the quote must be balanced.
+    pub fn quote(&mut self) -> Result<Spanned<AST>, Syntax> {
+        let start = self.consume(Token::Quote)?.span.clone();
+        self.consume(Token::OpenParen)?;
+
+        let mut tokens = vec![];
+        let mut depth = 0;
+
+        let end = loop {
+            let token = self.current().clone();
+            match token.item {
+                Token::End => return Err(Syntax::error(
+                    "Expected a closing paren to end the quote, found end of source",
+                    &token.span,
+                )),
+                Token::CloseParen if depth == 0 => break token.span,
+                Token::CloseParen => depth -= 1,
+                Token::OpenParen  => depth += 1,
+                Token::Unquote => match self.tokens[self.index + 1].item {
+                    Token::Symbol
+                    | Token::OpenParen
+                    | Token::Unit
+                    | Token::Integer(_)
+                    | Token::Real(_)
+                    | Token::String(_)
+                    | Token::Boolean(_) => (),
+                    ref next => return Err(Syntax::error(
+                        &format!("Expected an expression to unquote, found {}", next),
+                        &token.span,
+                    )),
+                },
+                _ => (),
+            }
+
+            tokens.push(token);
+            self.advance();
+        };
+
+        self.advance();
+        let combined = Span::combine(&start, &end);
+        return Ok(Spanned::new(AST::quote(tokens), combined));
+    }
+
+    /// Parse an `extern` statement.
+    /// used for compiler magic and other glue.
+    /// takes the form:
+    /// ```ignore
+    /// magic "FFI String Name" data_to_pass_out
+    /// ```
+    /// and evaluates to the value returned by the ffi function.
+    pub fn magic(&mut self) -> Result<Spanned<AST>, Syntax> {
+        let start = self.consume(Token::Magic)?.span.clone();
+
+        let Spanned { item: token, span } = self.advance();
+        let name = match token {
+            Token::String(Data::String(s))  => s.clone(),
+            unexpected => return Err(Syntax::error(
+                &format!("Expected a string, found {}", unexpected),
+                &span
+            )),
+        };
+
+        let ast = self.expression(Prec::End, false)?;
         let end = ast.span.clone();
 
         return Ok(Spanned::new(
@@ -416,6 +1699,30 @@ impl Parser {
         ));
     }
 
+    /// Parse an assertion.
+    /// `assert`, followed by a condition,
+    /// optionally followed by a string message.
+    /// A condition followed by a message must be a single atom or a group,
+    /// e.g. `assert (x == 1) "x should be one"`,
+    /// otherwise a trailing string is an argument of the condition,
+    /// e.g. `assert starts_with name "a"`.
+    pub fn assert(&mut self) -> Result<Spanned<AST>, Syntax> {
+        let start = self.consume(Token::Assert)?.span.clone();
+        let ast = self.expression(Prec::Pair.associate_left(), false)?;
+        let combined = Span::combine(&start, &ast.span);
+
+        let (condition, message) = match ast.item {
+            AST::Form(mut form) if form.len() == 2
+            && matches!(form[1].item, AST::Data(Data::String(_))) => {
+                let message = form.pop().unwrap();
+                (form.pop().unwrap(), Some(message))
+            },
+            item => (Spanned::new(item, ast.span), None),
+        };
+
+        return Ok(Spanned::new(AST::assert(condition, message), combined));
+    }
+
     /// Parse a label.
     /// A label takes the form of `<Label> <expression>`
     pub fn label(&mut self) -> Result<Spanned<AST>, Syntax> {
@@ -454,28 +1761,50 @@ impl Parser {
 
     /// Parses an assignment, associates right.
     pub fn assign(&mut self, left: Spanned<AST>) -> Result<Spanned<AST>, Syntax> {
-        let left_span = left.span.clone();
-        let pattern = left.map(ASTPattern::try_from)
-            .map_err(|e| Syntax::error(&e, &left_span))?;
+        let pattern = Parser::pattern(left)?;
+
+        let equals = self.consume(Token::Assign)?.span.clone();
+
+        // `x =` with nothing after it would otherwise be assigned an empty block
+        let expression = if self.draw().item == Token::End {
+            let after = Span::point(equals.source.as_ref().unwrap(), equals.end());
+            let error = Syntax::error("Expected an expression after `=`", &after)
+                .with_help("Finish the assignment, like `x = 1`");
+            match &mut self.recovered {
+                Some(errors) => { errors.push(error); Spanned::new(AST::Error, after) },
+                None => return Err(error),
+            }
+        } else {
+            self.expression(Prec::Assign, false)?
+        };
 
-        self.consume(Token::Assign)?;
-        let expression = self.expression(Prec::Assign, false)?;
-        let combined   = Span::combine(&pattern.span, &expression.span);
-        Ok(Spanned::new(AST::assign(pattern, expression), combined))
+        let combined   = Span::combine(pattern.span(), expression.span());
+        Ok(Spanned::new(AST::assign(pattern, expression, None), combined))
     }
 
     /// Parses a lambda definition, associates right.
     pub fn lambda(&mut self, left: Spanned<AST>) -> Result<Spanned<AST>, Syntax> {
-        let left_span = left.span.clone();
-        let pattern = left.map(ASTPattern::try_from)
-            .map_err(|e| Syntax::error(&e, &left_span))?;
+        let pattern = Parser::pattern(left)?;
 
         self.consume(Token::Lambda)?;
         let expression = self.expression(Prec::Lambda, false)?;
-        let combined   = Span::combine(&pattern.span, &expression.span);
+        let combined   = Span::combine(pattern.span(), expression.span());
         Ok(Spanned::new(AST::lambda(pattern, expression), combined))
     }
 
+    /// Parses a type annotation on a pattern, `pattern: Type`,
+    /// e.g. the parameters of a lambda, `(x: Number, y) -> x`.
+    /// The type extends as far as it can, so `x: A -> B` is annotated with `A -> B`,
+    /// hence annotated parameters are parenthesized.
+    pub fn annotation(&mut self, left: Spanned<AST>) -> Result<Spanned<AST>, Syntax> {
+        let pattern = Parser::pattern(left)?;
+
+        self.consume(Token::Colon)?;
+        let annotation = self.type_expr()?;
+        let combined   = Span::combine(pattern.span(), &annotation.span);
+        Ok(Spanned::new(AST::CSTPattern(ASTPattern::typed(pattern, annotation)), combined))
+    }
+
     // TODO: trailing comma must be grouped
     /// Parses a pair operator, i.e. the comma used to build tuples: `a, b, c`.
     pub fn pair(&mut self, left: Spanned<AST>) -> Result<Spanned<AST>, Syntax> {
@@ -487,8 +1816,15 @@ impl Parser {
             _ => vec![left],
         };
 
+        // a trailing comma at the end of the source ends the tuple,
+        // rather than pairing with an empty block
         let index = self.index;
-        let span = if let Ok(item) = self.expression(Prec::Pair.associate_left(), false) {
+        let item = match self.draw().item {
+            Token::End => Err(self.unexpected()),
+            _ => self.expression(Prec::Pair.associate_left(), false),
+        };
+
+        let span = if let Ok(item) = item {
             let combined = Span::combine(&left_span, &item.span);
             tuple.push(item);
             combined
@@ -501,7 +1837,21 @@ impl Parser {
         return Ok(Spanned::new(AST::Tuple(tuple), span));
     }
 
-    /// Parses a function composition, i.e. `a . b`
+    /// Parses a postfix `?`, i.e. `a?`.
+    /// Binds looser than calls and compositions,
+    /// so `f x?` applies to the result of the call,
+    /// and `a?.b?` is `(a?.b)?`.
+    pub fn try_(&mut self, left: Spanned<AST>) -> Result<Spanned<AST>, Syntax> {
+        let end = self.consume(Token::Question)?.span.clone();
+        let combined = Span::combine(&left.span, &end);
+        return Ok(Spanned::new(AST::try_(left), combined));
+    }
+
+    /// Parses a function composition, i.e. `a . b`,
+    /// which applies `b` to `a`.
+    /// The right side is a whole call, so `x . f y` is `(f y) x`,
+    /// rather than a method call on `x`, like `f x y`,
+    /// and `x . f` is just `f x`.
     pub fn compose(&mut self, left: Spanned<AST>) -> Result<Spanned<AST>, Syntax> {
         self.consume(Token::Compose)?;
         let right = self.expression(Prec::Compose.associate_left(), false)?;
@@ -519,8 +1869,14 @@ impl Parser {
         name: &str,
         left: Spanned<AST>
     ) -> Result<Spanned<AST>, Syntax> {
-        self.consume(op)?;
-        let right = self.expression(prec.associate_left(), false)?;
+        // the operator's fixity may have been declared, see `fixities`
+        let span = self.consume(op)?.span.clone();
+        let prec = match self.declared_infix(&span) {
+            Some(Operator { assoc: Assoc::Right, prec, .. }) => *prec,
+            Some(operator) => operator.prec.associate_left(),
+            None => prec.associate_left(),
+        };
+        let right = self.expression(prec, false)?;
         let combined = Span::combine(&left.span, &right.span);
 
         let arguments = Spanned::new(AST::Tuple(vec![left, right]), combined.clone());
@@ -552,11 +1908,137 @@ impl Parser {
         return self.binop(Token::Equal, Prec::Logic, "equal", left);
     }
 
+    /// Parses a less than comparison, calls out to FFI.
+    pub fn less(&mut self, left: Spanned<AST>) -> Result<Spanned<AST>, Syntax> {
+        return self.binop(Token::Less, Prec::Logic, "less", left);
+    }
+
+    /// Parses a greater than comparison, calls out to FFI.
+    pub fn greater(&mut self, left: Spanned<AST>) -> Result<Spanned<AST>, Syntax> {
+        return self.binop(Token::Greater, Prec::Logic, "greater", left);
+    }
+
+    /// Parses a less than or equal comparison, calls out to FFI.
+    pub fn less_equal(&mut self, left: Spanned<AST>) -> Result<Spanned<AST>, Syntax> {
+        return self.binop(Token::LessEqual, Prec::Logic, "less_equal", left);
+    }
+
+    /// Parses a greater than or equal comparison, calls out to FFI.
+    pub fn greater_equal(&mut self, left: Spanned<AST>) -> Result<Spanned<AST>, Syntax> {
+        return self.binop(Token::GreaterEqual, Prec::Logic, "greater_equal", left);
+    }
+
     /// Parses an equality, calls out to FFI.
     pub fn remainder(&mut self, left: Spanned<AST>) -> Result<Spanned<AST>, Syntax> {
         return self.binop(Token::Rem, Prec::MulDiv, "remainder", left);
     }
 
+    /// Parses a left shift, calls out to FFI.
+    pub fn shl(&mut self, left: Spanned<AST>) -> Result<Spanned<AST>, Syntax> {
+        return self.binop(Token::Shl, Prec::Shift, "shift_left", left);
+    }
+
+    /// Parses an arithmetic right shift, calls out to FFI.
+    pub fn shr(&mut self, left: Spanned<AST>) -> Result<Spanned<AST>, Syntax> {
+        return self.binop(Token::Shr, Prec::Shift, "shift_right", left);
+    }
+
+    /// Parses a bitwise and, calls out to FFI.
+    pub fn bit_and(&mut self, left: Spanned<AST>) -> Result<Spanned<AST>, Syntax> {
+        return self.binop(Token::BitAnd, Prec::BitAnd, "bit_and", left);
+    }
+
+    /// Parses a bitwise or, calls out to FFI.
+    pub fn bit_or(&mut self, left: Spanned<AST>) -> Result<Spanned<AST>, Syntax> {
+        return self.binop(Token::BitOr, Prec::BitOr, "bit_or", left);
+    }
+
+    /// Parses a user-defined infix operator,
+    /// looking up its precedence and associativity in the operator table.
+    /// `a <op> b` becomes the function call `function a b`.
+    pub fn operator(&mut self, left: Spanned<AST>) -> Result<Spanned<AST>, Syntax> {
+        // a prefix operator starts an argument, like `f <> x`
+        if self.is_prefix() { return self.call(left); }
+
+        let Spanned { item: token, span } = self.advance().clone();
+        let glyph = match token {
+            Token::Op(glyph) => glyph,
+            _ => unreachable!("Expected an operator"),
+        };
+
+        let operator = match self.operators.get(&glyph) {
+            Some(operator) => operator.clone(),
+            None => return Err(Syntax::error(
+                &format!("Unknown operator '{}'", glyph),
+                &span,
+            )),
+        };
+
+        let prec = match operator.assoc {
+            Assoc::Left   => operator.prec.associate_left(),
+            Assoc::Right  => operator.prec,
+            Assoc::Prefix => unreachable!("Expected an infix operator"),
+        };
+
+        let function = match operator.function {
+            Some(function) => function,
+            None => return Err(Parser::uncallable(&glyph, &span)),
+        };
+
+        let right    = self.expression(prec, false)?;
+        let combined = Span::combine(&left.span, &right.span);
+        let function = Spanned::new(AST::Symbol(function), span);
+        return Ok(Spanned::new(AST::Form(vec![function, left, right]), combined));
+    }
+
+    /// The error for using an operator declared without a function to call,
+    /// like `infixr 5 (^)`.
+    fn uncallable(glyph: &str, span: &Span) -> Syntax {
+        Syntax::error(
+            &format!("The operator '{}' was declared without a function to call", glyph),
+            span,
+        ).with_help(&format!("Name the function after the operator, like `infixr 5 ({}) power`", glyph))
+    }
+
+    /// Whether the current token is a user-defined prefix operator.
+    fn is_prefix(&self) -> bool {
+        match &self.current().item {
+            Token::Op(glyph) => matches!(
+                self.operators.get(glyph),
+                Some(Operator { assoc: Assoc::Prefix, .. }),
+            ),
+            _ => false,
+        }
+    }
+
+    /// Parses a user-defined prefix operator, see `Parser::operator`.
+    /// `<op> a` becomes the function call `function a`.
+    pub fn prefix_operator(&mut self) -> Result<Spanned<AST>, Syntax> {
+        let Spanned { item: token, span } = self.current().clone();
+        let glyph = match token {
+            Token::Op(glyph) => glyph,
+            _ => unreachable!("Expected an operator"),
+        };
+
+        let function = match self.operators.get(&glyph) {
+            Some(Operator { assoc: Assoc::Prefix, function: Some(function), .. }) => function.clone(),
+            Some(Operator { assoc: Assoc::Prefix, function: None, .. }) => {
+                return Err(Parser::uncallable(&glyph, &span));
+            },
+            Some(_) => return Err(Syntax::error("Expected an expression", &span)),
+            None => return Err(Syntax::error(
+                &format!("Unknown operator '{}'", glyph),
+                &span,
+            )),
+        };
+        self.advance();
+
+        let ast      = self.expression(Prec::Call, false)?;
+        let combined = Span::combine(&span, &ast.span);
+        let function = Spanned::new(AST::Symbol(function), span);
+        return Ok(Spanned::new(AST::Form(vec![function, ast]), combined));
+    }
+
     /// Parses a function call.
     /// Function calls are a bit magical,
     /// because they're just a series of expressions.
@@ -565,15 +2047,18 @@ impl Parser {
     /// Then pull a fast one and not parse it like an operator at all.
     pub fn call(&mut self, left: Spanned<AST>) -> Result<Spanned<AST>, Syntax> {
         let argument = self.expression(Prec::Call.associate_left(), false)?;
-        let combined = Span::combine(&left.span, &argument.span);
 
-        let mut form = match left.item {
+        // a form's span already covers its previous arguments,
+        // so it just needs to grow to cover the new one
+        let Spanned { item, mut span } = left;
+        let mut form = match item {
             AST::Form(f) => f,
-            _ => vec![left],
+            other => vec![Spanned::new(other, span.clone())],
         };
 
+        span.extend_to(argument.span());
         form.push(argument);
-        return Ok(Spanned::new(AST::Form(form), combined));
+        return Ok(Spanned::new(AST::Form(form), span));
     }
 }
 
@@ -584,65 +2069,418 @@ mod test {
         source::Source
     };
 
-    use crate::compiler::lex::lex;
+    use crate::compiler::{
+        lex::lex,
+        operator::Operator,
+        ast::eq_ignoring_spans,
+    };
     use super::*;
 
     #[test]
-    pub fn empty() {
-        let source = Source::source("");
-        let ast = parse(lex(source.clone()).unwrap()).unwrap();
-        assert_eq!(ast, Spanned::new(AST::Block(vec![]), Span::empty()));
+    fn concurrent() {
+        use std::thread;
+        use crate::compiler::format::format;
+
+        let sources: &'static [&str] = &[
+            "x = 1 + 2 * 3",
+            "f = a b -> a - b\nf 7 3",
+            "y = (true, \"hello\", 4.5)",
+            "counter = n -> { m = n + 1; m }",
+            "z = x . f . g",
+        ];
+
+        // tokens and ASTs hold spans, which can't be sent between threads,
+        // so each thread lexes and parses on its own and sends back the formatted AST.
+        let parse_and_format = |source: &str| {
+            let tokens = lex(Source::source(source)).unwrap();
+            format(&parse(tokens).unwrap()).unwrap()
+        };
+
+        let expected = sources.iter()
+            .map(|source| parse_and_format(source))
+            .collect::<Vec<String>>();
+
+        let handles = (0..4).flat_map(|_| sources.iter().enumerate())
+            .map(|(index, source)| thread::spawn(move || {
+                // parse repeatedly so threads overlap
+                let mut formatted = parse_and_format(source);
+                for _ in 0..25 { formatted = parse_and_format(source); }
+                (index, formatted)
+            }))
+            .collect::<Vec<_>>();
+
+        for handle in handles {
+            let (index, formatted) = handle.join().unwrap();
+            assert_eq!(formatted, expected[index]);
+        }
     }
 
     #[test]
-    pub fn literal() {
-        let source = Source::source("x = 55.0");
+    fn bare_statements() {
+        use crate::compiler::ast::{symbol, call};
+
+        let source = Source::source("{ print x; f y }");
+        let ast = parse(lex(source).unwrap()).unwrap();
+
+        let block = match ast.item {
+            AST::Block(mut outer) => outer.remove(0),
+            _ => panic!("Expected a block"),
+        };
+
+        let expected = AST::Block(vec![
+            Spanned::new(AST::ffi("println", symbol("x")), Span::empty()),
+            call(symbol("f"), symbol("y")),
+        ]);
+        assert_eq!(block.item.without_spans(), expected);
+
+        // the print runs for its effect, the call is the value of the block
+        let discarded = block.item.discarded();
+        assert_eq!(discarded.len(), 1);
+        assert_eq!(discarded[0].item.without_spans(), AST::ffi("println", symbol("x")));
+    }
+
+    #[test]
+    fn node_at_offset() {
+        use crate::compiler::ast::{symbol, call};
+
+        let source = Source::source("x = f (g y) 2");
         let ast = parse(lex(source.clone()).unwrap()).unwrap();
-        assert_eq!(
-            ast,
-            Spanned::new(
-                AST::Block(
-                    vec![
-                        Spanned::new(
-                            AST::assign(
-                                Spanned::new(ASTPattern::Symbol("x".to_string()), Span::new(&source, 0, 1)),
-                                Spanned::new(
-                                    AST::Data(Data::Real(55.0)),
-                                    Span::new(&source, 4, 4),
-                                ),
-                            ),
-                            Span::new(&source, 0, 8),
-                        )
-                    ]
+        let found = |offset| node_at(&ast, offset).map(|node| node.item.without_spans());
+
+        assert_eq!(found(9),  Some(AST::Symbol("y".to_string())));
+        assert_eq!(found(4),  Some(AST::Symbol("f".to_string())));
+        // whitespace within a call finds the call
+        assert_eq!(found(8),  Some(call(symbol("g"), symbol("y")).item));
+        assert_eq!(found(11).map(|node| matches!(node, AST::Form(_))), Some(true));
+        assert_eq!(node_at(&ast, 11).unwrap().span, Span::new(&source, 4, 9));
+        // the pattern isn't an `AST` node, so the assignment is found
+        assert!(matches!(found(0), Some(AST::Assign { .. })));
+        assert_eq!(found(13), None);
+    }
+
+    fn attributes_of(source: &str) -> (Vec<Attribute>, AST) {
+        let ast = parse(lex(Source::source(source)).unwrap()).unwrap();
+        match ast.item.without_spans() {
+            AST::Block(mut statements) => match statements.remove(0).item {
+                AST::Attributed { attributes, node } => (
+                    attributes.into_iter().map(|a| a.item).collect(),
+                    node.item,
                 ),
-                Span::empty(),
-            )
-        );
+                other => panic!("Expected an attributed declaration, found {:?}", other),
+            },
+            _ => unreachable!(),
+        }
+    }
+
+    fn attribute(name: &str, arguments: Vec<Spanned<AST>>) -> Attribute {
+        Attribute { name: name.to_string(), arguments }
     }
 
     #[test]
-    pub fn lambda() {
-        let source = Source::source("x = y -> 3.141592");
-        let ast = parse(lex(source.clone()).unwrap()).unwrap();
-        // println!("{:#?}", ast);
-        assert_eq!(
-            ast,
-            Spanned::new(
-                AST::Block(
-                    vec![
-                        Spanned::new(
-                            AST::assign(
-                                Spanned::new(ASTPattern::Symbol("x".to_string()), Span::new(&source, 0, 1)),
-                                Spanned::new(
-                                    AST::lambda(
-                                        Spanned::new(ASTPattern::Symbol("y".to_string()), Span::new(&source, 4, 1)),
-                                        Spanned::new(
+    fn bare_attribute() {
+        let (attributes, node) = attributes_of("@inline\nf = x -> x");
+        assert_eq!(attributes, vec![attribute("inline", vec![])]);
+        assert!(matches!(node, AST::Assign { .. }));
+
+        let (attributes, node) = attributes_of("@opaque type Id = Int");
+        assert_eq!(attributes, vec![attribute("opaque", vec![])]);
+        assert!(matches!(node, AST::TypeDef { .. }));
+    }
+
+    #[test]
+    fn attribute_arguments() {
+        use crate::compiler::ast::{symbol, data};
+
+        let (attributes, _) = attributes_of("@deprecated(\"use g\")\nf = 1");
+        let message = data(Data::String("use g".into()));
+        assert_eq!(attributes, vec![attribute("deprecated", vec![message])]);
+
+        let (attributes, _) = attributes_of("@since(1, x)\nf = 1");
+        let arguments = vec![data(Data::Integer(1)), symbol("x")];
+        assert_eq!(attributes, vec![attribute("since", arguments)]);
+
+        let (attributes, _) = attributes_of("@none()\nf = 1");
+        assert_eq!(attributes, vec![attribute("none", vec![])]);
+
+        // arguments must directly follow the name
+        let (attributes, node) = attributes_of("@swap (a, b) = (b, a)");
+        assert_eq!(attributes, vec![attribute("swap", vec![])]);
+        assert!(matches!(node, AST::Assign { .. }));
+    }
+
+    #[test]
+    fn stacked_attributes() {
+        let (attributes, node) = attributes_of("--| Doubles.\n@inline @pure\n@deprecated\nf = x -> x + x");
+        let names = attributes.iter().map(|a| a.name.as_str()).collect::<Vec<_>>();
+        assert_eq!(names, vec!["inline", "pure", "deprecated"]);
+        assert!(matches!(node, AST::Assign { doc: Some(_), .. }));
+    }
+
+    #[test]
+    fn dangling_attributes() {
+        for (source, at) in &[("@inline", (0, 7)), ("x = 1\n@a @b\nf x", (6, 5))] {
+            let source = Source::source(source);
+            let result = parse(lex(source.clone()).unwrap());
+            assert_eq!(result, Err(Syntax::error(
+                "An attribute must be followed by a declaration, like a binding or type definition",
+                &Span::new(&source, at.0, at.1),
+            )));
+        }
+
+        let source = Source::source("x = @inline f");
+        let result = parse(lex(source.clone()).unwrap());
+        assert_eq!(result, Err(Syntax::error(
+            "An attribute is only allowed before a declaration",
+            &Span::new(&source, 4, 1),
+        )));
+    }
+
+    #[test]
+    fn no_empty_spans() {
+        use crate::compiler::ast::find_empty_spans;
+
+        let mut sources = vec![
+            "x = 1 + 2 * 3\nprint x".to_string(),
+            "f = a (b, c) -> { d = a b; d . c }\nf 1 (2, 3)".to_string(),
+            "assert (x == 1) \"message\"\n~x\nx?\nf ...xs".to_string(),
+            "--| Doc.\n@inline\ng = Some x -> x\ntype T = Int\nuse a.b".to_string(),
+            "syntax 'if cond then 'else other { magic \"if\" (cond, then, other) }".to_string(),
+            "`(f ~x)\ny = True (1,)\nz = ()".to_string(),
+        ];
+
+        for entry in std::fs::read_dir("./tests/snippets").unwrap() {
+            sources.push(std::fs::read_to_string(entry.unwrap().path()).unwrap());
+        }
+
+        for source in sources.iter() {
+            // some snippets test syntax errors
+            let ast = match lex(Source::source(source)).and_then(parse) {
+                Ok(ast) => ast,
+                Err(_) => continue,
+            };
+
+            let empty = find_empty_spans(&ast);
+            assert!(empty.is_empty(), "Empty spans in {:?}:\n{:#?}", source, empty);
+        }
+    }
+
+    #[test]
+    fn finds_empty_spans() {
+        use crate::compiler::ast::{find_empty_spans, symbol};
+
+        // built by hand, so the leaves have no spans
+        let source = Source::source("f x");
+        let call = AST::Form(vec![symbol("f"), symbol("x")]);
+        let ast = Spanned::new(
+            AST::Block(vec![Spanned::new(call, Span::new(&source, 0, 3))]),
+            Span::empty(),
+        );
+
+        let empty = find_empty_spans(&ast);
+        let found = empty.iter().map(|node| node.item.clone()).collect::<Vec<_>>();
+        assert_eq!(found, vec![AST::Symbol("f".to_string()), AST::Symbol("x".to_string())]);
+    }
+
+    #[test]
+    fn labeled_block() {
+        use crate::compiler::ast::{self, symbol, call, binop};
+
+        let source = Source::source("x = 'found: {\n    break 'found (f y)\n    z + 1\n}");
+        let ast = parse(lex(source.clone()).unwrap()).unwrap();
+
+        let body = ast::block(vec![
+            Spanned::new(
+                AST::break_("found", ast::group(call(symbol("f"), symbol("y")))),
+                Span::empty(),
+            ),
+            binop("add", symbol("z"), ast::data(Data::Integer(1))),
+        ]);
+        let expected = ast::block(vec![
+            ast::assign("x", Spanned::new(AST::labeled_block("found", body), Span::empty())),
+        ]);
+        assert!(eq_ignoring_spans(&ast, &expected));
+
+        // the labeled block spans from its label to its closing bracket
+        let labeled = node_at(&ast, 4).unwrap();
+        assert_eq!(labeled.span, Span::new(&source, 4, source.contents.len() - 4));
+    }
+
+    #[test]
+    fn conditional_expression() {
+        use crate::compiler::ast::{self, symbol, call};
+
+        // `if` is a macro, so a conditional is just a form,
+        // which may appear anywhere an expression can
+        let conditional = || call(
+            call(call(call(symbol("if"), symbol("c")), ast::block(vec![ast::data(Data::Integer(1))])), symbol("else")),
+            ast::block(vec![ast::data(Data::Integer(2))]),
+        );
+
+        let ast = parse(lex(Source::source("x = if c { 1 } else { 2 }")).unwrap()).unwrap();
+        let expected = ast::block(vec![ast::assign("x", conditional())]);
+        assert!(eq_ignoring_spans(&ast, &expected));
+
+        let ast = parse(lex(Source::source("f (if c { 1 } else { 2 }) y")).unwrap()).unwrap();
+        let expected = ast::block(vec![
+            call(call(symbol("f"), ast::group(conditional())), symbol("y")),
+        ]);
+        assert!(eq_ignoring_spans(&ast, &expected));
+    }
+
+    #[test]
+    fn function_literal() {
+        use crate::compiler::ast::{self, symbol, call};
+
+        let source = Source::source("f = fn {\n    0 -> \"zero\", n -> \"nonzero\"\n}");
+        let ast = parse(lex(source.clone()).unwrap()).unwrap();
+
+        let arm = |pattern, value: &str| Spanned::new(
+            AST::lambda(Spanned::new(pattern, Span::empty()), ast::data(Data::String(value.into()))),
+            Span::empty(),
+        );
+        let arms = Spanned::new(AST::Tuple(vec![
+            arm(ASTPattern::Data(Data::Integer(0)), "zero"),
+            arm(ASTPattern::Symbol("n".to_string()), "nonzero"),
+        ]), Span::empty());
+
+        // `arg -> match arg (arms...)`
+        let expected = ast::block(vec![ast::assign("f", Spanned::new(
+            AST::lambda(
+                Spanned::new(ASTPattern::Symbol(FUNCTION_ARGUMENT.to_string()), Span::empty()),
+                call(call(symbol("match"), symbol(FUNCTION_ARGUMENT)), arms),
+            ),
+            Span::empty(),
+        ))]);
+        assert!(eq_ignoring_spans(&ast, &expected));
+        assert!(ast::find_empty_spans(&ast).is_empty());
+
+        // arms may be on separate lines, too
+        let lines = parse(lex(Source::source("f = fn {\n    0 -> \"zero\"\n    n -> \"nonzero\"\n}")).unwrap()).unwrap();
+        assert!(eq_ignoring_spans(&lines, &expected));
+    }
+
+    #[test]
+    fn guarded_arms() {
+        use crate::compiler::ast::find_empty_spans;
+
+        let source = Source::source("fn { n if n > 0 -> n, _ -> 0 }");
+        let ast = parse(lex(source).unwrap()).unwrap();
+
+        let arms = match &ast.item {
+            AST::Block(b) => match &b[0].item {
+                AST::Lambda { expression, .. } => match &expression.item {
+                    AST::Form(f) => match &f[2].item {
+                        AST::Tuple(arms) => arms.clone(),
+                        other => panic!("Expected the arms, found {:?}", other),
+                    },
+                    other => panic!("Expected a match, found {:?}", other),
+                },
+                other => panic!("Expected a function, found {:?}", other),
+            },
+            _ => unreachable!(),
+        };
+
+        let patterns = arms.iter().map(|arm| match &arm.item {
+            AST::Lambda { pattern, .. } => pattern.item.without_spans(),
+            other => panic!("Expected an arm, found {:?}", other),
+        }).collect::<Vec<_>>();
+
+        let n = Spanned::new(ASTPattern::Symbol("n".to_string()), Span::empty());
+        let guard = Parser::new(lex(Source::source("n > 0")).unwrap()).expression(Prec::None, false).unwrap();
+        assert_eq!(patterns, vec![
+            ASTPattern::guarded(n, Spanned::new(guard.item.without_spans(), Span::empty())),
+            ASTPattern::Symbol("_".to_string()),
+        ]);
+        assert!(find_empty_spans(&ast).is_empty());
+
+        // `if` only starts a guard in the pattern of an arm
+        let body = parse(lex(Source::source("fn { x -> if x { 1 } else { 2 } }")).unwrap());
+        assert!(body.is_ok());
+    }
+
+    #[test]
+    fn function_literal_errors() {
+        for (source, message) in &[
+            ("fn x -> x", "Expected the arms of the function in a block, like fn { x -> x }"),
+            ("fn { x -> x, 2 }", "Expected an arm of the function, like `pattern -> expression`"),
+            ("fn { x if x }", "Expected an arm of the function, like `pattern -> expression`"),
+            ("fn {}", "A function literal must have at least one arm"),
+        ] {
+            let result = parse(lex(Source::source(source)).unwrap());
+            assert_eq!(result.unwrap_err().message, *message);
+        }
+    }
+
+    #[test]
+    fn break_without_label() {
+        for (source, message, at) in &[
+            ("'a: { break x }", "Expected the label of a block to break out of, like 'name, found a symbol", 12),
+            ("'a: x", "Expected a block after the label 'a:", 4),
+        ] {
+            let source = Source::source(source);
+            let result = parse(lex(source.clone()).unwrap());
+            assert_eq!(result, Err(Syntax::error(message, &Span::new(&source, *at, 1))));
+        }
+    }
+
+    #[test]
+    pub fn empty() {
+        let source = Source::source("");
+        let ast = parse(lex(source.clone()).unwrap()).unwrap();
+        assert_eq!(ast, Spanned::new(AST::Block(vec![]), Span::empty()));
+    }
+
+    #[test]
+    pub fn literal() {
+        let source = Source::source("x = 55.0");
+        let ast = parse(lex(source.clone()).unwrap()).unwrap();
+        assert_eq!(
+            ast,
+            Spanned::new(
+                AST::Block(
+                    vec![
+                        Spanned::new(
+                            AST::assign(
+                                Spanned::new(ASTPattern::Symbol("x".to_string()), Span::new(&source, 0, 1)),
+                                Spanned::new(
+                                    AST::Data(Data::Real(55.0)),
+                                    Span::new(&source, 4, 4),
+                                ),
+                                None,
+                            ),
+                            Span::new(&source, 0, 8),
+                        )
+                    ]
+                ),
+                Span::empty(),
+            )
+        );
+    }
+
+    #[test]
+    pub fn lambda() {
+        let source = Source::source("x = y -> 3.141592");
+        let ast = parse(lex(source.clone()).unwrap()).unwrap();
+        // println!("{:#?}", ast);
+        assert_eq!(
+            ast,
+            Spanned::new(
+                AST::Block(
+                    vec![
+                        Spanned::new(
+                            AST::assign(
+                                Spanned::new(ASTPattern::Symbol("x".to_string()), Span::new(&source, 0, 1)),
+                                Spanned::new(
+                                    AST::lambda(
+                                        Spanned::new(ASTPattern::Symbol("y".to_string()), Span::new(&source, 4, 1)),
+                                        Spanned::new(
                                             AST::Data(Data::Real(3.141592)),
                                             Span::new(&source, 9, 8),
                                         ),
                                     ),
                                     Span::new(&source, 4, 13),
                                 ),
+                                None,
                             ),
                             Span::new(&source, 0, 17),
                         ),
@@ -652,4 +2490,1062 @@ mod test {
             )
         );
     }
+
+    #[test]
+    pub fn type_alias() {
+        let source = Source::source("type Name = String");
+        let ast = parse(lex(source.clone()).unwrap()).unwrap();
+        assert_eq!(
+            ast,
+            Spanned::new(
+                AST::Block(vec![
+                    Spanned::new(
+                        AST::type_def(
+                            "Name",
+                            Spanned::new(ASTType::Name("String".to_string()), Span::new(&source, 12, 6)),
+                        ),
+                        Span::new(&source, 0, 18),
+                    ),
+                ]),
+                Span::empty(),
+            )
+        );
+    }
+
+    #[test]
+    pub fn type_function_alias() {
+        let source = Source::source("type Map = (a -> b, List a) -> List b");
+        let ast = parse(lex(source.clone()).unwrap()).unwrap();
+        let name = |n: &str, offset, length| Spanned::new(
+            ASTType::Name(n.to_string()),
+            Span::new(&source, offset, length),
+        );
+
+        let function = Spanned::new(
+            ASTType::function(name("a", 12, 1), name("b", 17, 1)),
+            Span::new(&source, 12, 6),
+        );
+        let list_a = Spanned::new(
+            ASTType::apply(name("List", 20, 4), name("a", 25, 1)),
+            Span::new(&source, 20, 6),
+        );
+        let list_b = Spanned::new(
+            ASTType::apply(name("List", 31, 4), name("b", 36, 1)),
+            Span::new(&source, 31, 6),
+        );
+        let arguments = Spanned::new(
+            ASTType::Tuple(vec![function, list_a]),
+            Span::new(&source, 11, 16),
+        );
+
+        assert_eq!(
+            ast,
+            Spanned::new(
+                AST::Block(vec![
+                    Spanned::new(
+                        AST::type_def(
+                            "Map",
+                            Spanned::new(
+                                ASTType::function(arguments, list_b),
+                                Span::new(&source, 11, 26),
+                            ),
+                        ),
+                        Span::new(&source, 0, 37),
+                    ),
+                ]),
+                Span::empty(),
+            )
+        );
+    }
+
+    #[test]
+    pub fn use_dotted() {
+        let source = Source::source("use foo.bar.baz");
+        let ast = parse(lex(source.clone()).unwrap()).unwrap();
+        let path = vec!["foo".to_string(), "bar".to_string(), "baz".to_string()];
+        assert_eq!(
+            ast,
+            Spanned::new(
+                AST::Block(vec![
+                    Spanned::new(AST::import(ASTImport::Module(path)), Span::new(&source, 0, 15)),
+                ]),
+                Span::empty(),
+            )
+        );
+    }
+
+    #[test]
+    pub fn spread_argument() {
+        use crate::compiler::ast::{self, symbol, call};
+        let ast = parse(lex(Source::source("f ...args y")).unwrap()).unwrap();
+        let spread = Spanned::new(AST::spread(symbol("args")), Span::empty());
+        let expected = ast::block(vec![call(call(symbol("f"), spread), symbol("y"))]);
+        assert!(eq_ignoring_spans(&ast, &expected));
+    }
+
+    #[test]
+    pub fn spread_item() {
+        use crate::compiler::ast::{self, symbol, tuple, group};
+        let ast = parse(lex(Source::source("(...xs, y, ...zs)")).unwrap()).unwrap();
+        let spread = |name| Spanned::new(AST::spread(symbol(name)), Span::empty());
+        let expected = ast::block(vec![
+            group(tuple(vec![spread("xs"), symbol("y"), spread("zs")])),
+        ]);
+        assert!(eq_ignoring_spans(&ast, &expected));
+    }
+
+    #[test]
+    pub fn misplaced_spread() {
+        for source in &["...xs", "x = ...xs", "a + ...b", "f (...xs)", "...f x", "{ ...xs }"] {
+            let source = Source::source(source);
+            let error = parse(lex(source.clone()).unwrap()).unwrap_err();
+            assert_eq!(
+                error.message,
+                "A spread is only allowed as a call argument or a tuple item",
+                "while parsing '{}'", source.contents,
+            );
+        }
+    }
+
+    #[test]
+    pub fn quote() {
+        let source = Source::source("`(x + (1 2))");
+        let tokens = lex(source.clone()).unwrap();
+        let quoted = tokens[2..8].to_vec();
+
+        let ast = parse(tokens).unwrap();
+        assert_eq!(
+            ast,
+            Spanned::new(
+                AST::Block(vec![
+                    Spanned::new(AST::quote(quoted), Span::new(&source, 0, 12)),
+                ]),
+                Span::empty(),
+            )
+        );
+    }
+
+    #[test]
+    pub fn quote_with_unquote() {
+        let source = Source::source("f `(g ~x)");
+        let ast = parse(lex(source.clone()).unwrap()).unwrap();
+        let quoted = vec![
+            Spanned::new(Token::Symbol,  Span::new(&source, 4, 1)),
+            Spanned::new(Token::Unquote, Span::new(&source, 6, 1)),
+            Spanned::new(Token::Symbol,  Span::new(&source, 7, 1)),
+        ];
+
+        assert_eq!(
+            ast,
+            Spanned::new(
+                AST::Block(vec![
+                    Spanned::new(
+                        AST::Form(vec![
+                            Spanned::new(AST::Symbol("f".to_string()), Span::new(&source, 0, 1)),
+                            Spanned::new(AST::quote(quoted), Span::new(&source, 2, 7)),
+                        ]),
+                        Span::new(&source, 0, 9),
+                    ),
+                ]),
+                Span::empty(),
+            )
+        );
+    }
+
+    #[test]
+    pub fn malformed_quote() {
+        for source in &["`x", "`(~)", "`(a ~ + b)", "`(a", "`((a)"] {
+            let tokens = lex(Source::source(source)).unwrap();
+            assert!(parse(tokens).is_err(), "while parsing '{}'", source);
+        }
+    }
+
+    #[test]
+    pub fn import_string() {
+        let source = Source::source("import \"lib/math.pn\"\nx");
+        let ast = parse(lex(source.clone()).unwrap()).unwrap();
+        assert_eq!(
+            ast,
+            Spanned::new(
+                AST::Block(vec![
+                    Spanned::new(
+                        AST::import(ASTImport::File("lib/math.pn".to_string())),
+                        Span::new(&source, 0, 20),
+                    ),
+                    Spanned::new(AST::Symbol("x".to_string()), Span::new(&source, 21, 1)),
+                ]),
+                Span::empty(),
+            )
+        );
+    }
+
+    #[test]
+    pub fn malformed_import() {
+        for source in &["use", "use foo.", "use foo..bar", "use .foo", "import \"\"", "import foo", "x = use foo"] {
+            let tokens = lex(Source::source(source)).unwrap();
+            assert!(parse(tokens).is_err(), "'{}' should not parse", source);
+        }
+    }
+
+    #[test]
+    pub fn doc_comment() {
+        let source = Source::source("--| The answer.\n--| Probably.\nx = 42\ny = 0");
+        let (ast, warnings) = parse_with_warnings(lex(source).unwrap()).unwrap();
+        assert!(warnings.is_empty());
+
+        let docs = match ast.item {
+            AST::Block(b) => b.into_iter().map(|e| match e.item {
+                AST::Assign { doc, .. } => doc,
+                other => panic!("Expected an assignment, found {:?}", other),
+            }).collect::<Vec<_>>(),
+            _ => unreachable!(),
+        };
+        assert_eq!(docs, vec![Some("The answer.\nProbably.".to_string()), None]);
+    }
+
+    #[test]
+    pub fn dangling_doc_comment() {
+        for source in &["--| Nothing here.\nprint 42", "x = 7\n--| At the end."] {
+            let source = Source::source(source);
+            let (_, warnings) = parse_with_warnings(lex(source).unwrap()).unwrap();
+            assert_eq!(warnings.len(), 1);
+            assert!(warnings[0].message.contains("doc comment"));
+            assert_eq!(warnings[0].severity, Severity::Warning);
+        }
+    }
+
+    #[test]
+    pub fn assert() {
+        use crate::compiler::ast::{self, symbol, group, binop};
+        let parse_source = |source| parse(lex(Source::source(source)).unwrap()).unwrap();
+        let x_is_one = || binop("equal", symbol("x"), ast::data(Data::Integer(1)));
+
+        let expected = ast::block(vec![
+            Spanned::new(AST::assert(x_is_one(), None), Span::empty()),
+        ]);
+        assert!(eq_ignoring_spans(&parse_source("assert x == 1"), &expected));
+
+        let message = ast::data(Data::String("x is one".into()));
+        let expected = ast::block(vec![
+            Spanned::new(AST::assert(group(x_is_one()), Some(message)), Span::empty()),
+        ]);
+        assert!(eq_ignoring_spans(&parse_source("assert (x == 1) \"x is one\""), &expected));
+
+        // a message is only a trailing string
+        let expected = ast::block(vec![
+            Spanned::new(AST::assert(ast::call(symbol("f"), symbol("x")), None), Span::empty()),
+        ]);
+        assert!(eq_ignoring_spans(&parse_source("assert f x"), &expected));
+
+        // a call's trailing string is its argument, not a message
+        let name = symbol("name");
+        let a = ast::data(Data::String("a".into()));
+        let expected = ast::block(vec![Spanned::new(
+            AST::assert(ast::call(ast::call(symbol("starts_with"), name), a), None),
+            Span::empty(),
+        )]);
+        assert!(eq_ignoring_spans(&parse_source("assert starts_with name \"a\""), &expected));
+    }
+
+    #[test]
+    pub fn limits() {
+        let source = Source::source("x = 1 + 2");
+        let tokens = lex(source.clone()).unwrap();
+        let limit = |tokens| Limits { source_bytes: None, tokens };
+
+        assert!(parse_with_limits(tokens.clone(), limit(Some(5))).is_ok());
+        assert!(parse_with_limits(tokens.clone(), limit(None)).is_ok());
+
+        let error = parse_with_limits(tokens, limit(Some(4))).unwrap_err();
+        assert_eq!(error.span, Span::new(&source, 8, 1));
+    }
+
+    #[test]
+    pub fn type_in_expression() {
+        let tokens = lex(Source::source("x = type Name = String")).unwrap();
+        assert!(parse(tokens).is_err());
+    }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    pub fn serialize() {
+        let source = Source::source("f x");
+        let ast = parse(lex(source).unwrap()).unwrap();
+
+        let expected = serde_json::json!({
+            "item": { "Block": [{
+                "item": { "Form": [
+                    { "item": { "Symbol": "f" }, "span": { "offset": 0, "length": 1 } },
+                    { "item": { "Symbol": "x" }, "span": { "offset": 2, "length": 1 } },
+                ] },
+                "span": { "offset": 0, "length": 3 },
+            }] },
+            "span": null,
+        });
+
+        assert_eq!(serde_json::to_value(&ast).unwrap(), expected);
+    }
+
+    /// Renders the shape of an `AST` as an s-expression, ignoring spans.
+    /// Only handles the nodes needed to check groupings.
+    fn shape(ast: &AST) -> String {
+        match ast {
+            AST::Symbol(s)     => s.clone(),
+            AST::Data(d)       => format!("{}", d),
+            AST::Group(e)      => format!("[{}]", shape(&e.item)),
+            AST::Block(b)      => b.iter().map(|e| shape(&e.item)).collect::<Vec<_>>().join("; "),
+            AST::Form(f)       => format!("({})", f.iter().map(|e| shape(&e.item)).collect::<Vec<_>>().join(" ")),
+            AST::FFI { name, expression } => match &expression.item {
+                AST::Tuple(t) => format!("({} {})", name, t.iter().map(|e| shape(&e.item)).collect::<Vec<_>>().join(" ")),
+                other         => format!("({} {})", name, shape(other)),
+            },
+            AST::Lambda { expression, .. } => format!("(-> {})", shape(&expression.item)),
+            AST::Try(e)        => format!("(? {})", shape(&e.item)),
+            AST::Assign { expression, .. } => format!("(= {})", shape(&expression.item)),
+            AST::Composition { argument, function } => format!(
+                "(. {} {})", shape(&argument.item), shape(&function.item),
+            ),
+            AST::Fixity { operator, .. } => format!("(fixity {})", operator),
+            other => panic!("Can not render the shape of {:?}", other),
+        }
+    }
+
+    fn parse_shape(source: &str) -> String {
+        shape(&parse(lex(Source::source(source)).unwrap()).unwrap().item)
+    }
+
+    #[test]
+    pub fn calling() {
+        use crate::compiler::ast::{self, symbol, call, group, lambda, binop};
+
+        let source = "add = x -> y -> x + y\nadd 1 (add 2 3)";
+        let integer = |n| ast::data(Data::Integer(n));
+
+        let expected = ast::block(vec![
+            ast::assign("add", lambda("x", lambda("y", binop("add", symbol("x"), symbol("y"))))),
+            call(
+                call(symbol("add"), integer(1)),
+                group(call(call(symbol("add"), integer(2)), integer(3))),
+            ),
+        ]);
+
+        let parsed = parse(lex(Source::source(source)).unwrap()).unwrap();
+        assert!(eq_ignoring_spans(&parsed, &expected));
+    }
+
+    #[test]
+    pub fn placeholders() {
+        use crate::compiler::ast::{self, symbol, call, group, lambda, binop};
+        let parse_source = |source| parse(lex(Source::source(source)).unwrap());
+        let integer = |n| ast::data(Data::Integer(n));
+        let placeholder = || symbol(PLACEHOLDER);
+
+        let parsed = parse_source("map xs (# + 1)").unwrap();
+        let expected = ast::block(vec![call(
+            call(symbol("map"), symbol("xs")),
+            group(lambda(PLACEHOLDER, binop("add", placeholder(), integer(1)))),
+        )]);
+        assert!(eq_ignoring_spans(&parsed, &expected));
+
+        // every placeholder is the same parameter
+        let parsed = parse_source("(# * #)").unwrap();
+        let expected = ast::block(vec![
+            group(lambda(PLACEHOLDER, binop("mul", placeholder(), placeholder()))),
+        ]);
+        assert!(eq_ignoring_spans(&parsed, &expected));
+
+        // only the innermost parens become a function
+        let parsed = parse_source("(f (# + 1) x)").unwrap();
+        let expected = ast::block(vec![group(call(
+            call(symbol("f"), group(lambda(PLACEHOLDER, binop("add", placeholder(), integer(1))))),
+            symbol("x"),
+        ))]);
+        assert!(eq_ignoring_spans(&parsed, &expected));
+
+        let error = parse_source("x = f #").unwrap_err();
+        assert_eq!(error.span, Span::new(&Source::source("x = f #"), 6, 1));
+        assert!(error.message.contains("inside of parens"));
+
+        // only parens make a function of a placeholder
+        let error = parse_source("f = # -> # + 1").unwrap_err();
+        assert_eq!(error.span, Span::new(&Source::source("f = # -> # + 1"), 4, 1));
+        assert!(error.message.contains("can not be used in a pattern"));
+        assert!(parse_source("(# -> 1)").is_err());
+        assert!(parse_source("(a, #) = (1, 2)").is_err());
+    }
+
+    #[test]
+    pub fn transform_literals() {
+        use crate::compiler::ast::transform;
+        let parse_source = |source| parse(lex(Source::source(source)).unwrap()).unwrap();
+
+        let zeroes = parse_source("x = 0.0\nf (0.0, { y -> y + 0.0 }) 0\n@scale(0.0)\nz = { 0.0 }?");
+        let ones   = parse_source("x = 1.0\nf (1.0, { y -> y + 1.0 }) 0\n@scale(1.0)\nz = { 1.0 }?");
+
+        let rewritten = transform(zeroes, &mut |node| match node.item {
+            AST::Data(Data::Real(0.0)) => Spanned::new(AST::Data(Data::Real(1.0)), node.span),
+            _ => node,
+        });
+        assert!(eq_ignoring_spans(&rewritten, &ones));
+
+        // children are rewritten before their parents
+        let mut visited = vec![];
+        transform(parse_source("f (g x)"), &mut |node| {
+            visited.push(shape(&node.item));
+            node
+        });
+        assert_eq!(visited, vec!["f", "g", "x", "(g x)", "[(g x)]", "(f [(g x)])", "(f [(g x)])"]);
+    }
+
+    #[test]
+    pub fn lambda_free_vars() {
+        use crate::compiler::ast::{arity, free_vars};
+
+        let statement = |source| match parse(lex(Source::source(source)).unwrap()).unwrap().item {
+            AST::Block(mut b) => b.remove(0),
+            _ => unreachable!(),
+        };
+
+        let closure = statement("x -> x + y");
+        assert_eq!(free_vars(&closure), vec!["y".to_string()]);
+        assert_eq!(arity(&closure.item), Some(1));
+
+        let closed = statement("a (b, Some c) -> { d = a + b; e -> d + c + e }");
+        assert_eq!(free_vars(&closed), Vec::<String>::new());
+        assert_eq!(arity(&closed.item), Some(2));
+
+        // an inner binding shadows y, even where it's used before being assigned
+        let shadowed = statement("x -> { z = y; y = x; y + z + w }");
+        assert_eq!(free_vars(&shadowed), vec!["w".to_string()]);
+        let nested = statement("x -> { f = w -> { y = w; y + z }; f x + y }");
+        assert_eq!(free_vars(&nested), vec!["z".to_string(), "y".to_string()]);
+
+        assert_eq!(arity(&statement("x -> y -> x").item), Some(2));
+        assert_eq!(arity(&statement("f x").item), None);
+    }
+
+    #[test]
+    pub fn tuple_arity() {
+        use crate::compiler::ast::{self, symbol, group, tuple};
+
+        for (source, expected) in [
+            ("()",     ast::data(Data::Unit)),
+            ("(x)",    group(symbol("x"))),
+            ("(x,)",   group(tuple(vec![symbol("x")]))),
+            ("x,",     tuple(vec![symbol("x")])),
+            ("(x, y)", group(tuple(vec![symbol("x"), symbol("y")]))),
+            ("(x, y,)", group(tuple(vec![symbol("x"), symbol("y")]))),
+        ] {
+            let parsed = parse(lex(Source::source(source)).unwrap()).unwrap();
+            assert!(eq_ignoring_spans(&parsed, &ast::block(vec![expected])), "while parsing {:?}", source);
+        }
+    }
+
+    #[test]
+    pub fn typed_parameters() {
+        use crate::compiler::ast::{self, symbol};
+        let parse_source = |source| parse(lex(Source::source(source)).unwrap());
+
+        let name  = |n: &str| Spanned::new(ASTType::Name(n.to_string()), Span::empty());
+        let param = |n: &str| Spanned::new(ASTPattern::Symbol(n.to_string()), Span::empty());
+        let typed = |n: &str, t: &str| Spanned::new(ASTPattern::typed(param(n), name(t)), Span::empty());
+        let lambda = |pattern: Spanned<ASTPattern>, body| ast::block(vec![
+            Spanned::new(AST::lambda(pattern, body), Span::empty()),
+        ]);
+        let tuple = |items| Spanned::new(ASTPattern::Tuple(items), Span::empty());
+
+        // a single typed parameter
+        let expected = lambda(typed("x", "Number"), symbol("x"));
+        assert!(eq_ignoring_spans(&parse_source("(x: Number) -> x").unwrap(), &expected));
+
+        // several typed parameters, as a tuple or curried
+        let expected = lambda(tuple(vec![typed("x", "Number"), typed("y", "String")]), symbol("y"));
+        assert!(eq_ignoring_spans(&parse_source("(x: Number, y: String) -> y").unwrap(), &expected));
+        let expected = lambda(
+            Spanned::new(ASTPattern::Chain(vec![typed("x", "Number"), typed("y", "String")]), Span::empty()),
+            symbol("y"),
+        );
+        assert!(eq_ignoring_spans(&parse_source("(x: Number) (y: String) -> y").unwrap(), &expected));
+
+        // typed and untyped parameters mixed
+        let expected = lambda(tuple(vec![param("x"), typed("y", "String")]), symbol("y"));
+        assert!(eq_ignoring_spans(&parse_source("(x, y: String) -> y").unwrap(), &expected));
+
+        // any type expression may be used
+        let function = Spanned::new(ASTType::function(name("A"), name("B")), Span::empty());
+        let expected = lambda(
+            Spanned::new(ASTPattern::typed(param("f"), function), Span::empty()),
+            symbol("f"),
+        );
+        assert!(eq_ignoring_spans(&parse_source("(f: A -> B) -> f").unwrap(), &expected));
+
+        // bare parameters still work
+        let expected = lambda(param("x"), symbol("x"));
+        assert!(eq_ignoring_spans(&parse_source("x -> x").unwrap(), &expected));
+
+        assert!(parse_source("(1 + 2: Number) -> x").is_err());
+    }
+
+    #[test]
+    pub fn separator_ends_call() {
+        use crate::compiler::ast::{self, symbol, call};
+        let parse_source = |source| parse(lex(Source::source(source)).unwrap());
+
+        let statements = vec![
+            call(symbol("f"), symbol("a")),
+            call(symbol("g"), symbol("b")),
+        ];
+        let expected = ast::block(statements.clone());
+        assert!(eq_ignoring_spans(&parse_source("f a\ng b").unwrap(), &expected));
+        assert!(eq_ignoring_spans(&parse_source("f a\n\n  g b\n").unwrap(), &expected));
+
+        let expected = ast::block(vec![ast::block(statements)]);
+        assert!(eq_ignoring_spans(&parse_source("{\n    f a\n    g b\n}").unwrap(), &expected));
+    }
+
+    #[test]
+    pub fn multiline_groups() {
+        // a call over several lines
+        assert_eq!(parse_shape("(f\n 1\n 2)"), "[(f 1 2)]");
+        assert_eq!(parse_shape("(f a\ng b)"), "[(f a g b)]");
+        assert_eq!(parse_shape("(\n    f a\n)"), "[(f a)]");
+
+        // a parameter pattern over several lines
+        assert_eq!(parse_shape("send = (\n    socket\n    data\n) -> data"), "(= (-> data))");
+        let source = "f = a -> b -> a + b\n(f\n 1\n 2)";
+        assert_eq!(crate::run_str(source), Ok(Data::Integer(3)));
+        let source = "send = (\n    socket\n    data\n) -> data\nsend 1 2";
+        assert_eq!(crate::run_str(source), Ok(Data::Integer(2)));
+    }
+
+    #[test]
+    pub fn structural_equality() {
+        use crate::compiler::ast::{self, symbol, call, binop};
+        let parse_source = |source| parse(lex(Source::source(source)).unwrap()).unwrap();
+
+        // same structure, different spans
+        let a = parse_source("x = f 1 + y");
+        let b = parse_source("x   =   f   1\t+ y");
+        assert_ne!(a, b);
+        assert!(eq_ignoring_spans(&a, &b));
+
+        let expected = ast::block(vec![ast::assign(
+            "x",
+            binop("add", call(symbol("f"), ast::data(Data::Integer(1))), symbol("y")),
+        )]);
+        assert!(eq_ignoring_spans(&a, &expected));
+
+        // spans inside patterns and blocks are ignored too
+        let a = parse_source("f = (a, b) -> { a; b }");
+        let b = parse_source("f = ( a , b ) -> {\n  a\n  b\n}");
+        assert!(eq_ignoring_spans(&a, &b));
+
+        // but contents are not
+        assert!(!eq_ignoring_spans(&parse_source("x = 1"), &parse_source("x = 2")));
+        assert!(!eq_ignoring_spans(&parse_source("x = y"), &parse_source("y = x")));
+        assert!(!eq_ignoring_spans(&parse_source("f (g x)"), &parse_source("f g x")));
+    }
+
+    #[test]
+    pub fn symbols_compare_by_name() {
+        let last_symbol = |source| match parse(lex(Source::source(source)).unwrap()).unwrap().item {
+            AST::Block(b) => b.last().unwrap().clone(),
+            _ => unreachable!(),
+        };
+
+        // parsed from different sources, at different positions
+        let x = last_symbol("x");
+        let other_x = last_symbol("y = 1\nx");
+        let y = last_symbol("y");
+
+        assert_eq!(x.item, AST::Symbol("x".to_string()));
+        assert_eq!(x.item, other_x.item);
+        assert!(eq_ignoring_spans(&x, &other_x));
+        assert_ne!(x.item, y.item);
+        assert!(!eq_ignoring_spans(&x, &y));
+    }
+
+    #[test]
+    fn grouped_statements() {
+        for (source, expected) in &[
+            ("(a; b)",          "[a; b]"),
+            ("(a;\n b)",        "[a; b]"),
+            ("(x = 1; f x;)",   "[(= 1); (f x)]"),
+            ("(a;)",            "[a]"),
+            ("(a\n + b)",       "[(add a b)]"),
+        ] {
+            assert_eq!(parse_shape(source), *expected, "{}", source);
+        }
+
+        // commas still make a tuple
+        let tuple = parse(lex(Source::source("(a, b)")).unwrap()).unwrap();
+        match &tuple.item {
+            AST::Block(b) => assert!(matches!(&b[0].item, AST::Group(g) if matches!(g.item, AST::Tuple(_)))),
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    pub fn groups_override_precedence() {
+        for (source, expected) in &[
+            ("a + b * c",   "(add a (mul b c))"),
+            ("(a + b) * c", "(mul [(add a b)] c)"),
+            ("a * (b + c)", "(mul a [(add b c)])"),
+            ("a - b - c",   "(sub (sub a b) c)"),
+            ("a - (b - c)", "(sub a [(sub b c)])"),
+            ("(a == b) == c", "(equal [(equal a b)] c)"),
+            ("f g x",       "(f g x)"),
+            ("f (g x)",     "(f [(g x)])"),
+            ("a | b & c",   "(bit_or a (bit_and b c))"),
+            ("a & b << c",  "(bit_and a (shift_left b c))"),
+            ("a << b + c",  "(shift_left a (add b c))"),
+            ("a >> b >> c", "(shift_right (shift_right a b) c)"),
+            ("a | b == c",  "(equal (bit_or a b) c)"),
+            ("a + 1 < b",   "(less (add a 1) b)"),
+            ("a >= b << c", "(greater_equal a (shift_left b c))"),
+            ("~a + b",      "(add (bit_not a) b)"),
+            ("~f x",        "(bit_not (f x))"),
+            ("(f g) + x",   "(add [(f g)] x)"),
+            ("f (g + x)",   "(f [(add g x)])"),
+            ("(x -> x) y",  "([(-> x)] y)"),
+            ("x -> x y",    "(-> (x y))"),
+            ("((a + b)) * c", "(mul [[(add a b)]] c)"),
+            ("(a\n+ b) * c", "(mul [(add a b)] c)"),
+        ] {
+            assert_eq!(&parse_shape(source), expected, "while parsing '{}'", source);
+        }
+    }
+
+    #[test]
+    pub fn composition_applies_calls() {
+        for (source, expected) in &[
+            ("x.f",       "(. x f)"),
+            ("x . f y",   "(. x (f y))"),
+            ("x.f y",     "(. x (f y))"),
+            ("x.f(y)",    "(. x (f [y]))"),
+            ("x.f y . g", "(. (. x (f y)) g)"),
+        ] {
+            assert_eq!(&parse_shape(source), expected, "while parsing '{}'", source);
+        }
+    }
+
+    #[test]
+    pub fn try_postfix() {
+        for (source, expected) in &[
+            ("x?",        "(? x)"),
+            ("f x?",      "(? (f x))"),
+            ("f x ?",     "(? (f x))"),
+            ("f (x?)",    "(f [(? x)])"),
+            ("a?.b?",     "(? (. (? a) b))"),
+            ("a + b?",    "(add a (? b))"),
+            ("x = f y?",  "(= (? (f y)))"),
+        ] {
+            assert_eq!(&parse_shape(source), expected, "while parsing '{}'", source);
+        }
+    }
+
+    #[test]
+    pub fn groups_override_operators() {
+        let mut operators = Operators::new();
+        operators.add("<>", Operator::new(Prec::MulDiv, Assoc::Right, "join")).unwrap();
+
+        let tokens = lex(Source::source("(a <> b) <> c")).unwrap();
+        let ast = parse_with_operators(tokens, operators).unwrap();
+        assert_eq!(shape(&ast.item), "(join [(join a b)] c)");
+    }
+
+    #[test]
+    pub fn incremental() {
+        let tokens = lex(Source::source("x = {")).unwrap();
+        match parse_incremental(tokens) {
+            Incremental::Incomplete { consumed } => assert_eq!(consumed, 3),
+            other => panic!("Expected incomplete input, found {:?}", other),
+        }
+
+        let tokens = lex(Source::source("x = }")).unwrap();
+        match parse_incremental(tokens) {
+            Incremental::Error(_) => (),
+            other => panic!("Expected an error, found {:?}", other),
+        }
+
+        let tokens = lex(Source::source("x = { y }")).unwrap();
+        match parse_incremental(tokens) {
+            Incremental::Complete(_) => (),
+            other => panic!("Expected complete input, found {:?}", other),
+        }
+    }
+
+    #[test]
+    pub fn reparse_reuses_statements() {
+        use crate::compiler::ast::transform;
+
+        // marks every literal of the old tree, so reused statements stand out
+        let marked = |ast, marking: bool| transform(ast, &mut |node: Spanned<AST>| match node.item {
+            AST::Data(Data::Integer(n)) if marking || n > 100 => {
+                let n = if marking { n + 100 } else { n - 100 };
+                Spanned::new(AST::Data(Data::Integer(n)), node.span)
+            },
+            other => Spanned::new(other, node.span),
+        });
+
+        let old = parse(lex(Source::source("a = 1\nb = 2\nc = 3\nd = 4\ne = 5")).unwrap()).unwrap();
+        let old = marked(old, true);
+
+        // `c = 3` becomes `c = 30`
+        let source = Source::source("a = 1\nb = 2\nc = 30\nd = 4\ne = 5");
+        let reparsed = reparse(old.clone(), lex(source.clone()).unwrap(), 16..17).unwrap();
+
+        // the statements next to the edit are reparsed, the others are reused
+        let values = match &reparsed.item {
+            AST::Block(statements) => statements.iter().map(|s| match &s.item {
+                AST::Assign { expression, .. } => match expression.item {
+                    AST::Data(Data::Integer(n)) => n,
+                    ref other => panic!("Expected an integer, found {:?}", other),
+                },
+                other => panic!("Expected an assignment, found {:?}", other),
+            }).collect::<Vec<_>>(),
+            _ => unreachable!(),
+        };
+        assert_eq!(values, vec![101, 2, 30, 4, 105]);
+
+        // otherwise it's the same as parsing from scratch, spans and all
+        let parsed = parse(lex(source).unwrap()).unwrap();
+        assert_eq!(marked(reparsed, false), parsed);
+
+        // editing the last statement reuses all but the one before it
+        let source = Source::source("a = 1\nb = 2\nc = 3\nd = 4\ne = 50");
+        let reparsed = reparse(old, lex(source).unwrap(), 28..29).unwrap();
+        let values = match &reparsed.item {
+            AST::Block(statements) => statements.iter().map(|s| match &s.item {
+                AST::Assign { expression, .. } => expression.item.clone(),
+                other => panic!("Expected an assignment, found {:?}", other),
+            }).collect::<Vec<_>>(),
+            _ => unreachable!(),
+        };
+        let expected = [101, 102, 103, 4, 50].iter()
+            .map(|n| AST::Data(Data::Integer(*n)))
+            .collect::<Vec<_>>();
+        assert_eq!(values, expected);
+    }
+
+    #[test]
+    pub fn reparse_like_parse() {
+        for (old, new, edit) in &[
+            ("a\nb\nc",                 "a\n+ b\nc",                2..2),
+            ("a = 1\nb = 2\nc = 3",     "a = 1\n--| Two.\nb = 2\nc = 3", 6..6),
+            ("a = 1\nb = 2\nc = 3",     "a = 1\nb = 2\nc = 3\nd",  17..17),
+            ("a = 1\nb = 2\nc = 3\nd", "a = 1\nd",                6..18),
+            ("f a\ng b\nh c",           "f a\ng (b,\n x)\nh c",     6..7),
+            ("x = 1\ny = 2",            "x = 1\ny = (",             10..11),
+            ("x = 1",                   "",                         0..5),
+            // declarations change the precedence of statements that weren't edited
+            ("infixl 6 (<>) join\nx\na <> b * c", "infixl 8 (<>) join\nx\na <> b * c", 7..8),
+            ("x\nb * c",                "infixl 8 (<>) join\nx\nb * c", 0..0),
+            ("infixl 6 (<>) join\nx\na <> b", "x\na <> b",            0..19),
+        ] {
+            let old = parse(lex(Source::source(old)).unwrap()).unwrap();
+            let tokens = lex(Source::source(new)).unwrap();
+            assert_eq!(reparse(old, tokens.clone(), edit.clone()), parse(tokens), "{}", new);
+        }
+    }
+
+    #[test]
+    pub fn reparse_moves_statements() {
+        // the address of the right side of each assignment
+        let addresses = |ast: &Spanned<AST>| match &ast.item {
+            AST::Block(statements) => statements.iter().map(|s| match &s.item {
+                AST::Assign { expression, .. } => &**expression as *const Spanned<AST>,
+                other => panic!("Expected an assignment, found {:?}", other),
+            }).collect::<Vec<_>>(),
+            _ => unreachable!(),
+        };
+
+        let old = parse(lex(Source::source("a = 1\nb = 2\nc = 3\nd = 4\ne = 5")).unwrap()).unwrap();
+        let before = addresses(&old);
+
+        // `c = 3` becomes `c = 30`
+        let source = Source::source("a = 1\nb = 2\nc = 30\nd = 4\ne = 5");
+        let reparsed = reparse(old, lex(source.clone()).unwrap(), 16..17).unwrap();
+        let after = addresses(&reparsed);
+
+        // reused statements aren't copied or rebuilt;
+        // the reparsed ones are new, but may be allocated where old ones were freed
+        assert_eq!(before[0], after[0]);
+        assert_eq!(before[4], after[4]);
+
+        // but their spans are in the new source
+        assert_eq!(reparsed, parse(lex(source).unwrap()).unwrap());
+    }
+
+    #[test]
+    pub fn missing_assignment() {
+        for (source, at) in &[("x = ", 3), ("y = 1\nx =\n", 9)] {
+            let source = Source::source(source);
+            let error = parse(lex(source.clone()).unwrap()).unwrap_err();
+            assert_eq!(error.message, "Expected an expression after `=`");
+            assert_eq!(error.span, Span::point(&source, *at));
+
+            // a REPL waits for the rest of the assignment
+            let incremental = parse_incremental(lex(source).unwrap());
+            assert!(matches!(incremental, Incremental::Incomplete { .. }), "{:?}", incremental);
+        }
+
+        // `x = y = 1` still assigns across lines
+        assert!(parse(lex(Source::source("x =\ny = 1")).unwrap()).is_ok());
+    }
+
+    #[test]
+    pub fn recover_missing_assignment() {
+        use crate::compiler::ast::{self, symbol};
+
+        let source = Source::source("y = 1\nx = ");
+        let (ast, errors) = parse_recover(lex(source.clone()).unwrap()).unwrap();
+
+        let expected = ast::block(vec![
+            ast::assign("y", ast::data(Data::Integer(1))),
+            Spanned::new(AST::assign(
+                Spanned::new(ASTPattern::Symbol("x".to_string()), Span::empty()),
+                Spanned::new(AST::Error, Span::empty()),
+                None,
+            ), Span::empty()),
+        ]);
+        assert!(eq_ignoring_spans(&ast, &expected));
+
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].message, "Expected an expression after `=`");
+        assert_eq!(errors[0].span, Span::point(&source, 9));
+
+        // without errors it's just `parse`
+        let (ast, errors) = parse_recover(lex(Source::source("x = y")).unwrap()).unwrap();
+        assert!(eq_ignoring_spans(&ast, &ast::block(vec![ast::assign("x", symbol("y"))])));
+        assert!(errors.is_empty());
+    }
+
+    #[test]
+    pub fn recover_lex_errors() {
+        use crate::compiler::{ast::{self, binop}, lex::lex_recover};
+
+        let source = Source::source("x = \"\\q\" + 1\ny = §");
+        let (tokens, lexed) = lex_recover(source).unwrap();
+        let (ast, errors) = parse_recover(tokens.clone()).unwrap();
+
+        let error = || Spanned::new(AST::Error, Span::empty());
+        let expected = ast::block(vec![
+            ast::assign("x", binop("add", error(), ast::data(Data::Integer(1)))),
+            ast::assign("y", error()),
+        ]);
+        assert!(eq_ignoring_spans(&ast, &expected));
+
+        // the lexer has already reported them
+        assert_eq!(lexed.len(), 2);
+        assert!(errors.is_empty());
+
+        // outside of recovery, they're still errors
+        assert!(parse(tokens).is_err());
+    }
+
+    #[test]
+    pub fn custom_operator() {
+        let source = Source::source("a ++ b * c");
+        let mut operators = Operators::new();
+        operators.add("++", Operator::new(Prec::AddSub, Assoc::Left, "concat")).unwrap();
+
+        let ast = parse_with_operators(lex(source.clone()).unwrap(), operators).unwrap();
+        let symbol = |name: &str, offset| Spanned::new(
+            AST::Symbol(name.to_string()),
+            Span::new(&source, offset, name.len()),
+        );
+
+        // `*` binds tighter than `++`
+        let product = Spanned::new(
+            AST::ffi("mul", Spanned::new(
+                AST::Tuple(vec![symbol("b", 5), symbol("c", 9)]),
+                Span::new(&source, 5, 5),
+            )),
+            Span::new(&source, 5, 5),
+        );
+
+        assert_eq!(
+            ast,
+            Spanned::new(
+                AST::Block(vec![
+                    Spanned::new(
+                        AST::Form(vec![
+                            Spanned::new(AST::Symbol("concat".to_string()), Span::new(&source, 2, 2)),
+                            symbol("a", 0),
+                            product,
+                        ]),
+                        Span::new(&source, 0, 10),
+                    ),
+                ]),
+                Span::empty(),
+            )
+        );
+    }
+
+    #[test]
+    pub fn symbolic_operators() {
+        let mut operators = Operators::new();
+        operators.add(">>=", Operator::new(Prec::Logic, Assoc::Left, "bind")).unwrap();
+        operators.add("<$>", Operator::new(Prec::MulDiv, Assoc::Left, "fmap")).unwrap();
+
+        let tokens = lex(Source::source("m >>= f <$> x >>= g")).unwrap();
+        let ast = parse_with_operators(tokens, operators).unwrap();
+        assert_eq!(shape(&ast.item), "(bind (bind m (fmap f x)) g)");
+
+        // `~` and `?` are built-in on their own
+        let tokens = lex(Source::source("f ~x?")).unwrap();
+        assert_eq!(shape(&parse(tokens).unwrap().item), "(? (f (bit_not x)))");
+
+        let source = Source::source("m >>= f");
+        let result = parse(lex(source.clone()).unwrap());
+        assert_eq!(result, Err(Syntax::error("Unknown operator '>>='", &Span::new(&source, 2, 3))));
+    }
+
+    #[test]
+    pub fn peek_and_split_first_token() {
+        let tokens = lex(Source::source("a b")).unwrap();
+        let (first, rest) = advance(&tokens);
+        assert_eq!(first, peek(&tokens));
+        assert_eq!(first.map(|t| t.span.contents()), Some("a".to_string()));
+        assert_eq!(rest.len(), tokens.len() - 1);
+
+        // the last token is `End`, after which there's nothing
+        let (end, rest) = advance(&tokens[tokens.len() - 1..]);
+        assert_eq!(end.map(|t| &t.item), Some(&Token::End));
+        assert_eq!(peek(rest), None);
+        assert_eq!(advance(rest), (None, rest));
+        assert_eq!(advance(&[]), (None, &[] as Tokens));
+    }
+
+    #[test]
+    pub fn past_the_end() {
+        // running out of tokens is an error, rather than a panic
+        let mut parser = Parser::new(vec![]);
+        assert!(!parser.sep());
+        assert_eq!(parser.remaining(), &[] as Tokens);
+        assert!(parser.consume(Token::End).is_err());
+        assert!(parser.literal().is_err());
+
+        let mut parser = Parser::new(lex(Source::source("1")).unwrap());
+        assert_eq!(parser.literal().unwrap().item, AST::Data(Data::Integer(1)));
+        assert!(parser.consume(Token::End).is_ok());
+        assert!(parser.consume(Token::End).is_err());
+    }
+
+    #[test]
+    pub fn fixity_declarations() {
+        let source = "infixl 6 (<+>) concat\ninfixr 8 (^^) power\nprefix (~~) negate\na <+> b ^^ c ^^ ~~d <+> e";
+        let ast = parse(lex(Source::source(source)).unwrap()).unwrap();
+
+        let statements = match &ast.item { AST::Block(s) => s, _ => panic!() };
+        assert_eq!(statements[0].item, AST::fixity(Assoc::Left, Some(6), "<+>", Some("concat")));
+        assert_eq!(statements[1].item, AST::fixity(Assoc::Right, Some(8), "^^", Some("power")));
+        assert_eq!(statements[2].item, AST::fixity(Assoc::Prefix, None, "~~", Some("negate")));
+
+        // `^^` binds tighter than `<+>`, and `~~` tighter than both
+        assert_eq!(shape(&statements[3].item), "(concat (concat a (power b (power c (negate d)))) e)");
+    }
+
+    #[test]
+    pub fn builtin_fixities() {
+        // redeclaring a built-in changes how it parses, not what it calls
+        assert_eq!(parse_shape("infixl 6 (+)\na + b * c"), "(fixity +); (add a (mul b c))");
+        assert_eq!(parse_shape("infixl 8 (+)\na + b * c"), "(fixity +); (mul (add a b) c)");
+        assert_eq!(parse_shape("infixr 6 (-)\na - b - c"), "(fixity -); (sub a (sub b c))");
+
+        // a new operator can be declared without a function
+        let ast = parse(lex(Source::source("infixr 5 (^)")).unwrap()).unwrap();
+        let statements = match &ast.item { AST::Block(s) => s, _ => panic!() };
+        assert_eq!(statements[0].item, AST::fixity(Assoc::Right, Some(5), "^", None));
+
+        // a prefix minus negates
+        assert_eq!(parse_shape("prefix (-)\n2*-3"),  "(fixity -); (mul 2 (negate 3))");
+        assert_eq!(parse_shape("prefix (-)\n-x - y"), "(fixity -); (sub (negate x) y)");
+        assert!(parse(lex(Source::source("-x")).unwrap()).is_err());
+    }
+
+    #[test]
+    pub fn fixity_before_declaration() {
+        // operators are declared before anything is parsed
+        let source = "f ~~x <> y\ninfixl 0 (<>) join\nprefix (~~) negate";
+        let ast = parse(lex(Source::source(source)).unwrap()).unwrap();
+        let statements = match &ast.item { AST::Block(s) => s, _ => panic!() };
+        assert_eq!(shape(&statements[0].item), "(join (f (negate x)) y)");
+
+        // a symbol named like a fixity is just a symbol
+        assert_eq!(parse_shape("prefix (x)\ninfixl 6 y"), "(prefix [x]); (infixl 6 y)");
+    }
+
+    #[test]
+    pub fn split_operator_runs() {
+        // runs that aren't operators parse like their spaced out parts
+        assert_eq!(parse_shape("2*~3"), parse_shape("2 * ~3"));
+        assert_eq!(parse_shape("x=~1"), parse_shape("x = ~1"));
+        assert_eq!(parse_shape("~~x"),  parse_shape("~ ~x"));
+        assert_eq!(parse_shape("x?+1"), parse_shape("x? + 1"));
+        assert_eq!(parse_shape("x??"),  parse_shape("x? ?"));
+    }
+
+    #[test]
+    pub fn fixity_errors() {
+        let error = |source: &str| parse(lex(Source::source(source)).unwrap()).unwrap_err();
+
+        let source = Source::source("infixl 6 (<>) join\ninfixr 2 (<>) join");
+        assert_eq!(
+            parse(lex(source.clone()).unwrap()),
+            Err(Syntax::error("The operator '<>' has already been defined", &Span::new(&source, 29, 2))),
+        );
+
+        let source = Source::source("infixl 6 (+) add");
+        assert_eq!(
+            parse(lex(source.clone()).unwrap()),
+            Err(Syntax::error("The built-in operator '+' always calls its own function", &Span::new(&source, 13, 3))
+                .with_help("Remove the name of the function, i.e. `(+)`")),
+        );
+        let source = Source::source("prefix (*)");
+        assert_eq!(
+            parse(lex(source.clone()).unwrap()),
+            Err(Syntax::error("The built-in operator '*' can not be declared prefix", &Span::new(&source, 8, 1))
+                .with_help("Declare a new operator instead, like `infixl 6 (<+>) concat`")),
+        );
+        assert!(error("infixl 0 (.)").message.contains("can not be declared infix"));
+        assert!(error("infixr 10 (<>) join").message.contains("from 0 to 9"));
+
+        // only well-formed declarations are read, but reading others is an error rather than a panic
+        for source in &["", "infixl", "infixl 6 <> join", "prefix (x)", "infixr 6 (<>"] {
+            assert!(Parser::declared(&lex(Source::source(source)).unwrap()).is_err(), "{}", source);
+        }
+
+        // an operator declared without a function has nothing to call
+        let source = Source::source("infixr 5 (^)\n1 ^ 2");
+        assert_eq!(
+            parse(lex(source.clone()).unwrap()),
+            Err(Syntax::error("The operator '^' was declared without a function to call", &Span::new(&source, 15, 1))
+                .with_help("Name the function after the operator, like `infixr 5 (^) power`")),
+        );
+        assert!(error("prefix (~~)\n~~x").message.contains("without a function"));
+
+        // declarations aren't scoped, so can't be nested
+        let source = Source::source("x = {\n  infixl 6 (<>) join\n  a <> b\n}\nc <> d");
+        assert_eq!(
+            parse(lex(source.clone()).unwrap()),
+            Err(Syntax::error("A fixity declaration is only allowed at the top level", &Span::new(&source, 8, 6))
+                .with_help("Move it out of the block, operators are declared for the whole source")),
+        );
+        assert!(error("(a; prefix (~~) negate)").message.contains("top level"));
+
+        // operators in the parser's table can't be declared again
+        let mut operators = Operators::new();
+        operators.add("<>", Operator::new(Prec::MulDiv, Assoc::Right, "join")).unwrap();
+        let tokens = lex(Source::source("infixl 6 (<>) join")).unwrap();
+        assert!(parse_with_operators(tokens, operators).is_err());
+    }
+
+    #[test]
+    pub fn unknown_operator() {
+        let source = Source::source("a <> b");
+        let result = parse(lex(source.clone()).unwrap());
+        assert_eq!(result, Err(Syntax::error("Unknown operator '<>'", &Span::new(&source, 2, 2))));
+    }
 }
@@ -9,7 +9,7 @@ use crate::common::{
 };
 
 use crate::compiler::{
-    ast::{AST, ASTPattern, ArgPattern},
+    ast::{AST, ASTPattern, ArgPattern, Attribute},
     syntax::Syntax
 };
 
@@ -205,7 +205,16 @@ impl Rule {
                         .map(|b| Rule::expand_pattern(b, bindings))
                         .collect::<Result<Vec<_>, _>>()?;
                     Spanned::new(ASTPattern::Tuple(expanded), span)
-                }
+                },
+                ASTPattern::Typed(typed, annotation) => {
+                    let expanded = Rule::expand_pattern(*typed, bindings)?;
+                    Spanned::new(ASTPattern::Typed(Box::new(expanded), annotation), pattern.span)
+                },
+                ASTPattern::Where(guarded, guard) => {
+                    let expanded = Rule::expand_pattern(*guarded, bindings)?;
+                    let guard    = Rule::expand(*guard, bindings)?;
+                    Spanned::new(ASTPattern::guarded(expanded, guard), pattern.span)
+                },
             }
         )
     }
@@ -271,6 +280,22 @@ impl Rule {
             ),
 
             AST::Group(expression) => AST::group(Rule::expand(*expression, bindings)?),
+            AST::Try(expression)   => AST::try_(Rule::expand(*expression, bindings)?),
+            AST::Spread(expression) => AST::spread(Rule::expand(*expression, bindings)?),
+            // TODO: should block labels be hygienic?
+            AST::LabeledBlock { label, block } => AST::labeled_block(
+                &label, Rule::expand(*block, bindings)?,
+            ),
+            AST::Break { label, expression } => AST::break_(
+                &label, Rule::expand(*expression, bindings)?,
+            ),
+            AST::Assert { expression, message } => AST::assert(
+                Rule::expand(*expression, bindings)?,
+                match message {
+                    Some(m) => Some(Rule::expand(*m, bindings)?),
+                    None    => None,
+                },
+            ),
 
             // Appy the transformation to the left and right sides of the composition
             AST::Composition { argument, function } => {
@@ -290,10 +315,10 @@ impl Rule {
             },
 
             // replace the variables in the patterns and the expression
-            AST::Assign { pattern, expression } => {
+            AST::Assign { pattern, expression, doc } => {
                 let p = Rule::expand_pattern(*pattern, bindings)?;
                 let e = Rule::expand(*expression, bindings)?;
-                AST::assign(p, e)
+                AST::assign(p, e, doc)
             },
             AST::Lambda { pattern, expression } => {
                 let p = Rule::expand_pattern(*pattern, bindings)?;
@@ -327,6 +352,26 @@ impl Rule {
                 &name,
                 Rule::expand(*expression, bindings)?
             ),
+
+            // attribute names aren't bound by macros, but their arguments are
+            AST::Attributed { attributes, node } => {
+                let mut expanded = vec![];
+                for Spanned { item: attribute, span } in attributes {
+                    let arguments = attribute.arguments.into_iter()
+                        .map(|a| Rule::expand(a, bindings))
+                        .collect::<Result<Vec<_>, _>>()?;
+                    expanded.push(Spanned::new(Attribute { name: attribute.name, arguments }, span));
+                }
+                AST::attributed(expanded, Rule::expand(*node, bindings)?)
+            },
+
+            // types aren't bound by macros
+            t @ AST::TypeDef { .. } => t,
+            i @ AST::Import(_) => i,
+            f @ AST::Fixity { .. } => f,
+            // quotes are left as-is for the macros that use them
+            q @ AST::Quote(_) => q,
+            AST::Error => AST::Error,
         };
 
         return Ok(Spanned::new(item, tree.span));
@@ -57,6 +57,8 @@ impl Scope {
 #[derive(Debug, Clone, PartialEq)]
 pub enum SST {
     Symbol(UniqueSymbol),
+    /// A symbol bound in the `Prelude`, identified by its global slot.
+    Global(usize),
     Data(Data),
     Block(Vec<Spanned<SST>>),
     Assign {
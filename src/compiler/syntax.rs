@@ -1,25 +1,102 @@
 use std::fmt;
 use crate::common::span::Span;
 
+/// How serious a static error is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    /// Stops compilation.
+    Error,
+    /// Reported, but doesn't stop compilation.
+    Warning,
+}
+
+impl fmt::Display for Severity {
+    fn fmt (&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Severity::Error   => write!(f, "Error"),
+            Severity::Warning => write!(f, "Warning"),
+        }
+    }
+}
+
 // TODO: rename to Static?
 /// Represents a static error (syntax, semantics, etc.) found at compile time
+/// Errors are built up fluently, e.g.:
+/// ```ignore
+/// Syntax::error("Unexpected token", &span)
+///     .with_note(&other, "The block started here")
+///     .with_help("Try adding a closing bracket")
+/// ```
 #[derive(Debug, PartialEq, Eq)]
 pub struct Syntax {
-    pub message: String,
-    pub span:    Span,
+    pub message:  String,
+    pub span:     Span,
+    /// A suggestion for how to fix the error.
+    pub help:     Option<String>,
+    /// Other relevant places in the source, each with an explanation.
+    pub notes:    Vec<(Span, String)>,
+    pub severity: Severity,
 }
 
 impl Syntax {
     /// Creates a new static error.
     pub fn error(message: &str, span: &Span) -> Syntax {
-        Syntax { message: message.to_string(), span: span.clone() }
+        Syntax {
+            message:  message.to_string(),
+            span:     span.clone(),
+            help:     None,
+            notes:    vec![],
+            severity: Severity::Error,
+        }
+    }
+
+    /// Moves the error to a different span.
+    pub fn with_span(mut self, span: &Span) -> Syntax {
+        self.span = span.clone();
+        self
+    }
+
+    /// Adds a suggestion for how to fix the error,
+    /// replacing any previous suggestion.
+    pub fn with_help(mut self, help: &str) -> Syntax {
+        self.help = Some(help.to_string());
+        self
+    }
+
+    /// Points out another relevant place in the source.
+    /// Notes are displayed in the order they're added.
+    pub fn with_note(mut self, span: &Span, note: &str) -> Syntax {
+        self.notes.push((span.clone(), note.to_string()));
+        self
+    }
+
+    /// Sorts errors by where they are in the source, see `Span`'s `Ord`,
+    /// so they can be listed top-to-bottom.
+    /// Errors at the same place keep the order they were found in.
+    pub fn sort(errors: &mut [Syntax]) {
+        errors.sort_by(|a, b| a.span.cmp(&b.span));
+    }
+
+    /// Sets how serious the error is, see `Severity`.
+    pub fn with_severity(mut self, severity: Severity) -> Syntax {
+        self.severity = severity;
+        self
     }
 }
 
 impl fmt::Display for Syntax {
     fn fmt (&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         if !self.span.is_empty() { fmt::Display::fmt(&self.span, f)? };
-        write!(f, "Syntax Error: {}", self.message)
+        write!(f, "Syntax {}: {}", self.severity, self.message)?;
+
+        for (span, note) in self.notes.iter() {
+            writeln!(f)?;
+            if !span.is_empty() { fmt::Display::fmt(span, f)? };
+            write!(f, "Note: {}", note)?;
+        }
+
+        if let Some(help) = &self.help { write!(f, "\nHelp: {}", help)?; }
+        Ok(())
     }
 }
 
@@ -50,4 +127,75 @@ Syntax Error: Unexpected token '\"Hello, world!\"'\
         let result = format!("{}", error);
         assert_eq!(result, target);
     }
+
+    #[test]
+    fn named_source() {
+        use crate::compiler::{lex, parse};
+
+        let source = Source::named("<repl>", "x = )");
+        let error = lex(source).and_then(parse).unwrap_err();
+        assert!(format!("{}", error).starts_with("In <repl>:1:5\n"), "{}", error);
+    }
+
+    #[test]
+    fn builder() {
+        let source = Rc::new(Source::source("x = { y\nz = 1"));
+        let error = Syntax::error("Expected a closing bracket", &Span::point(&source, 13))
+            .with_note(&Span::new(&source, 4, 1), "The block started here")
+            .with_help("Add a '}' to close the block")
+            .with_severity(Severity::Warning);
+
+        assert_eq!(error.message, "Expected a closing bracket");
+        assert_eq!(error.span, Span::point(&source, 13));
+        assert_eq!(error.help, Some("Add a '}' to close the block".to_string()));
+        assert_eq!(error.notes, vec![(Span::new(&source, 4, 1), "The block started here".to_string())]);
+        assert_eq!(error.severity, Severity::Warning);
+
+        let moved = Syntax::error("Moved", &Span::empty()).with_span(&Span::new(&source, 0, 1));
+        assert_eq!(moved.span, Span::new(&source, 0, 1));
+        assert_eq!(moved.severity, Severity::Error);
+    }
+
+    #[test]
+    fn sort_by_position() {
+        let source = Rc::new(Source::source("a b c"));
+        let error = |message, offset| Syntax::error(message, &Span::new(&source, offset, 1));
+
+        let mut errors = vec![
+            Syntax::error("nowhere", &Span::empty()),
+            error("c", 4),
+            error("a", 0),
+            error("b, first", 2),
+            error("b, second", 2),
+        ];
+        Syntax::sort(&mut errors);
+
+        let messages = errors.iter().map(|e| e.message.as_str()).collect::<Vec<_>>();
+        assert_eq!(messages, vec!["a", "b, first", "b, second", "c", "nowhere"]);
+    }
+
+    #[test]
+    fn builder_display() {
+        let source = Rc::new(Source::source("x = { y"));
+        let error = Syntax::error("Expected a closing bracket", &Span::new(&source, 6, 1))
+            .with_note(&Span::new(&source, 4, 1), "The block started here")
+            .with_help("Add a '}' to close the block");
+
+        let target = "In ./source:1:7
+   |
+ 1 | x = { y
+   |       ^
+   |
+Syntax Error: Expected a closing bracket
+In ./source:1:5
+   |
+ 1 | x = { y
+   |     ^
+   |
+Note: The block started here
+Help: Add a '}' to close the block\
+";
+
+        assert_eq!(format!("{}", error), target);
+    }
 }
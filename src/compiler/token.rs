@@ -1,11 +1,15 @@
 use std::fmt::Display;
 use crate::common::data::Data;
 
+#[cfg(feature = "serde")]
+use serde::Serialize;
+
 /// These are the different tokens the lexer will output.
 /// `Token`s with data contain that data,
 /// e.g. a boolean will be a `Data::Boolean(...)`, not just a string.
 /// `Token`s can be spanned using `Spanned<Token>`.
 #[derive(Debug, Clone, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
 pub enum Token {
     // Delimiters
     OpenBracket,
@@ -14,21 +18,45 @@ pub enum Token {
     CloseParen,
     Sep,
     Pair,
+    Spread,
 
     // Keywords
     Syntax,
+    Type,
+    Use,
+    Import,
     Assign,
     Lambda,
+    // `:`, followed by a type
+    Colon,
     Compose,
+    Question,
     Print,
     Magic,
+    Assert,
+    Break,
+    // `fn`, followed by a block of arms
+    Fn,
+    // `#`, the parameter of the parens it's in
+    Placeholder,
+    // `` ` ``, followed by a group of tokens to quote
+    Quote,
+    // `~` inside of a quote
+    Unquote,
+    // `@`, followed by the name of an attribute
+    At,
+    // `--|`, followed by the documentation
+    Doc(String),
     // pseudokeywords
     Keyword(String),
+    // `'name:`, before a block
+    BlockLabel(String),
 
     // Datatypes
     // TODO: just have one variant, `Data`
     Unit,
-    Number(Data),
+    Integer(Data),
+    Real(Data),
     String(Data),
     Boolean(Data),
 
@@ -41,6 +69,19 @@ pub enum Token {
     Mul, Div, Rem,
 
     Equal,
+    Less, Greater,
+    LessEqual, GreaterEqual,
+
+    // Bitwise
+    Shl, Shr,
+    BitAnd, BitOr,
+    BitNot,
+
+    // user-defined, looked up in the parser's operator table
+    Op(String),
+
+    // in place of what couldn't be lexed, see `lex_recover`
+    Error,
 
     // EoS
     End,
@@ -57,16 +98,31 @@ impl Display for Token {
             Token::CloseParen   => "a closing paren",
             Token::Sep          => "a separator",
             Token::Syntax       => "a syntax definition",
+            Token::Type         => "a type definition",
+            Token::Use          => "a use statement",
+            Token::Import       => "an import statement",
             Token::Assign       => "an assignment",
             Token::Lambda       => "a lambda",
+            Token::Colon        => "a type annotation",
             Token::Compose      => "a composition",
+            Token::Question     => "a question mark",
             Token::Unit         => "the Unit, '()'",
             Token::Pair         => "a tuple",
+            Token::Spread       => "a spread",
             Token::Print        => "a print keyword",
             Token::Magic        => "a magic keyword",
+            Token::Assert       => "an assertion",
+            Token::Break        => "a break",
+            Token::Fn           => "a function literal",
+            Token::Placeholder  => "a placeholder",
+            Token::BlockLabel(_) => "a block label",
+            Token::Quote        => "a quote",
+            Token::Unquote      => "an unquote",
+            Token::At           => "an attribute",
             Token::Symbol       => "a symbol",
             Token::Label        => "a Label", // capitilized to mimic actual labels
-            Token::Number(_)    => "a number",
+            Token::Integer(_)   => "an integer",
+            Token::Real(_)      => "a real number",
             Token::String(_)    => "a string",
             Token::Add          => "an addition",
             Token::Sub          => "a subtraction",
@@ -74,8 +130,20 @@ impl Display for Token {
             Token::Div          => "a division",
             Token::Rem          => "a remainder operator",
             Token::Equal        => "an equality test",
+            Token::Less         => "a less than comparison",
+            Token::Greater      => "a greater than comparison",
+            Token::LessEqual    => "a less than or equal comparison",
+            Token::GreaterEqual => "a greater than or equal comparison",
+            Token::Shl          => "a left shift",
+            Token::Shr          => "a right shift",
+            Token::BitAnd       => "a bitwise and",
+            Token::BitOr        => "a bitwise or",
+            Token::BitNot       => "a bitwise not",
+            Token::Error        => "something that couldn't be lexed",
             Token::End          => "end of source",
             Token::Keyword(k) => { return write!(f, "the pseudokeyword '{}", k); },
+            Token::Op(o)      => { return write!(f, "the operator '{}'",       o); },
+            Token::Doc(_)     => "a doc comment",
             Token::Boolean(b) => { return write!(f, "the boolean {}",        b); },
         };
         write!(f, "{}", message)
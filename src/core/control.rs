@@ -1,17 +1,32 @@
 use crate::common::data::Data;
-use crate::core::extract::triop;
+use crate::core::{extract::triop, ffi::FFIError};
 
 /// An implementation of an if statement, as an FFI.
 /// Interesting idea, not sure if I'm going to keep it.
-pub fn if_choice(data: Data) -> Result<Data, String> {
-    if let (Data::Boolean(condition), option_a, option_b) = triop(data) {
-        let choice = if condition { option_a } else { option_b };
-        Ok(choice)
-    } else {
-        Err("\
-            Expected the condition to be a boolean.\n\
-            Note that Passerine does not have a notion of truthiness."
-            .to_string()
-        )
-    }
+/// The condition must be a boolean, see `Data::as_bool`.
+pub fn if_choice(data: Data) -> Result<Data, FFIError> {
+    let (condition, option_a, option_b) = triop(data);
+    let choice = if condition.as_bool()? { option_a } else { option_b };
+    Ok(choice)
+}
+
+/// Every failed assertion's error starts with this,
+/// and blames the condition, see `FFIError`.
+pub const ASSERTION_FAILED: &str = "Assertion failed";
+
+/// Raises an error if an assertion's condition is false.
+/// Takes the condition, the source of the condition, and a message,
+/// where the latter two may be `()`.
+pub fn assert(data: Data) -> Result<Data, FFIError> {
+    let (condition, source, message) = triop(data);
+    let holds = condition.as_bool().map_err(|e| FFIError::from(e).blame(0))?;
+    if holds { return Ok(Data::Unit); }
+
+    let message = match (source, message) {
+        (_, Data::String(message)) => format!("{}: {}", ASSERTION_FAILED, message),
+        (Data::String(source), _)  => format!("{}: `{}`", ASSERTION_FAILED, source),
+        _ => ASSERTION_FAILED.to_string(),
+    };
+
+    return Err(FFIError::from(message).blame(0));
 }
@@ -2,28 +2,95 @@ use std::{
     rc::Rc,
     collections::HashMap,
 };
+#[cfg(test)]
+use std::cell::Cell;
 use crate::common::data::Data;
+use crate::vm::trace::Trace;
 
 // TODO: have FFI function keep track of number of arguments
 // it takes, so this invariant can be checket at compile time?
 // TODO: find size of FFI function (128 bytes on 64-bit?)
+/// The error returned by a `FFIFunction`.
+/// If the function knows which of its operands is at fault,
+/// e.g. the divisor of a division by zero,
+/// `operand` is its index, and the VM points at that operand as the cause.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FFIError {
+    pub message: String,
+    pub operand: Option<usize>,
+}
+
+impl FFIError {
+    /// Creates a new error that doesn't blame any operand.
+    pub fn new(message: &str) -> FFIError {
+        FFIError { message: message.to_string(), operand: None }
+    }
+
+    /// Blames the operand at an index for the error.
+    pub fn blame(mut self, operand: usize) -> FFIError {
+        self.operand = Some(operand);
+        self
+    }
+}
+
+impl From<String> for FFIError {
+    fn from(message: String) -> FFIError {
+        FFIError { message, operand: None }
+    }
+}
+
+impl From<&str> for FFIError {
+    fn from(message: &str) -> FFIError {
+        FFIError::new(message)
+    }
+}
+
+/// Raised by a method of `Data`, e.g. `Data::as_bool`,
+/// the VM adds the spans back when it reports the error.
+impl From<Trace> for FFIError {
+    fn from(trace: Trace) -> FFIError {
+        FFIError::new(trace.message())
+    }
+}
+
 /// Represents a single FFI function,
 /// Bound at compile time,
 /// Through the use of `FFI`.
-#[derive(Clone)]
-pub struct FFIFunction(Rc<dyn Fn(Data) -> Result<Data, String>>);
+pub struct FFIFunction(Rc<dyn Fn(Data) -> Result<Data, FFIError>>);
+
+#[cfg(test)]
+thread_local! {
+    /// Test hook: while set, cloning an `FFIFunction` panics.
+    /// Used to check that the VM stays consistent when a clone panics.
+    pub static PANIC_ON_CLONE: Cell<bool> = const { Cell::new(false) };
+}
 
 impl FFIFunction {
-    pub fn new(function: Box<dyn Fn(Data) -> Result<Data, String>>) -> FFIFunction {
-        FFIFunction(Rc::new(function))
+    /// Wraps a function, which may fail with a plain message,
+    /// or with a `FFIError` that blames one of its operands.
+    pub fn new<E: Into<FFIError> + 'static>(
+        function: Box<dyn Fn(Data) -> Result<Data, E>>,
+    ) -> FFIFunction {
+        FFIFunction(Rc::new(move |data| function(data).map_err(Into::into)))
     }
 
     #[inline]
-    pub fn call(&self, data: Data) -> Result<Data, String> {
+    pub fn call(&self, data: Data) -> Result<Data, FFIError> {
         (self.0)(data)
     }
 }
 
+impl Clone for FFIFunction {
+    fn clone(&self) -> FFIFunction {
+        #[cfg(test)]
+        if PANIC_ON_CLONE.with(Cell::get) {
+            panic!("Cloned an FFIFunction while PANIC_ON_CLONE was set");
+        }
+
+        FFIFunction(Rc::clone(&self.0))
+    }
+}
+
 impl std::fmt::Debug for FFIFunction {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(f, "FFIFunction(...)")
@@ -0,0 +1,101 @@
+use crate::common::data::Data;
+use crate::core::extract::binop;
+
+/// Returns the item of a tuple at an index, i.e. `(tuple, index)`.
+/// Strings can be indexed too, returning the character at that index
+/// as a string of its own.
+/// Strings are indexed by character (i.e. unicode scalar value), not by byte,
+/// so multi-byte characters are never split.
+/// Indices start at zero, and must be integers.
+/// Negative indices are not supported, rather than counting from the end,
+/// so that an off-by-one can't silently return the last item.
+pub fn index(data: Data) -> Result<Data, String> {
+    let (indexed, index) = binop(data);
+    let index = to_index(index)?;
+
+    let items = match indexed {
+        Data::Tuple(t)  => t,
+        Data::Unit      => vec![],
+        Data::String(s) => return match s.chars().nth(index) {
+            Some(c) => Ok(Data::String(c.to_string().into())),
+            None => Err(format!(
+                "The string is of length {}, so the index {} is out-of-bounds",
+                s.chars().count(), index,
+            )),
+        },
+        other => return Err(format!("The data '{}' is not a tuple, and can not be indexed", other)),
+    };
+
+    return match items.get(index) {
+        Some(item) => Ok(item.clone()),
+        None => Err(format!(
+            "The tuple is of length {}, so the index {} is out-of-bounds",
+            items.len(), index,
+        )),
+    };
+}
+
+/// Checks that an index is a non-negative integer.
+fn to_index(index: Data) -> Result<usize, String> {
+    match index {
+        Data::Integer(n) if n < 0 => Err(format!(
+            "The index {} is negative, indices must be zero or greater", n,
+        )),
+        Data::Integer(n) => Ok(n as usize),
+        other => Err(format!("The index '{}' is not an integer", other)),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn items() -> Data {
+        Data::Tuple(vec![Data::Boolean(true), Data::Unit])
+    }
+
+    #[test]
+    fn valid() {
+        let item = index(Data::Tuple(vec![items(), Data::Integer(1)]));
+        assert_eq!(item, Ok(Data::Unit));
+    }
+
+    #[test]
+    fn negative() {
+        let error = index(Data::Tuple(vec![items(), Data::Integer(-1)])).unwrap_err();
+        assert!(error.contains("negative"));
+    }
+
+    #[test]
+    fn non_integer() {
+        for bad in vec![Data::Real(1.0), Data::String("1".into())] {
+            let error = index(Data::Tuple(vec![items(), bad])).unwrap_err();
+            assert!(error.contains("not an integer"));
+        }
+    }
+
+    #[test]
+    fn out_of_bounds() {
+        assert!(index(Data::Tuple(vec![items(), Data::Integer(2)])).is_err());
+    }
+
+    fn string() -> Data {
+        Data::String("añ😀b".into())
+    }
+
+    #[test]
+    fn string_by_character() {
+        for (i, expected) in ["a", "ñ", "😀", "b"].iter().enumerate() {
+            let item = index(Data::Tuple(vec![string(), Data::Integer(i as i64)]));
+            assert_eq!(item, Ok(Data::String((*expected).into())));
+        }
+    }
+
+    #[test]
+    fn string_out_of_bounds() {
+        // there are 4 characters, but 8 bytes
+        let error = index(Data::Tuple(vec![string(), Data::Integer(4)])).unwrap_err();
+        assert_eq!(error, "The string is of length 4, so the index 4 is out-of-bounds");
+        assert!(index(Data::Tuple(vec![string(), Data::Integer(-1)])).is_err());
+    }
+}
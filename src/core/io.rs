@@ -13,5 +13,5 @@ pub fn print(data: Data) -> Result<Data, String> {
 }
 
 pub fn to_string(data: Data) -> Result<Data, String> {
-    Ok(Data::String(format!("{}", data)))
+    Ok(Data::String(data.to_string().into()))
 }
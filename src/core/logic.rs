@@ -1,5 +1,7 @@
+use std::cmp::Ordering;
+
 use crate::common::data::Data;
-use crate::core::extract::binop;
+use crate::core::{extract::binop, ffi::FFIError};
 
 // TODO: implement equality rather than just deriving PartialEq on Data.
 
@@ -12,13 +14,36 @@ pub fn equal(data: Data) -> Result<Data, String> {
     return Ok(Data::Boolean(left == right));
 }
 
-pub fn greater(data: Data) -> Result<Data, String> {
-    // TODO: type coercion
-    let result = match binop(data) {
-        (Data::Real(left),    Data::Real(right))    => left > right,
-        (Data::Integer(left), Data::Integer(right)) => left > right,
-        _ => Err("Expected two numbers of the same type")?,
-    };
+/// Logically negates a boolean, see `Data::not`.
+pub fn not(data: Data) -> Result<Data, FFIError> {
+    return Ok(data.not()?);
+}
 
+/// Compares two values, see `Data::compare`,
+/// returning whether the ordering passes the test.
+/// Unordered values, i.e. NaNs, never pass.
+fn compare(data: Data, test: fn(Ordering) -> bool) -> Result<Data, String> {
+    let (left, right) = binop(data);
+    let result = left.compare(&right)?.map_or(false, test);
     return Ok(Data::Boolean(result));
 }
+
+/// Returns `true` if the left operand is greater than the right.
+pub fn greater(data: Data) -> Result<Data, String> {
+    return compare(data, |o| o == Ordering::Greater);
+}
+
+/// Returns `true` if the left operand is less than the right.
+pub fn less(data: Data) -> Result<Data, String> {
+    return compare(data, |o| o == Ordering::Less);
+}
+
+/// Returns `true` if the left operand is greater than or equal to the right.
+pub fn greater_equal(data: Data) -> Result<Data, String> {
+    return compare(data, |o| o != Ordering::Less);
+}
+
+/// Returns `true` if the left operand is less than or equal to the right.
+pub fn less_equal(data: Data) -> Result<Data, String> {
+    return compare(data, |o| o != Ordering::Greater);
+}
@@ -1,12 +1,126 @@
 use crate::common::data::Data;
-use crate::core::extract::binop;
+use crate::core::{extract::binop, ffi::FFIError};
+
+#[cfg(feature = "bignum")]
+use num_bigint::BigInt;
+
+/// Applies an operation to two integers, at least one of which is a `BigInt`.
+/// The result is demoted to a 64-bit integer if it fits.
+#[cfg(feature = "bignum")]
+fn big_binop(
+    left:  Data,
+    right: Data,
+    op:    fn(BigInt, BigInt) -> BigInt,
+) -> Result<Data, String> {
+    let widen = |data| match data {
+        Data::Integer(n) => Ok(BigInt::from(n)),
+        Data::BigInt(n)  => Ok(n),
+        _ => Err("Arithmetic between unsupported datatypes".to_string()),
+    };
+
+    return Ok(Data::big_integer(op(widen(left)?, widen(right)?)));
+}
+
+/// Applies a checked operation to two 64-bit integers,
+/// promoting the result to a `BigInt` on overflow.
+#[cfg(feature = "bignum")]
+fn promote(
+    left:    i64,
+    right:   i64,
+    checked: fn(i64, i64) -> Option<i64>,
+    op:      fn(BigInt, BigInt) -> BigInt,
+) -> Data {
+    match checked(left, right) {
+        Some(n) => Data::Integer(n),
+        None    => Data::big_integer(op(BigInt::from(left), BigInt::from(right))),
+    }
+}
+
+/// The error raised when dividing by zero,
+/// which blames the divisor, see `FFIError`.
+pub const DIVISION_BY_ZERO: &str = "Division by zero";
+
+/// How arithmetic treats an integer mixed with a real, e.g. `2 + 3.0`.
+/// Arithmetic on two integers always stays integer,
+/// and arithmetic on two reals always stays real.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Coercion {
+    /// The integer is promoted to a real, so the result is a real,
+    /// e.g. `2 + 3.0` is `5.0`.
+    Promote,
+    /// Mixing the two is an error, one must be converted explicitly.
+    Strict,
+}
+
+/// Destructures the operands of an arithmetic operation,
+/// promoting an integer mixed with a real to a real, see `Coercion::Promote`.
+fn operands(data: Data) -> (Data, Data) {
+    match binop(data) {
+        (Data::Integer(l), Data::Real(r)) => (Data::Real(l as f64), Data::Real(r)),
+        (Data::Real(l), Data::Integer(r)) => (Data::Real(l), Data::Real(r as f64)),
+        #[cfg(feature = "bignum")]
+        (l @ Data::BigInt(_), r @ Data::Real(_)) | (l @ Data::Real(_), r @ Data::BigInt(_)) => (
+            l.to_real().unwrap(),
+            r.to_real().unwrap(),
+        ),
+        other => other,
+    }
+}
+
+/// Whether some data is an integer, big or not.
+fn is_integer(data: &Data) -> bool {
+    match data {
+        Data::Integer(_) => true,
+        #[cfg(feature = "bignum")]
+        Data::BigInt(_)  => true,
+        _ => false,
+    }
+}
+
+/// Whether the operands of a division are numbers, the latter of which is zero.
+fn divides_by_zero(left: &Data, right: &Data) -> bool {
+    let zero = match right {
+        Data::Real(n)    => *n == 0.0,
+        Data::Integer(n) => *n == 0,
+        #[cfg(feature = "bignum")]
+        Data::BigInt(n)  => *n == BigInt::from(0),
+        _ => false,
+    };
+
+    return zero && (is_integer(left) || matches!(left, Data::Real(_)));
+}
+
+/// Applies an arithmetic operation, like `add`,
+/// unless it mixes an integer with a real, see `Coercion::Strict`.
+pub fn strict(data: Data, op: fn(Data) -> Result<Data, FFIError>) -> Result<Data, FFIError> {
+    if let Data::Tuple(ref t) = data {
+        if let [l, r] = &t[..] {
+            let real = |d: &Data| matches!(d, Data::Real(_));
+            if is_integer(l) && real(r) || real(l) && is_integer(r) {
+                return Err(format!(
+                    "Arithmetic between the integer and real {} and {}, convert one to match the other",
+                    l, r,
+                ).into());
+            }
+        }
+    }
+
+    return op(data);
+}
 
 /// Adds two numbers, concatenates two strings.
-pub fn add(data: Data) -> Result<Data, String> {
-    let result = match binop(data) {
+/// Like the other arithmetic operations,
+/// an integer mixed with a real is promoted, see `Coercion`.
+pub fn add(data: Data) -> Result<Data, FFIError> {
+    let result = match operands(data) {
         (Data::Real(l),    Data::Real(r))    => Data::Real(l + r),
+        #[cfg(not(feature = "bignum"))]
         (Data::Integer(l), Data::Integer(r)) => Data::Integer(l + r),
-        (Data::String(l),  Data::String(r))  => Data::String(format!("{}{}", l, r)),
+        #[cfg(feature = "bignum")]
+        (Data::Integer(l), Data::Integer(r)) => promote(l, r, i64::checked_add, |l, r| l + r),
+        #[cfg(feature = "bignum")]
+        (l @ Data::BigInt(_), r) | (l, r @ Data::BigInt(_)) => big_binop(l, r, |l, r| l + r)?,
+        (Data::String(l),  Data::String(r))  => Data::String(format!("{}{}", l, r).into()),
         _ => Err("Addition between unsupported datatypes")?,
     };
 
@@ -14,21 +128,47 @@ pub fn add(data: Data) -> Result<Data, String> {
 }
 
 /// Subtraction between two numbers.
-pub fn sub(data: Data) -> Result<Data, String> {
-    let result = match binop(data) {
+pub fn sub(data: Data) -> Result<Data, FFIError> {
+    let result = match operands(data) {
         (Data::Real(l),    Data::Real(r))    => Data::Real(l - r),
+        #[cfg(not(feature = "bignum"))]
         (Data::Integer(l), Data::Integer(r)) => Data::Integer(l - r),
+        #[cfg(feature = "bignum")]
+        (Data::Integer(l), Data::Integer(r)) => promote(l, r, i64::checked_sub, |l, r| l - r),
+        #[cfg(feature = "bignum")]
+        (l @ Data::BigInt(_), r) | (l, r @ Data::BigInt(_)) => big_binop(l, r, |l, r| l - r)?,
         _ => Err("Subtraction between unsupported datatypes")?,
     };
 
     return Ok(result);
 }
 
+/// Negates a number, for a declared prefix minus, i.e. `prefix (-)`.
+pub fn negate(data: Data) -> Result<Data, FFIError> {
+    let result = match data {
+        Data::Real(n)    => Data::Real(-n),
+        #[cfg(not(feature = "bignum"))]
+        Data::Integer(n) => Data::Integer(n.checked_neg().ok_or("Integer overflow in negation")?),
+        #[cfg(feature = "bignum")]
+        Data::Integer(n) => promote(0, n, i64::checked_sub, |l, r| l - r),
+        #[cfg(feature = "bignum")]
+        Data::BigInt(n)  => Data::big_integer(-n),
+        _ => Err("Negation of an unsupported datatype")?,
+    };
+
+    return Ok(result);
+}
+
 /// Multiplication between two numbers.
-pub fn mul(data: Data) -> Result<Data, String> {
-    let result = match binop(data) {
+pub fn mul(data: Data) -> Result<Data, FFIError> {
+    let result = match operands(data) {
         (Data::Real(l),    Data::Real(r))    => Data::Real(l * r),
+        #[cfg(not(feature = "bignum"))]
         (Data::Integer(l), Data::Integer(r)) => Data::Integer(l * r),
+        #[cfg(feature = "bignum")]
+        (Data::Integer(l), Data::Integer(r)) => promote(l, r, i64::checked_mul, |l, r| l * r),
+        #[cfg(feature = "bignum")]
+        (l @ Data::BigInt(_), r) | (l, r @ Data::BigInt(_)) => big_binop(l, r, |l, r| l * r)?,
         _ => Err("Multiplication between unsupported datatypes")?,
     };
 
@@ -37,12 +177,18 @@ pub fn mul(data: Data) -> Result<Data, String> {
 
 /// Division between two numbers.
 /// Raises a runtime error if there is a division by zero.
-pub fn div(data: Data) -> Result<Data, String> {
-    let result = match binop(data) {
-        (Data::Real(_), Data::Real(n)) if n == 0.0 => Err("Division by zero")?,
+pub fn div(data: Data) -> Result<Data, FFIError> {
+    let result = match operands(data) {
+        (l, r) if divides_by_zero(&l, &r) => Err(FFIError::new(DIVISION_BY_ZERO).blame(1))?,
         (Data::Real(l), Data::Real(r)) => Data::Real(l / r),
-        (Data::Integer(_), Data::Integer(n)) if n == 0 => Err("Division by zero")?,
-        (Data::Integer(l), Data::Integer(r)) => Data::Integer(l / r),
+        #[cfg(not(feature = "bignum"))]
+        (Data::Integer(l), Data::Integer(r)) => Data::Integer(
+            l.checked_div(r).ok_or("Integer overflow in division")?
+        ),
+        #[cfg(feature = "bignum")]
+        (Data::Integer(l), Data::Integer(r)) => promote(l, r, i64::checked_div, |l, r| l / r),
+        #[cfg(feature = "bignum")]
+        (l @ Data::BigInt(_), r) | (l, r @ Data::BigInt(_)) => big_binop(l, r, |l, r| l / r)?,
         _ => Err("Division between unsupported datatypes")?,
     };
 
@@ -51,14 +197,217 @@ pub fn div(data: Data) -> Result<Data, String> {
 
 /// remainder of left operand by right operand division.
 /// Raises a runtime error if there is a division by zero.
-pub fn remainder(data: Data) -> Result<Data, String> {
-    let result = match binop(data) {
-        (Data::Real(_),   Data::Real(r)) if r == 0.0 => Err("Division by zero")?,
+pub fn remainder(data: Data) -> Result<Data, FFIError> {
+    let result = match operands(data) {
+        (l, r) if divides_by_zero(&l, &r) => Err(FFIError::new(DIVISION_BY_ZERO).blame(1))?,
         (Data::Real(l),   Data::Real(r)) => Data::Real(l.rem_euclid(r)),
-        (Data::Integer(_), Data::Integer(n)) if n == 0 => Err("Division by zero")?,
-        (Data::Integer(l), Data::Integer(r)) => Data::Integer(l.rem_euclid(r)),
+        // only overflows for `i64::MIN % -1`, which is 0
+        (Data::Integer(l), Data::Integer(r)) => Data::Integer(l.wrapping_rem_euclid(r)),
+        #[cfg(feature = "bignum")]
+        (l @ Data::BigInt(_), r) | (l, r @ Data::BigInt(_)) => big_binop(l, r, |l, r| {
+            let m = l % &r;
+            if m >= BigInt::from(0) { m } else if r > BigInt::from(0) { m + r } else { m - r }
+        })?,
         _ => Err("Division between unsupported datatypes")?,
     };
 
     return Ok(result);
 }
+
+/// Checks that a shift amount is not negative.
+/// Shifting by 64 or more bits shifts out every bit.
+fn shift_amount(amount: i64) -> Result<u32, String> {
+    if amount < 0 {
+        return Err(format!("Can not shift by a negative amount, {}", amount));
+    }
+
+    return Ok(amount.min(63) as u32);
+}
+
+/// Shifts an integer to the left.
+pub fn shift_left(data: Data) -> Result<Data, String> {
+    let result = match binop(data) {
+        (Data::Integer(_), Data::Integer(r)) if r >= 64 => Data::Integer(0),
+        (Data::Integer(l), Data::Integer(r)) => Data::Integer(l << shift_amount(r)?),
+        _ => Err("Shift between unsupported datatypes")?,
+    };
+
+    return Ok(result);
+}
+
+/// Arithmetic right shift of an integer, i.e. the sign is preserved.
+pub fn shift_right(data: Data) -> Result<Data, String> {
+    let result = match binop(data) {
+        (Data::Integer(l), Data::Integer(r)) => Data::Integer(l >> shift_amount(r)?),
+        _ => Err("Shift between unsupported datatypes")?,
+    };
+
+    return Ok(result);
+}
+
+/// Bitwise and of two integers.
+pub fn bit_and(data: Data) -> Result<Data, String> {
+    let result = match binop(data) {
+        (Data::Integer(l), Data::Integer(r)) => Data::Integer(l & r),
+        _ => Err("Bitwise and between unsupported datatypes")?,
+    };
+
+    return Ok(result);
+}
+
+/// Bitwise or of two integers.
+pub fn bit_or(data: Data) -> Result<Data, String> {
+    let result = match binop(data) {
+        (Data::Integer(l), Data::Integer(r)) => Data::Integer(l | r),
+        _ => Err("Bitwise or between unsupported datatypes")?,
+    };
+
+    return Ok(result);
+}
+
+/// Bitwise not of an integer.
+pub fn bit_not(data: Data) -> Result<Data, String> {
+    let result = match data {
+        Data::Integer(n) => Data::Integer(!n),
+        _ => Err("Bitwise not of an unsupported datatype")?,
+    };
+
+    return Ok(result);
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    #[cfg(feature = "bignum")]
+    fn promote_on_overflow() {
+        let sum = add(Data::Tuple(vec![Data::Integer(i64::MAX), Data::Integer(1)])).unwrap();
+        let expected = BigInt::from(i64::MAX) + BigInt::from(1);
+        assert_eq!(sum, Data::BigInt(expected));
+
+        // and back again
+        let difference = sub(Data::Tuple(vec![sum, Data::Integer(1)])).unwrap();
+        assert_eq!(difference, Data::Integer(i64::MAX));
+    }
+
+    #[test]
+    #[cfg(feature = "bignum")]
+    fn big_division() {
+        let big = BigInt::from(i64::MAX) + BigInt::from(1);
+        let half = div(Data::Tuple(vec![Data::BigInt(big.clone()), Data::Integer(2)])).unwrap();
+        assert_eq!(half, Data::Integer(1 << 62));
+
+        let rest = remainder(Data::Tuple(vec![Data::BigInt(big), Data::Integer(-3)])).unwrap();
+        assert_eq!(rest, Data::Integer(2));
+
+        // the remainder is never negative, like `rem_euclid`
+        let negative = Data::big_integer(-(BigInt::from(i64::MAX) + BigInt::from(3)));
+        let rest = remainder(Data::Tuple(vec![negative.clone(), Data::Integer(3)])).unwrap();
+        assert_eq!(rest, Data::Integer(2));
+        let rest = remainder(Data::Tuple(vec![negative, Data::Integer(-3)])).unwrap();
+        assert_eq!(rest, Data::Integer(2));
+
+        let quotient = div(pair(i64::MIN, -1)).unwrap();
+        assert_eq!(quotient, Data::BigInt(-BigInt::from(i64::MIN)));
+    }
+
+    #[test]
+    #[cfg(feature = "bignum")]
+    fn big_division_by_zero() {
+        let big = || Data::BigInt(BigInt::from(i64::MAX) + BigInt::from(1));
+        for divisor in [Data::Integer(0), Data::BigInt(BigInt::from(0)), Data::Real(0.0)] {
+            let operands = || Data::Tuple(vec![big(), divisor.clone()]);
+            assert_eq!(div(operands()), Err(FFIError::new(DIVISION_BY_ZERO).blame(1)));
+            assert_eq!(remainder(operands()), Err(FFIError::new(DIVISION_BY_ZERO).blame(1)));
+        }
+
+        let zero = || Data::Tuple(vec![Data::Integer(1), Data::BigInt(BigInt::from(0))]);
+        assert_eq!(div(zero()), Err(FFIError::new(DIVISION_BY_ZERO).blame(1)));
+        assert_eq!(remainder(zero()), Err(FFIError::new(DIVISION_BY_ZERO).blame(1)));
+    }
+
+    #[test]
+    #[cfg(feature = "bignum")]
+    fn big_mixed_with_real() {
+        let big = Data::BigInt(BigInt::from(i64::MAX) + BigInt::from(1));
+        let half = mul(Data::Tuple(vec![big.clone(), Data::Real(0.5)])).unwrap();
+        assert_eq!(half, Data::Real((1u64 << 62) as f64));
+
+        let tiny = div(Data::Tuple(vec![Data::Real(1.0), big.clone()])).unwrap();
+        assert_eq!(tiny, Data::Real(1.0 / (1u64 << 63) as f64));
+
+        assert!(strict(Data::Tuple(vec![big, Data::Real(0.5)]), add).is_err());
+    }
+
+    #[test]
+    fn division_overflow() {
+        #[cfg(not(feature = "bignum"))]
+        assert!(div(pair(i64::MIN, -1)).is_err());
+        assert_eq!(remainder(pair(i64::MIN, -1)), Ok(Data::Integer(0)));
+    }
+
+    #[test]
+    fn integer_arithmetic() {
+        let product = mul(Data::Tuple(vec![Data::Integer(6), Data::Integer(7)])).unwrap();
+        assert_eq!(product, Data::Integer(42));
+
+        assert_eq!(negate(Data::Integer(3)), Ok(Data::Integer(-3)));
+        assert!(negate(Data::Boolean(true)).is_err());
+    }
+
+    fn pair(l: i64, r: i64) -> Data {
+        Data::Tuple(vec![Data::Integer(l), Data::Integer(r)])
+    }
+
+    #[test]
+    fn bitwise() {
+        assert_eq!(shift_left(pair(3, 4)),    Ok(Data::Integer(48)));
+        assert_eq!(shift_left(pair(1, 64)),   Ok(Data::Integer(0)));
+        assert_eq!(shift_right(pair(48, 4)),  Ok(Data::Integer(3)));
+        assert_eq!(shift_right(pair(-16, 2)), Ok(Data::Integer(-4)));
+        assert_eq!(shift_right(pair(-1, 99)), Ok(Data::Integer(-1)));
+        assert_eq!(bit_and(pair(0b1100, 0b1010)), Ok(Data::Integer(0b1000)));
+        assert_eq!(bit_or(pair(0b1100, 0b1010)),  Ok(Data::Integer(0b1110)));
+        assert_eq!(bit_not(Data::Integer(0)),     Ok(Data::Integer(-1)));
+    }
+
+    #[test]
+    fn bitwise_errors() {
+        assert!(shift_left(pair(1, -1)).is_err());
+        assert!(shift_right(pair(1, -1)).is_err());
+
+        let mixed = Data::Tuple(vec![Data::Integer(1), Data::Real(1.0)]);
+        assert!(shift_left(mixed.clone()).is_err());
+        assert!(shift_right(mixed.clone()).is_err());
+        assert!(bit_and(mixed.clone()).is_err());
+        assert!(bit_or(mixed).is_err());
+        assert!(bit_not(Data::Boolean(true)).is_err());
+    }
+
+    #[test]
+    fn mixed_arithmetic() {
+        let mixed = Data::Tuple(vec![Data::Integer(2), Data::Real(3.0)]);
+        assert_eq!(add(mixed.clone()), Ok(Data::Real(5.0)));
+        assert_eq!(sub(mixed.clone()), Ok(Data::Real(-1.0)));
+        assert_eq!(mul(Data::Tuple(vec![Data::Real(1.5), Data::Integer(2)])), Ok(Data::Real(3.0)));
+        assert_eq!(div(Data::Tuple(vec![Data::Integer(3), Data::Real(2.0)])), Ok(Data::Real(1.5)));
+
+        // integers stay integers
+        assert_eq!(add(pair(2, 3)), Ok(Data::Integer(5)));
+        assert_eq!(div(pair(7, 2)), Ok(Data::Integer(3)));
+    }
+
+    #[test]
+    fn strict_arithmetic() {
+        let mixed = Data::Tuple(vec![Data::Integer(2), Data::Real(3.0)]);
+        assert!(strict(mixed, add).is_err());
+        assert!(strict(Data::Tuple(vec![Data::Real(3.0), Data::Integer(2)]), mul).is_err());
+
+        assert_eq!(strict(pair(2, 3), add), Ok(Data::Integer(5)));
+        assert_eq!(
+            strict(Data::Tuple(vec![Data::Real(2.0), Data::Real(3.0)]), add),
+            Ok(Data::Real(5.0)),
+        );
+    }
+}
@@ -2,26 +2,49 @@
 //! And compiler-magic FFI bindings.
 
 pub mod ffi;
+pub mod prelude;
 pub mod extract;
 
 pub mod math;
 pub mod io;
 pub mod control;
 pub mod logic;
+pub mod index;
+pub mod string;
 
-use ffi::{FFI, FFIFunction};
+use ffi::{FFI, FFIFunction, FFIError};
+use math::Coercion;
+use crate::common::data::Data;
 
 // Returns the core FFI used by Passerine.
 // Implements basic langauge features, like addition.
+// An integer mixed with a real is promoted in arithmetic.
 pub fn ffi_core() -> FFI {
+    return ffi_core_with(Coercion::Promote);
+}
+
+// Returns the core FFI used by Passerine,
+// with arithmetic treating an integer mixed with a real as per `coercion`.
+pub fn ffi_core_with(coercion: Coercion) -> FFI {
     let mut ffi = FFI::new();
 
+    let arithmetic = |op: fn(Data) -> Result<Data, FFIError>| match coercion {
+        Coercion::Promote => FFIFunction::new(Box::new(op)),
+        Coercion::Strict  => FFIFunction::new(Box::new(move |data| math::strict(data, op))),
+    };
+
     // math
-    ffi.add("add", FFIFunction::new(Box::new(math::add))).unwrap();
-    ffi.add("sub", FFIFunction::new(Box::new(math::sub))).unwrap();
-    ffi.add("mul", FFIFunction::new(Box::new(math::mul))).unwrap();
-    ffi.add("div", FFIFunction::new(Box::new(math::div))).unwrap();
-    ffi.add("remainder", FFIFunction::new(Box::new(math::remainder))).unwrap();
+    ffi.add("add", arithmetic(math::add)).unwrap();
+    ffi.add("sub", arithmetic(math::sub)).unwrap();
+    ffi.add("negate", FFIFunction::new(Box::new(math::negate))).unwrap();
+    ffi.add("mul", arithmetic(math::mul)).unwrap();
+    ffi.add("div", arithmetic(math::div)).unwrap();
+    ffi.add("remainder", arithmetic(math::remainder)).unwrap();
+    ffi.add("shift_left", FFIFunction::new(Box::new(math::shift_left))).unwrap();
+    ffi.add("shift_right", FFIFunction::new(Box::new(math::shift_right))).unwrap();
+    ffi.add("bit_and", FFIFunction::new(Box::new(math::bit_and))).unwrap();
+    ffi.add("bit_or", FFIFunction::new(Box::new(math::bit_or))).unwrap();
+    ffi.add("bit_not", FFIFunction::new(Box::new(math::bit_not))).unwrap();
 
     // io
     ffi.add("println", FFIFunction::new(Box::new(io::println))).unwrap();
@@ -30,10 +53,22 @@ pub fn ffi_core() -> FFI {
 
     // control
     ffi.add("if", FFIFunction::new(Box::new(control::if_choice))).unwrap();
+    ffi.add("assert", FFIFunction::new(Box::new(control::assert))).unwrap();
 
     // logic
+    ffi.add("not", FFIFunction::new(Box::new(logic::not))).unwrap();
     ffi.add("equal", FFIFunction::new(Box::new(logic::equal))).unwrap();
     ffi.add("greater", FFIFunction::new(Box::new(logic::greater))).unwrap();
+    ffi.add("less", FFIFunction::new(Box::new(logic::less))).unwrap();
+    ffi.add("greater_equal", FFIFunction::new(Box::new(logic::greater_equal))).unwrap();
+    ffi.add("less_equal", FFIFunction::new(Box::new(logic::less_equal))).unwrap();
+
+    // index
+    ffi.add("index", FFIFunction::new(Box::new(index::index))).unwrap();
+
+    // string
+    ffi.add("length", FFIFunction::new(Box::new(string::length))).unwrap();
+    ffi.add("concat", FFIFunction::new(Box::new(string::concat))).unwrap();
 
     return ffi;
 }
@@ -0,0 +1,43 @@
+use crate::common::data::Data;
+
+/// A set of global bindings, i.e. constants and native functions,
+/// that are visible to every script.
+/// Symbols that are not defined in any enclosing scope
+/// are resolved against the prelude during hoisting,
+/// then fetched from it by the `VM` at runtime.
+/// Globals are identified by their index, i.e. their global slot.
+#[derive(Debug, Clone, Default)]
+pub struct Prelude {
+    names:  Vec<String>,
+    values: Vec<Data>,
+}
+
+impl Prelude {
+    /// Creates a new empty prelude.
+    pub fn new() -> Prelude {
+        Prelude { names: vec![], values: vec![] }
+    }
+
+    /// Registers a global binding.
+    /// Use `Data::NativeFunction` to register a Rust function.
+    /// Returns an error if the name has already been registered.
+    pub fn register_global(&mut self, name: &str, data: Data) -> Result<(), String> {
+        if self.index(name).is_some() {
+            return Err(format!("The global '{}' has already been defined", name));
+        }
+
+        self.names.push(name.to_string());
+        self.values.push(data);
+        Ok(())
+    }
+
+    /// Returns the global slot of the binding with the provided name.
+    pub fn index(&self, name: &str) -> Option<usize> {
+        self.names.iter().position(|n| n == name)
+    }
+
+    /// Returns the value stored in a global slot.
+    pub fn get(&self, index: usize) -> &Data {
+        &self.values[index]
+    }
+}
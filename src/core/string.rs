@@ -0,0 +1,48 @@
+use crate::common::data::Data;
+use crate::core::extract::binop;
+
+/// Returns the length of a string in characters, i.e. unicode scalar values,
+/// rather than in bytes, so `"😀"` is of length 1.
+pub fn length(data: Data) -> Result<Data, String> {
+    match data {
+        Data::String(s) => Ok(Data::Integer(s.chars().count() as i64)),
+        other => Err(format!("The data '{}' is not a string, so has no length", other)),
+    }
+}
+
+/// Joins two strings, i.e. `(left, right)`.
+/// Note that `+` also joins strings.
+pub fn concat(data: Data) -> Result<Data, String> {
+    match binop(data) {
+        (Data::String(l), Data::String(r)) => Ok(Data::String(format!("{}{}", l, r).into())),
+        (l, r) => Err(format!("Can not join '{}' and '{}', as both must be strings", l, r)),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn string(s: &str) -> Data {
+        Data::String(s.into())
+    }
+
+    #[test]
+    fn length_in_characters() {
+        assert_eq!(length(string("")),         Ok(Data::Integer(0)));
+        assert_eq!(length(string("hello")),    Ok(Data::Integer(5)));
+        assert_eq!(length(string("ünï😀")),    Ok(Data::Integer(4)));
+        assert_eq!(length(string("うん、気持ち")), Ok(Data::Integer(6)));
+        assert!(length(Data::Integer(5)).is_err());
+    }
+
+    #[test]
+    fn concatenation() {
+        let joined = concat(Data::Tuple(vec![string("añ"), string("😀b")]));
+        assert_eq!(joined, Ok(string("añ😀b")));
+        assert_eq!(length(joined.unwrap()), Ok(Data::Integer(4)));
+
+        let error = concat(Data::Tuple(vec![string("a"), Data::Integer(1)]));
+        assert!(error.is_err());
+    }
+}
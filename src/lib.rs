@@ -21,7 +21,7 @@
 //! [passerine.io](https://www.passerine.io/#install).
 //!
 //! ## Embedding Passerine in Rust
-//! > TODO: Clean up crate visibility, create `run` function.
+//! > TODO: Clean up crate visibility.
 //!
 //! Add passerine to your `Cargo.toml`:
 //! ```toml
@@ -29,16 +29,11 @@
 //! passerine = 0.9
 //! ```
 //! Then simply:
-//! ```ignore
-//! // DISCLAIMER: The `run` function used here has not been implemented yet,
-//! //             although the underlying interface is mostly stable.
-//!
-//! use passerine;
-//!
-//! fn main() {
-//!     passerine::run("print \"Hello from Passerine!\"");
-//! }
 //! ```
+//! passerine::run_str("print \"Hello from Passerine!\"").unwrap();
+//! ```
+//! Errors from any step of the pipeline are a `PasserineError`,
+//! which can be rendered with `PasserineError::report`.
 //!
 //! ## Overview of the compilation process
 //! > NOTE: For a more detail, read through the documentation
@@ -124,3 +119,19 @@ pub mod common;
 pub mod core;
 pub mod compiler;
 pub mod vm;
+
+pub use common::error::PasserineError;
+
+use common::{source::Source, data::Data, closure::Closure};
+use vm::vm::VM;
+
+/// Compiles and runs some source code,
+/// returning the value of its last expression.
+/// Errors are reported against a source named `./source`.
+pub fn run_str(source: &str) -> Result<Data, PasserineError> {
+    let source = Source::source(source);
+    let ast    = compiler::parse(compiler::lex(source)?)?;
+    let lambda = compiler::compile(ast)?;
+    let mut vm = VM::init(Closure::wrap(lambda));
+    return Ok(vm.evaluate()?);
+}
@@ -0,0 +1,210 @@
+use std::{
+    fmt,
+    ops::{Index, IndexMut},
+};
+
+/// The number of items in each chunk of a `Chunks`.
+pub const CHUNK_SIZE: usize = 1024;
+
+/// A growable sequence of items, stored as fixed-size chunks.
+/// Unlike a `Vec`, growing never moves existing items,
+/// a new chunk is allocated instead,
+/// so a very deep `Stack` never stalls on a large reallocation,
+/// and indices into it stay valid as it grows.
+/// Chunks are kept when the sequence shrinks,
+/// so they can be reused without reallocating.
+pub struct Chunks<T> {
+    chunks: Vec<Vec<T>>,
+    len:    usize,
+}
+
+impl<T> Chunks<T> {
+    /// Creates a new empty `Chunks`, without allocating.
+    pub fn new() -> Chunks<T> {
+        Chunks { chunks: vec![], len: 0 }
+    }
+
+    /// The number of items.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Whether there are no items.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// The number of items that fit in the chunks allocated so far.
+    pub fn capacity(&self) -> usize {
+        self.chunks.len() * CHUNK_SIZE
+    }
+
+    /// Appends an item, allocating a new chunk if the last one is full.
+    #[inline]
+    pub fn push(&mut self, item: T) {
+        let chunk = self.len / CHUNK_SIZE;
+        if chunk == self.chunks.len() {
+            self.chunks.push(Vec::with_capacity(CHUNK_SIZE));
+        }
+
+        self.chunks[chunk].push(item);
+        self.len += 1;
+    }
+
+    /// Removes and returns the last item, if there is one.
+    #[inline]
+    pub fn pop(&mut self) -> Option<T> {
+        if self.len == 0 { return None; }
+        self.len -= 1;
+        return self.chunks[self.len / CHUNK_SIZE].pop();
+    }
+
+    /// Returns the last item, if there is one.
+    #[inline]
+    pub fn last(&self) -> Option<&T> {
+        if self.len == 0 { return None; }
+        return self.chunks[(self.len - 1) / CHUNK_SIZE].last();
+    }
+
+    /// Shortens to the first `len` items, dropping the rest.
+    /// Does nothing if there are already `len` items or fewer.
+    pub fn truncate(&mut self, len: usize) {
+        if len >= self.len { return; }
+
+        let first = len / CHUNK_SIZE;
+        self.chunks[first].truncate(len % CHUNK_SIZE);
+        for chunk in self.chunks[first + 1..].iter_mut() {
+            chunk.clear();
+        }
+
+        self.len = len;
+    }
+
+    /// Frees the chunks past the last item, kept by `pop` and `truncate`.
+    /// The chunks still holding items are left as they are,
+    /// so no item is moved.
+    pub fn shrink_to_fit(&mut self) {
+        let used = (self.len + CHUNK_SIZE - 1) / CHUNK_SIZE;
+        self.chunks.truncate(used);
+        self.chunks.shrink_to_fit();
+    }
+
+    /// Iterates over the items, first to last.
+    pub fn iter(&self) -> impl Iterator<Item = &T> {
+        // chunks past the last item are empty
+        self.chunks.iter().flatten()
+    }
+}
+
+impl<T> Default for Chunks<T> {
+    fn default() -> Chunks<T> {
+        Chunks::new()
+    }
+}
+
+impl<T> Index<usize> for Chunks<T> {
+    type Output = T;
+
+    #[inline]
+    fn index(&self, index: usize) -> &T {
+        if index >= self.len {
+            panic!("Index {} out of bounds for chunks of length {}", index, self.len);
+        }
+        &self.chunks[index / CHUNK_SIZE][index % CHUNK_SIZE]
+    }
+}
+
+impl<T> IndexMut<usize> for Chunks<T> {
+    #[inline]
+    fn index_mut(&mut self, index: usize) -> &mut T {
+        if index >= self.len {
+            panic!("Index {} out of bounds for chunks of length {}", index, self.len);
+        }
+        &mut self.chunks[index / CHUNK_SIZE][index % CHUNK_SIZE]
+    }
+}
+
+/// Formats like a `Vec`, i.e. a list of the items.
+impl<T: fmt::Debug> fmt::Debug for Chunks<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self.iter()).finish()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn across_boundary() {
+        let mut chunks = Chunks::new();
+        for i in 0..CHUNK_SIZE * 3 + 1 { chunks.push(i); }
+
+        assert_eq!(chunks.len(), CHUNK_SIZE * 3 + 1);
+        assert_eq!(chunks.capacity(), CHUNK_SIZE * 4);
+        assert_eq!(chunks[CHUNK_SIZE - 1], CHUNK_SIZE - 1);
+        assert_eq!(chunks[CHUNK_SIZE], CHUNK_SIZE);
+        assert_eq!(chunks.last(), Some(&(CHUNK_SIZE * 3)));
+        assert!(chunks.iter().copied().eq(0..CHUNK_SIZE * 3 + 1));
+
+        for i in (0..CHUNK_SIZE * 3 + 1).rev() {
+            assert_eq!(chunks.pop(), Some(i));
+        }
+        assert_eq!(chunks.pop(), None);
+        assert_eq!(chunks.last(), None);
+    }
+
+    #[test]
+    fn growth_does_not_move_items() {
+        let mut chunks = Chunks::new();
+        chunks.push(0);
+        let first = &chunks[0] as *const usize;
+
+        for i in 1..CHUNK_SIZE * 8 { chunks.push(i); }
+        assert_eq!(&chunks[0] as *const usize, first);
+    }
+
+    #[test]
+    fn truncate_keeps_chunks() {
+        let mut chunks = Chunks::new();
+        for i in 0..CHUNK_SIZE * 2 + 5 { chunks.push(i); }
+
+        chunks.truncate(CHUNK_SIZE + 1);
+        assert_eq!(chunks.len(), CHUNK_SIZE + 1);
+        assert_eq!(chunks.capacity(), CHUNK_SIZE * 3);
+        assert_eq!(chunks.last(), Some(&CHUNK_SIZE));
+
+        // values pushed after truncating land in the right place
+        chunks.push(7);
+        chunks.push(8);
+        assert_eq!(chunks[CHUNK_SIZE + 1], 7);
+        assert!(chunks.iter().copied().eq((0..CHUNK_SIZE + 1).chain(vec![7, 8])));
+    }
+
+    #[test]
+    fn shrink_to_fit() {
+        let mut chunks = Chunks::new();
+        for i in 0..CHUNK_SIZE * 3 { chunks.push(i); }
+
+        chunks.truncate(CHUNK_SIZE + 1);
+        chunks.shrink_to_fit();
+        assert_eq!(chunks.capacity(), CHUNK_SIZE * 2);
+        assert!(chunks.iter().copied().eq(0..CHUNK_SIZE + 1));
+
+        chunks.truncate(0);
+        chunks.shrink_to_fit();
+        assert_eq!(chunks.capacity(), 0);
+        assert!(chunks.is_empty());
+    }
+
+    #[test]
+    #[should_panic(expected = "out of bounds")]
+    fn index_past_len() {
+        let mut chunks = Chunks::new();
+        for i in 0..3 { chunks.push(i); }
+        chunks.pop();
+        let _ = chunks[2];
+    }
+}
@@ -6,5 +6,6 @@ pub mod vm;
 
 pub mod tag;
 pub mod stack;
+pub mod chunks;
 pub mod trace;
 pub mod slot;
@@ -10,10 +10,13 @@ use crate::common::{
 };
 
 /// Represents a suspended closure.
+/// `height` is the length of the `Stack` when it was suspended,
+/// so a call made from it returns a value at that index.
 #[derive(Debug, Clone)]
 pub struct Suspend {
     pub ip:      usize,
     pub closure: Closure,
+    pub height:  usize,
 }
 
 /// Represents the value a slot on the VM can take.
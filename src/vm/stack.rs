@@ -1,16 +1,57 @@
 use std::{
+    fmt,
     mem,
     rc::Rc,
     cell::RefCell
 };
 
-use crate::common::data::Data;
+use crate::common::data::{Data, not_a_tuple, wrong_tuple_length};
 
 use crate::vm::{
     tag::Tagged,
+    chunks::Chunks,
     slot::{Slot, Suspend},
+    trace::Trace,
 };
 
+/// The default maximum number of frames on a `Stack`.
+pub const RECURSION_LIMIT: usize = 10_000;
+
+/// Counts of what a `Stack` has done, for profiling, see `Stack::stats`.
+/// Only tracked with the `stack-stats` feature,
+/// so there's no overhead otherwise.
+#[cfg(feature = "stack-stats")]
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct StackStats {
+    /// Slots pushed one at a time, including frame markers.
+    pub pushes: usize,
+    /// Slots popped one at a time, i.e. not counting unwinding.
+    pub pops: usize,
+    /// Frames pushed, i.e. function calls.
+    pub frames: usize,
+    /// Locals moved to the heap, i.e. captured by a closure.
+    pub heapifications: usize,
+    /// The most frames on the `Stack` at once, see `Stack::frame_depth`.
+    pub max_depth: usize,
+}
+
+/// The state of a `Stack` at some point, see `Stack::snapshot`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StackSnapshot {
+    len:    usize,
+    frames: Vec<usize>,
+}
+
+/// The extent of a frame on a `Stack`, see `Stack::frames_view`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FrameInfo {
+    /// The index of the frame marker.
+    pub base:   usize,
+    /// The number of slots above the marker that belong to the frame,
+    /// i.e. its locals followed by its temporaries.
+    pub locals: usize,
+}
+
 /// A stack of `Tagged` `Data`.
 /// Note that in general the stack is expected to follow the following pattern:
 /// ```plain
@@ -18,30 +59,198 @@ use crate::vm::{
 /// ```
 /// Or in other words, a frame followed by a block of *n* values that are locals
 /// followed by *n* temporaries, ad infinitum.
-#[derive(Debug)]
+/// Values are stored in `Chunks`, so a deep stack grows without
+/// moving the values already on it.
 pub struct Stack {
     pub frames: Vec<usize>,
-    pub stack:  Vec<Tagged>
+    pub stack:  Chunks<Tagged>,
+    /// The maximum number of frames, see `push_frame`.
+    pub recursion_limit: usize,
+    /// The indices into `frames` of the frames that handle errors,
+    /// from the outermost in, see `push_handler` and `unwind`.
+    handlers: Vec<usize>,
+    #[cfg(feature = "stack-stats")]
+    stats: StackStats,
+}
+
+/// Formats the state of the `Stack`,
+/// leaving out its stats, which describe its history.
+impl fmt::Debug for Stack {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Stack")
+            .field("frames", &self.frames)
+            .field("stack", &self.stack)
+            .field("recursion_limit", &self.recursion_limit)
+            .field("handlers", &self.handlers)
+            .finish()
+    }
 }
 
 impl Stack {
     /// Create a new `Stack` with a single frame.
     pub fn init() -> Stack {
+        let mut stack = Chunks::new();
+        stack.push(Tagged::frame());
+
         Stack {
             frames: vec![0],
-            stack:  vec![Tagged::frame()],
+            stack,
+            recursion_limit: RECURSION_LIMIT,
+            handlers: vec![],
+            #[cfg(feature = "stack-stats")]
+            stats: StackStats { max_depth: 1, ..StackStats::default() },
+        }
+    }
+
+    /// Resets the `Stack` to its initial state, a single frame,
+    /// while keeping the capacity of the underlying storage.
+    /// Any remaining values are dropped, freeing heaped data.
+    /// Useful when running many short scripts on a single `VM`.
+    pub fn reset(&mut self) {
+        self.stack.truncate(1);
+        self.frames.truncate(1);
+        self.frames[0] = 0;
+        self.handlers.clear();
+        self.stack[0]  = Tagged::frame();
+    }
+
+    /// Records the current state of the `Stack`,
+    /// so it can be rolled back with `restore`,
+    /// e.g. if speculative evaluation fails.
+    pub fn snapshot(&self) -> StackSnapshot {
+        StackSnapshot { len: self.stack.len(), frames: self.frames.clone() }
+    }
+
+    /// Rolls the `Stack` back to a snapshot taken with `snapshot`,
+    /// dropping everything pushed since, freeing heaped data,
+    /// and any frames suspended since.
+    /// Values that were already on the `Stack` when the snapshot was taken
+    /// are kept as they are now, so only values pushed since are undone.
+    /// Returns an error if the `Stack` has since shrunk below the snapshot,
+    /// as the snapshot can no longer be restored.
+    pub fn restore(&mut self, snapshot: StackSnapshot) -> Result<(), Trace> {
+        if self.stack.len() < snapshot.len
+        || !self.frames.starts_with(&snapshot.frames) {
+            return Err(Trace::error(
+                "Stack",
+                "Can not restore a snapshot of the stack after popping values that were on it",
+                vec![],
+            ));
         }
+
+        self.stack.truncate(snapshot.len);
+        self.frames = snapshot.frames;
+        let depth = self.frames.len();
+        self.handlers.retain(|handler| *handler < depth);
+
+        // pushing a frame suspends the context at the previous frame
+        let frame_index = self.frame_index();
+        mem::drop(self.swap(frame_index, Tagged::frame()));
+        Ok(())
+    }
+
+    /// Returns the number of frames on the `Stack`,
+    /// i.e. the current depth of recursion, plus one for the base frame.
+    pub fn frame_depth(&self) -> usize {
+        self.frames.len()
+    }
+
+    /// Returns the extent of every frame on the `Stack`,
+    /// from the base frame up to the current one, for debugging.
+    /// Each frame extends to the next frame's marker,
+    /// and the current frame extends to the top of the `Stack`.
+    pub fn frames_view(&self) -> Vec<FrameInfo> {
+        let ends = self.frames.iter().skip(1).copied().chain(std::iter::once(self.stack.len()));
+
+        return self.frames.iter().zip(ends)
+            .map(|(base, end)| FrameInfo { base: *base, locals: end - base - 1 })
+            .collect();
+    }
+
+    /// Returns the number of slots on the `Stack`,
+    /// i.e. every value and frame marker, including the base frame.
+    pub fn len(&self) -> usize {
+        self.stack.len()
+    }
+
+    /// Whether the `Stack` is empty, which is never the case,
+    /// as the base frame is always on the `Stack`.
+    pub fn is_empty(&self) -> bool {
+        self.stack.is_empty()
+    }
+
+    /// Returns the number of slots the `Stack` can hold
+    /// before it has to allocate more storage.
+    pub fn capacity(&self) -> usize {
+        self.stack.capacity()
+    }
+
+    /// Releases storage the `Stack` no longer uses,
+    /// e.g. after a deeply recursive computation has returned.
+    /// Values still on the `Stack` are kept, and are not moved.
+    pub fn shrink_to_fit(&mut self) {
+        self.stack.shrink_to_fit();
+        self.frames.shrink_to_fit();
     }
 
     /// Return the index of the topmost `Tagged(Slot::Frame)`.
     #[inline]
-    fn frame_index(&self) -> usize {
+    pub fn frame_index(&self) -> usize {
         *self.frames.last().unwrap()
     }
 
+    /// Renders the current frame, i.e. the topmost frame marker,
+    /// followed by each value above it, indexed from the frame, decoded.
+    /// Heaped values are shown with their contents.
+    /// Useful while stepping through the `VM`;
+    /// the derived `Debug` shows the whole stack.
+    pub fn debug_print_frame(&self) -> String {
+        let frame_index = self.frame_index();
+        let mut rendered = format!(
+            "Frame {} at {}\n", self.frame_depth() - 1, frame_index,
+        );
+
+        for (index, tagged) in self.stack.iter().skip(frame_index + 1).enumerate() {
+            let value = match tagged.copy() {
+                Slot::Data(data) => format!("{:?}", data),
+                other            => format!("{:?}", other),
+            };
+            rendered.push_str(&format!("  {}: {}\n", index, value));
+        }
+
+        return rendered;
+    }
+
+    /// Returns every `Data::Heaped` value on the `Stack`,
+    /// i.e. the roots a garbage collector would start marking from.
+    /// Frames, suspended closures, and other data are skipped.
+    pub fn roots(&self) -> impl Iterator<Item = &Data> {
+        self.stack.iter().filter_map(|tagged| match tagged.boxed() {
+            Some(Slot::Data(heaped @ Data::Heaped(_))) => Some(heaped),
+            _ => None,
+        })
+    }
+
+    /// Returns what the `Stack` has done since it was created,
+    /// e.g. how many values were pushed.
+    #[cfg(feature = "stack-stats")]
+    pub fn stats(&self) -> StackStats {
+        self.stats.clone()
+    }
+
+    /// Push a `Tagged` item.
+    #[inline]
+    fn push(&mut self, tagged: Tagged) {
+        #[cfg(feature = "stack-stats")]
+        { self.stats.pushes += 1; }
+        self.stack.push(tagged)
+    }
+
     /// Pop and return the topmost `Tagged` item.
     #[inline]
     fn pop(&mut self) -> Tagged {
+        #[cfg(feature = "stack-stats")]
+        { self.stats.pops += 1; }
         self.stack.pop()
             .expect("VM tried to pop empty stack, stack should never be empty")
     }
@@ -55,21 +264,40 @@ impl Stack {
     /// Pushes some `Data` onto the `Stack`, tagging it along the way
     #[inline]
     pub fn push_data(&mut self, data: Data) {
-        self.stack.push(Tagged::new(Slot::Data(data)))
+        self.push(Tagged::new(Slot::Data(data)))
+    }
+
+    /// Pushes a `Data::Real` onto the `Stack`.
+    /// Like `push_data`, but skips matching on the kind of `Data`,
+    /// for arithmetic-heavy code.
+    #[inline]
+    pub fn push_real(&mut self, n: f64) {
+        self.push(Tagged::real(n))
+    }
+
+    /// Pushes a `Data::Integer` onto the `Stack`, see `push_real`.
+    #[inline]
+    pub fn push_int(&mut self, n: i64) {
+        self.push(Tagged::integer(n))
+    }
+
+    /// Pushes a `Data::Boolean` onto the `Stack`, see `push_real`.
+    #[inline]
+    pub fn push_bool(&mut self, b: bool) {
+        self.push(Tagged::bool(b))
     }
 
     /// Pushes some `Tagged` `Data` onto the `Stack` without unwrapping it.
     #[inline]
     pub fn push_tagged(&mut self, tagged: Tagged) {
-        self.stack.push(tagged)
+        self.push(tagged)
     }
 
     /// Pops some `Data` of the `Stack`, panicking if what it pops is not `Data`.
     /// Note that this will never return a `Heaped` value, rather cloning the value inside.
     #[inline]
     pub fn pop_data(&mut self) -> Data {
-        let value = self.stack.pop()
-            .expect("VM tried to pop empty stack, stack should never be empty");
+        let value = self.pop();
 
         match value.slot().data() {
             Data::Heaped(h) => h.borrow().clone(),
@@ -77,14 +305,33 @@ impl Stack {
         }
     }
 
+    /// Pops some `Data` off the `Stack` and drops it,
+    /// panicking if what it pops is not `Data`.
+    /// Unlike `pop_data`, heaped values are not cloned,
+    /// the reference to the heap is just released.
+    #[inline]
+    pub fn pop_discard(&mut self) {
+        let value = self.pop();
+
+        if let Slot::Data(_) = value.slot() {} else {
+            unreachable!("Expected data on top of stack");
+        }
+    }
+
     /// Pops a stack frame from the `Stack`, restoring the previous frame.
     /// Panics if there are no frames left on the stack.
+    /// The frame marker must be on top, so anything the callee left on the `Stack`
+    /// must be popped first - to return a value, use `return_frame` instead.
     #[inline]
     pub fn pop_frame(&mut self) -> Suspend {
         if let Slot::Frame = self.pop().slot() {} else {
             unreachable!("Expected frame on top of stack");
         }
 
+        if self.handlers.last() == Some(&(self.frames.len() - 1)) {
+            self.handlers.pop();
+        }
+
         self.frames.pop();
         let old_slot = self.swap(self.frame_index(), Tagged::frame()).slot();
 
@@ -95,20 +342,154 @@ impl Stack {
         }
     }
 
+    /// Returns from a function call, leaving its result on top of the caller's frame.
+    /// A function returns by popping its result,
+    /// then clearing its locals so that its frame marker is on top, i.e.
+    /// `[..., F, V..., R]` becomes `[..., F]`, with `R` in hand.
+    /// This pops that frame and pushes `R` in its place,
+    /// so the caller's `Stack` ends with the result, i.e. `[..., R]`,
+    /// as if the call were a single instruction that pushed it.
+    /// Returns the suspended caller, like `pop_frame`.
+    #[inline]
+    pub fn return_frame(&mut self, result: Data) -> Suspend {
+        let suspend = self.pop_frame();
+        self.push_data(result);
+        return suspend;
+    }
+
     /// Pushes a new stack frame onto the `Stack`.
     /// Takes the old suspended closure / ip, and stores that on the stack.
+    /// Returns an error if the `Stack` is already `recursion_limit` frames deep.
     #[inline]
-    pub fn push_frame(&mut self, suspend: Suspend) {
+    pub fn push_frame(&mut self, suspend: Suspend) -> Result<(), Trace> {
+        if self.frame_depth() >= self.recursion_limit {
+            return Err(Trace::error(
+                "Stack Overflow",
+                &format!(
+                    "Stack overflow at depth {}, functions may only recurse {} frames deep",
+                    self.frame_depth(), self.recursion_limit,
+                ),
+                vec![],
+            ));
+        }
+
         let frame_index = self.frame_index();
         self.stack[frame_index] = Tagged::new(Slot::Suspend(suspend));
         self.frames.push(self.stack.len());
-        self.stack.push(Tagged::frame());
+        self.push(Tagged::frame());
+
+        #[cfg(feature = "stack-stats")]
+        {
+            self.stats.frames += 1;
+            self.stats.max_depth = self.stats.max_depth.max(self.frame_depth());
+        }
+        Ok(())
+    }
+
+    /// Enters a function in tail position, reusing the current frame
+    /// instead of pushing a new one, so tail calls run in constant stack space.
+    /// The `arg_count` arguments on top of the `Stack` become the frame's locals,
+    /// and whatever was in the frame before is dropped, i.e.
+    /// `[..., F, V..., T..., A...]` becomes `[..., F, A...]`.
+    /// `suspend` replaces the closure / ip the frame returns to, like `push_frame`.
+    /// Panics if there is only the base frame, as it has nothing to return to.
+    #[inline]
+    pub fn tail_enter(&mut self, suspend: Suspend, arg_count: usize) {
+        let base = self.frame_index();
+        let args = self.stack.len() - arg_count;
+        debug_assert!(args > base, "Expected {} arguments in the current frame", arg_count);
+
+        // shuffle the arguments down into the frame's local slots
+        for i in 0..arg_count {
+            let arg = self.swap(args + i, Tagged::not_init());
+            mem::drop(self.swap(base + 1 + i, arg));
+        }
+        self.stack.truncate(base + 1 + arg_count);
+
+        let caller = self.frames.len().checked_sub(2)
+            .expect("Can not tail call from the base frame");
+        let caller_index = self.frames[caller];
+        mem::drop(self.swap(caller_index, Tagged::new(Slot::Suspend(suspend))));
+    }
+
+    /// Pushes a new stack frame like `push_frame`,
+    /// marking it as a handler that errors unwind to, see `unwind`.
+    /// The frame is popped as usual if the call returns without error.
+    pub fn push_handler(&mut self, suspend: Suspend) -> Result<(), Trace> {
+        self.push_frame(suspend)?;
+        self.handlers.push(self.frames.len() - 1);
+        Ok(())
+    }
+
+    /// Unwinds the `Stack` after an error,
+    /// popping frames and dropping their locals and temporaries
+    /// until a frame pushed with `push_handler` is popped.
+    /// Returns that handler's suspended closure, the context that pushed it,
+    /// leaving the `Stack` as it was before the handler was pushed.
+    /// If there is no handler, every frame but the base frame is popped,
+    /// the base frame is emptied, and `None` is returned.
+    pub fn unwind(&mut self) -> Option<Suspend> {
+        while self.frame_depth() > 1 {
+            let handled = self.handlers.last() == Some(&(self.frames.len() - 1));
+            self.stack.truncate(self.frame_index() + 1);
+            let suspend = self.pop_frame();
+            if handled { return Some(suspend); }
+        }
+
+        self.stack.truncate(1);
+        return None;
+    }
+
+    /// Checks the `Stack` is consistent after a function call returns,
+    /// i.e. that the caller's frame is followed by exactly one return value.
+    /// `expected_len` is the length of the `Stack` before the call,
+    /// once the function and its argument were popped, plus one.
+    /// Only checked in debug builds, panics if the check fails.
+    #[inline]
+    pub fn validate_after_call(&self, expected_len: usize) {
+        debug_assert_eq!(
+            self.stack.len(), expected_len,
+            "Stack is unbalanced after a function call",
+        );
+        debug_assert!(
+            matches!(self.stack.last().map(Tagged::copy), Some(Slot::Data(_))),
+            "Expected a return value on top of the stack after a function call",
+        );
+    }
+
+    /// Pops the top `n` values off the `Stack`,
+    /// and pushes them back as a single `Data::Tuple`.
+    /// The value lowest on the stack becomes the first item.
+    pub fn build_tuple(&mut self, n: usize) {
+        let mut items = vec![];
+        for _ in 0..n {
+            items.push(self.pop_data());
+        }
+
+        items.reverse();
+        self.push_data(Data::Tuple(items));
+    }
+
+    /// Pops a `Data::Tuple` of exactly `n` items off the `Stack`,
+    /// and pushes each of its items, first item lowest.
+    /// The inverse of `build_tuple`.
+    /// Returns an error if the popped value is not a tuple of length `n`;
+    /// as the stack has no notion of source, the `Trace` has no spans.
+    pub fn destructure_tuple(&mut self, n: usize) -> Result<(), Trace> {
+        let items = match self.pop_data() {
+            Data::Tuple(t) if t.len() == n => t,
+            Data::Tuple(t) => return Err(wrong_tuple_length(t, n)),
+            other => return Err(not_a_tuple(&other)),
+        };
+
+        for item in items { self.push_data(item); }
+        Ok(())
     }
 
     /// Shorcut for pushing a `Tagged(Slot::NotInit)` on top of the stack.
     #[inline]
     pub fn push_not_init(&mut self) {
-        self.stack.push(Tagged::not_init());
+        self.push(Tagged::not_init());
     }
 
     /// Shortcut for calling `push_not_init` N times.
@@ -126,6 +507,9 @@ impl Stack {
         let data = self.swap(local_index, Tagged::not_init()).slot().data();
         let heaped = Slot::Data(Data::Heaped(Rc::new(RefCell::new(data))));
         mem::drop(mem::replace(&mut self.stack[local_index], Tagged::new(heaped)));
+
+        #[cfg(feature = "stack-stats")]
+        { self.stats.heapifications += 1; }
     }
 
     /// Truncates the stack to the last frame.
@@ -140,26 +524,14 @@ impl Stack {
     pub fn local_slot(&mut self, index: usize) -> Slot {
         let local_index = self.frame_index() + index + 1;
 
-        // a little bit of shuffling involved
-        // I know that something better than this can be done
-        let slot = self.swap(local_index, Tagged::not_init()).slot();
-        let copy = slot.clone();
-        mem::drop(self.swap(local_index, Tagged::new(slot)));
-
-        return copy;
+        // the local is copied in place rather than taken out and put back,
+        // so if cloning panics the local is still there
+        return self.stack[local_index].copy();
     }
 
     /// Returns a copy of the `Data` stored in a local variable on the stack.
     pub fn local_data(&mut self, index: usize) -> Data {
-        let local_index = self.frame_index() + index + 1;
-
-        // a little bit of shuffling involved
-        // I know that something better than this can be done
-        let data = self.swap(local_index, Tagged::not_init()).slot().data();
-        let copy = data.clone();
-        mem::drop(self.swap(local_index, Tagged::new(Slot::Data(data))));
-
-        return copy;
+        return self.local_slot(index).data();
     }
 
     /// Sets a local - note that this function doesn't do much.
@@ -190,10 +562,498 @@ impl Stack {
                 }
                 // if it's not on the heap, we assume it's data,
                 // and do a quick swap-and-drop
-                _ => self.stack.pop().unwrap(),
+                _ => self.pop(),
             };
 
             mem::drop(self.swap(local_index, tagged))
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::panic;
+    use crate::common::{
+        closure::Closure,
+        lambda::Lambda,
+    };
+    use crate::core::ffi::{FFIFunction, PANIC_ON_CLONE};
+
+    #[test]
+    fn local_survives_panicking_clone() {
+        let mut stack = Stack::init();
+        stack.push_data(Data::NativeFunction(FFIFunction::new(Box::new(Ok::<Data, String>))));
+
+        PANIC_ON_CLONE.with(|hook| hook.set(true));
+        let result = panic::catch_unwind(panic::AssertUnwindSafe(|| stack.local_data(0)));
+        PANIC_ON_CLONE.with(|hook| hook.set(false));
+        assert!(result.is_err());
+
+        // the local is still there, and can still be copied
+        assert!(matches!(stack.local_data(0), Data::NativeFunction(_)));
+        assert!(matches!(stack.local_slot(0), Slot::Data(Data::NativeFunction(_))));
+    }
+
+    #[test]
+    fn specialized_pushes() {
+        let values = [
+            Data::Real(2.5),
+            Data::Real(-0.0),
+            Data::Real(f64::INFINITY),
+            Data::Integer(0),
+            Data::Integer(i64::MIN),
+            Data::Integer(i64::MAX),
+            Data::Boolean(true),
+            Data::Boolean(false),
+        ];
+
+        let mut general = Stack::init();
+        let mut special = Stack::init();
+        for value in values.iter() {
+            general.push_data(value.clone());
+            match value {
+                Data::Real(n)    => special.push_real(*n),
+                Data::Integer(n) => special.push_int(*n),
+                Data::Boolean(b) => special.push_bool(*b),
+                _ => unreachable!(),
+            }
+        }
+
+        assert_eq!(format!("{:?}", general), format!("{:?}", special));
+        for value in values.iter().rev() {
+            assert_eq!(&special.pop_data(), value);
+            assert_eq!(&general.pop_data(), value);
+        }
+    }
+
+    #[test]
+    fn grows_across_chunks() {
+        use crate::vm::chunks::CHUNK_SIZE;
+
+        let mut stack = Stack::init();
+        let banana = Rc::new(RefCell::new(Data::String("Banana".into())));
+        stack.push_data(Data::Heaped(Rc::clone(&banana)));
+
+        // fill the first chunk, then push a frame into the next
+        for i in 2..CHUNK_SIZE { stack.push_int(i as i64); }
+        stack.push_frame(suspend()).unwrap();
+        assert_eq!(stack.frame_index(), CHUNK_SIZE);
+        for i in 0..CHUNK_SIZE * 2 { stack.push_int(i as i64); }
+
+        // locals are found across the chunk boundary
+        assert_eq!(stack.local_data(0), Data::Integer(0));
+        assert_eq!(stack.local_data(CHUNK_SIZE), Data::Integer(CHUNK_SIZE as i64));
+
+        for i in (0..CHUNK_SIZE * 2).rev() {
+            assert_eq!(stack.pop_data(), Data::Integer(i as i64));
+        }
+        stack.pop_frame();
+        for i in (2..CHUNK_SIZE).rev() {
+            assert_eq!(stack.pop_data(), Data::Integer(i as i64));
+        }
+        assert_eq!(stack.pop_data(), Data::String("Banana".into()));
+        assert_eq!(stack.stack.len(), 1);
+    }
+
+    #[test]
+    fn return_frame() {
+        let mut stack = Stack::init();
+        stack.push_int(1);
+
+        // call a function with one local, that returns a value
+        let caller = stack.frame_index();
+        stack.push_frame(suspend()).unwrap();
+        stack.push_data(Data::String("Banana".into()));
+        stack.heapify(0);
+        stack.push_int(2);
+
+        let result = stack.pop_data();
+        stack.pop_discard();
+        stack.return_frame(result);
+
+        assert_eq!(stack.frame_index(), caller);
+        assert_eq!(stack.pop_data(), Data::Integer(2));
+        assert_eq!(stack.pop_data(), Data::Integer(1));
+        assert_eq!(stack.len(), 1);
+    }
+
+    #[test]
+    fn pop_discard() {
+        let mut stack = Stack::init();
+        let banana = Rc::new(RefCell::new(Data::String("Banana".into())));
+        stack.push_int(1);
+        stack.push_data(Data::Heaped(Rc::clone(&banana)));
+        assert_eq!(Rc::strong_count(&banana), 2);
+
+        stack.pop_discard();
+        assert_eq!(Rc::strong_count(&banana), 1);
+        assert_eq!(stack.pop_data(), Data::Integer(1));
+        assert_eq!(stack.len(), 1);
+    }
+
+    #[test]
+    #[cfg(feature = "stack-stats")]
+    fn stats() {
+        let mut stack = Stack::init();
+        stack.push_int(1);
+        stack.declare(2);
+        stack.push_frame(suspend()).unwrap();
+        stack.push_real(2.0);
+        stack.heapify(0);
+        stack.push_frame(suspend()).unwrap();
+        stack.pop_frame();
+        stack.pop_data();
+        stack.pop_frame();
+        stack.pop_discard();
+
+        assert_eq!(stack.stats(), StackStats {
+            // 1 + 2 declared + 2 frames + 1
+            pushes: 6,
+            pops: 4,
+            frames: 2,
+            heapifications: 1,
+            max_depth: 3,
+        });
+    }
+
+    #[test]
+    fn len_and_capacity() {
+        use crate::vm::chunks::CHUNK_SIZE;
+
+        let mut stack = Stack::init();
+        assert_eq!(stack.len(), 1);
+        assert!(!stack.is_empty());
+        assert_eq!(stack.capacity(), CHUNK_SIZE);
+
+        stack.push_int(7);
+        stack.push_frame(suspend()).unwrap();
+        assert_eq!(stack.len(), 3);
+        for i in 0..CHUNK_SIZE * 2 { stack.push_int(i as i64); }
+        assert_eq!(stack.len(), CHUNK_SIZE * 2 + 3);
+        assert_eq!(stack.capacity(), CHUNK_SIZE * 3);
+
+        // popping keeps the storage, shrinking releases it
+        for _ in 0..CHUNK_SIZE * 2 { stack.pop_data(); }
+        assert_eq!(stack.capacity(), CHUNK_SIZE * 3);
+        stack.shrink_to_fit();
+        assert_eq!(stack.capacity(), CHUNK_SIZE);
+    }
+
+    #[test]
+    fn shrink_preserves_contents() {
+        use crate::vm::chunks::CHUNK_SIZE;
+
+        let mut stack = Stack::init();
+        let banana = Rc::new(RefCell::new(Data::String("Banana".into())));
+        stack.push_data(Data::Heaped(Rc::clone(&banana)));
+        for i in 0..CHUNK_SIZE * 2 { stack.push_int(i as i64); }
+        for _ in 0..CHUNK_SIZE { stack.pop_data(); }
+
+        let before = stack.debug_print_frame();
+        stack.shrink_to_fit();
+        assert_eq!(stack.capacity(), CHUNK_SIZE * 2);
+        assert_eq!(stack.debug_print_frame(), before);
+
+        for i in (0..CHUNK_SIZE).rev() {
+            assert_eq!(stack.pop_data(), Data::Integer(i as i64));
+        }
+        assert_eq!(stack.pop_data(), Data::String("Banana".into()));
+        assert_eq!(Rc::strong_count(&banana), 1);
+    }
+
+    #[test]
+    fn reset_keeps_capacity() {
+        let mut stack = Stack::init();
+        let heaped = Rc::new(RefCell::new(Data::String("Banana".into())));
+
+        stack.push_data(Data::Heaped(Rc::clone(&heaped)));
+        for i in 0..1024 { stack.push_data(Data::Real(i as f64)); }
+        let capacity = stack.stack.capacity();
+
+        stack.reset();
+
+        assert_eq!(stack.stack.len(), 1);
+        assert_eq!(stack.frames, vec![0]);
+        assert_eq!(stack.stack.capacity(), capacity);
+        // the only remaining reference is ours
+        assert_eq!(Rc::strong_count(&heaped), 1);
+    }
+
+    fn suspend() -> Suspend {
+        Suspend { ip: 0, closure: Closure::wrap(Lambda::empty()), height: 0 }
+    }
+
+    #[test]
+    fn unwind_to_handler() {
+        let mut stack = Stack::init();
+        let banana = Rc::new(RefCell::new(Data::String("Banana".into())));
+        stack.push_int(1);
+        stack.push_frame(suspend()).unwrap();
+        stack.push_int(2);
+        let before = stack.snapshot();
+
+        let handler = Suspend { ip: 7, ..suspend() };
+        stack.push_handler(handler).unwrap();
+        stack.push_data(Data::Heaped(Rc::clone(&banana)));
+        for _ in 0..2 {
+            stack.push_frame(suspend()).unwrap();
+            stack.push_int(3);
+            stack.push_data(Data::Heaped(Rc::clone(&banana)));
+        }
+
+        // the frames above the handler, and the handler's, are dropped
+        assert_eq!(stack.unwind().map(|s| s.ip), Some(7));
+        assert_eq!(stack.snapshot(), before);
+        assert_eq!(Rc::strong_count(&banana), 1);
+        assert_eq!(stack.pop_data(), Data::Integer(2));
+
+        // without a handler, everything is unwound
+        stack.push_frame(suspend()).unwrap();
+        assert!(stack.unwind().is_none());
+        assert_eq!(stack.frame_depth(), 1);
+        assert_eq!(stack.len(), 1);
+    }
+
+    #[test]
+    fn handler_returns() {
+        let mut stack = Stack::init();
+        stack.push_handler(suspend()).unwrap();
+        stack.push_frame(suspend()).unwrap();
+        stack.pop_frame();
+        stack.pop_frame();
+
+        // the handler was popped, so there's nothing to unwind to
+        stack.push_frame(suspend()).unwrap();
+        assert!(stack.unwind().is_none());
+    }
+
+    #[test]
+    fn frames_view() {
+        let mut stack = Stack::init();
+        stack.push_int(1);
+        stack.push_frame(suspend()).unwrap();
+        stack.declare(3);
+        stack.push_frame(suspend()).unwrap();
+        stack.push_frame(suspend()).unwrap();
+        stack.push_int(2);
+        stack.push_real(3.0);
+
+        assert_eq!(stack.frames_view(), vec![
+            FrameInfo { base: 0, locals: 1 },
+            FrameInfo { base: 2, locals: 3 },
+            FrameInfo { base: 6, locals: 0 },
+            FrameInfo { base: 7, locals: 2 },
+        ]);
+
+        stack.reset();
+        assert_eq!(stack.frames_view(), vec![FrameInfo { base: 0, locals: 0 }]);
+    }
+
+    #[test]
+    fn frame_depth() {
+        let mut stack = Stack::init();
+        assert_eq!(stack.frame_depth(), 1);
+
+        for _ in 0..5 { stack.push_frame(suspend()).unwrap(); }
+        assert_eq!(stack.frame_depth(), 6);
+
+        stack.pop_frame();
+        assert_eq!(stack.frame_depth(), 5);
+    }
+
+    #[test]
+    fn tail_enter() {
+        let mut stack = Stack::init();
+        let banana = Rc::new(RefCell::new(Data::String("Banana".into())));
+        stack.push_int(1);
+        stack.push_frame(suspend()).unwrap();
+        stack.push_int(0);
+        let depth = stack.frame_depth();
+
+        // count up to 10, tail calling with the next count each time
+        for n in 1..=10 {
+            stack.push_data(Data::Heaped(Rc::clone(&banana)));
+            stack.push_int(n);
+            stack.push_data(Data::Boolean(true));
+            stack.tail_enter(Suspend { ip: n as usize, ..suspend() }, 2);
+
+            assert_eq!(stack.frame_depth(), depth);
+            assert_eq!(stack.frames.len(), 2);
+            assert_eq!(stack.local_data(0), Data::Integer(n));
+            assert_eq!(stack.local_data(1), Data::Boolean(true));
+            assert_eq!(stack.len(), 5);
+        }
+        // nothing from earlier iterations is kept alive
+        assert_eq!(Rc::strong_count(&banana), 1);
+
+        // the frame returns to the last suspend, in the caller's frame
+        stack.pop_discard();
+        stack.pop_discard();
+        assert_eq!(stack.pop_frame().ip, 10);
+        assert_eq!(stack.pop_data(), Data::Integer(1));
+        assert_eq!(stack.len(), 1);
+    }
+
+    #[test]
+    fn recursion_limit() {
+        let mut stack = Stack::init();
+        stack.recursion_limit = 3;
+
+        stack.push_frame(suspend()).unwrap();
+        stack.push_frame(suspend()).unwrap();
+        assert!(stack.push_frame(suspend()).is_err());
+        assert_eq!(stack.frame_depth(), 3);
+    }
+
+    #[test]
+    fn debug_print_frame() {
+        let mut stack = Stack::init();
+        stack.push_data(Data::Integer(7));
+        stack.push_frame(suspend()).unwrap();
+
+        stack.push_data(Data::Boolean(true));
+        stack.push_data(Data::String("Banana".into()));
+        stack.heapify(1);
+        stack.push_not_init();
+
+        assert_eq!(
+            stack.debug_print_frame(),
+            "Frame 1 at 2\n  \
+               0: Boolean(true)\n  \
+               1: Heaped(String(\"Banana\"))\n  \
+               2: NotInit\n",
+        );
+    }
+
+    /// Mimics the `VM` calling a function with a single local,
+    /// leaving the return value on top of the stack.
+    /// Returns the stack length expected after the call.
+    fn call(stack: &mut Stack) -> usize {
+        let expected = stack.stack.len() + 1;
+        stack.push_frame(suspend()).unwrap();
+        stack.declare(1);
+        stack.push_data(Data::Integer(7));
+
+        let value = stack.pop_data();
+        mem::drop(stack.pop()); // delete the local
+        stack.pop_frame();
+        stack.push_data(value);
+        return expected;
+    }
+
+    #[test]
+    fn validate_after_call() {
+        let mut stack = Stack::init();
+        stack.push_data(Data::Unit);
+        let expected = call(&mut stack);
+        stack.validate_after_call(expected);
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "unbalanced")]
+    fn validate_after_unbalanced_call() {
+        let mut stack = Stack::init();
+        let expected = call(&mut stack);
+        // the return value was pushed twice
+        stack.push_data(Data::Integer(7));
+        stack.validate_after_call(expected);
+    }
+
+    #[test]
+    fn snapshot_and_restore() {
+        let mut stack = Stack::init();
+        let kept = Rc::new(RefCell::new(Data::Integer(1)));
+        stack.push_data(Data::Heaped(Rc::clone(&kept)));
+        stack.push_data(Data::Boolean(true));
+        let before = format!("{:?}", stack);
+        let snapshot = stack.snapshot();
+
+        let dropped = Rc::new(RefCell::new(Data::Integer(2)));
+        stack.push_data(Data::Heaped(Rc::clone(&dropped)));
+        stack.push_frame(suspend()).unwrap();
+        stack.push_data(Data::Heaped(Rc::clone(&dropped)));
+        assert_eq!(Rc::strong_count(&dropped), 3);
+
+        stack.restore(snapshot).unwrap();
+        assert_eq!(format!("{:?}", stack), before);
+        assert_eq!(stack.frame_depth(), 1);
+        assert_eq!(Rc::strong_count(&kept), 2);
+        assert_eq!(Rc::strong_count(&dropped), 1);
+    }
+
+    #[test]
+    fn restore_after_pop() {
+        let mut stack = Stack::init();
+        stack.push_data(Data::Integer(1));
+        let snapshot = stack.snapshot();
+
+        // the value the snapshot was taken on top of is gone
+        stack.pop_data();
+        stack.push_data(Data::Integer(2));
+        stack.push_data(Data::Integer(3));
+        stack.pop_data();
+        stack.pop_data();
+        assert!(stack.restore(snapshot).is_err());
+
+        // a snapshot taken inside a frame that has since returned
+        stack.push_frame(suspend()).unwrap();
+        stack.push_data(Data::Integer(4));
+        let snapshot = stack.snapshot();
+        let value = stack.pop_data();
+        stack.pop_frame();
+        stack.push_data(value);
+        stack.push_data(Data::Unit);
+        assert!(stack.restore(snapshot).is_err());
+    }
+
+    #[test]
+    fn roots() {
+        let mut stack = Stack::init();
+        let banana = Rc::new(RefCell::new(Data::String("Banana".into())));
+        let number = Rc::new(RefCell::new(Data::Real(2.5)));
+
+        stack.push_data(Data::Heaped(Rc::clone(&banana)));
+        stack.push_data(Data::String("Not heaped".into()));
+        stack.push_frame(suspend()).unwrap();
+        stack.push_data(Data::Boolean(true));
+        stack.push_data(Data::Heaped(Rc::clone(&number)));
+
+        let roots = stack.roots().cloned().collect::<Vec<Data>>();
+        assert_eq!(roots, vec![Data::Heaped(Rc::clone(&banana)), Data::Heaped(Rc::clone(&number))]);
+
+        // scanning roots doesn't take ownership
+        mem::drop(roots);
+        assert_eq!(Rc::strong_count(&banana), 2);
+        assert_eq!(Rc::strong_count(&number), 2);
+    }
+
+    #[test]
+    fn build_and_destructure_tuple() {
+        let mut stack = Stack::init();
+        stack.push_data(Data::Integer(1));
+        stack.push_data(Data::Boolean(true));
+
+        stack.build_tuple(2);
+        assert_eq!(stack.stack.len(), 2);
+
+        let tuple = stack.pop_data();
+        assert_eq!(tuple, Data::Tuple(vec![Data::Integer(1), Data::Boolean(true)]));
+
+        stack.push_data(tuple);
+        stack.destructure_tuple(2).unwrap();
+        assert_eq!(stack.pop_data(), Data::Boolean(true));
+        assert_eq!(stack.pop_data(), Data::Integer(1));
+    }
+
+    #[test]
+    fn destructure_arity_mismatch() {
+        let mut stack = Stack::init();
+        stack.push_data(Data::Tuple(vec![Data::Integer(1), Data::Integer(2)]));
+        assert!(stack.destructure_tuple(3).is_err());
+
+        stack.push_data(Data::Integer(1));
+        assert!(stack.destructure_tuple(1).is_err());
+    }
+}
@@ -46,13 +46,16 @@ const U_FLAG: u64 = 0x0000_0000_0000_0001; // unit
 const F_FLAG: u64 = 0x0000_0000_0000_0002; // false
 const T_FLAG: u64 = 0x0000_0000_0000_0003; // true
 const N_FLAG: u64 = 0x0000_0000_0000_0004; // not initialized
+const T_MASK: u64 = 0x0000_0000_0000_0007; // covers all of the above
+/// The quiet NaN every real NaN is stored as, outside of the tag space.
+const CANONICAL_NAN: u64 = 0x7ff8_0000_0000_0000;
 
 impl Tagged {
     /// Wraps `Data` to create a new tagged pointer.
     pub fn new(slot: Slot) -> Tagged {
         match slot {
             // Real
-            Slot::Data(Data::Real(f)) => Tagged(f.to_bits()),
+            Slot::Data(Data::Real(f)) => Tagged::real(f),
             // Unit
             Slot::Data(Data::Unit) => Tagged(QNAN | U_FLAG),
             // True and false
@@ -71,17 +74,49 @@ impl Tagged {
         }
     }
 
+    // The following constructors have fixed bit patterns,
+    // so they skip the `match` in `new`.
+
     // TODO: encode frame in tag itself; a frame is not data
     /// Creates a new stack frame.
     #[inline]
-    pub fn frame() -> Tagged {
-        Tagged::new(Slot::Frame)
+    pub const fn frame() -> Tagged {
+        Tagged(QNAN | S_FLAG)
     }
 
     /// Shortcut for creating a new `Tagged(Slot::NotInit)`.
     #[inline]
-    pub fn not_init() -> Tagged {
-        Tagged::new(Slot::Data(Data::NotInit))
+    pub const fn not_init() -> Tagged {
+        Tagged(QNAN | N_FLAG)
+    }
+
+    /// Shortcut for creating a new `Tagged(Data::Unit)`.
+    #[inline]
+    pub const fn unit() -> Tagged {
+        Tagged(QNAN | U_FLAG)
+    }
+
+    /// Shortcut for creating a new `Tagged(Data::Boolean(...))`.
+    #[inline]
+    pub const fn bool(b: bool) -> Tagged {
+        if b { Tagged(QNAN | T_FLAG) } else { Tagged(QNAN | F_FLAG) }
+    }
+
+    /// Shortcut for creating a new `Tagged(Data::Real(...))`.
+    /// Reals other than NaN are stored as their own bit pattern.
+    /// NaNs can have any payload, which could collide with a tag or a pointer,
+    /// so every NaN is stored as the same quiet NaN, outside of the tag space.
+    #[inline]
+    pub fn real(n: f64) -> Tagged {
+        if n.is_nan() { Tagged(CANONICAL_NAN) } else { Tagged(n.to_bits()) }
+    }
+
+    /// Shortcut for creating a new `Tagged(Data::Integer(...))`.
+    /// Integers don't fit in the tag, so they're still boxed.
+    #[inline]
+    pub fn integer(n: i64) -> Tagged {
+        let pointer = Box::into_raw(Box::new(Slot::Data(Data::Integer(n))));
+        Tagged(P_FLAG | QNAN | (P_MASK & pointer as u64))
     }
 
     /// Returns the underlying `Data` (or a pointer to that `Data`).
@@ -105,6 +140,44 @@ impl Tagged {
         }
     }
 
+    /// Returns the raw bits of the tagged pointer, for debugging.
+    /// Unlike converting into a `u64`, this doesn't consume the `Tagged`.
+    #[inline]
+    pub fn raw_bits(&self) -> u64 {
+        self.0
+    }
+
+    /// Checks if the `Slot` is stored on the heap,
+    /// i.e. if the payload is a 48-bit pointer rather than a real or a tag.
+    #[inline]
+    pub fn is_pointer(&self) -> bool {
+        (self.0 & QNAN) == QNAN && (self.0 & P_FLAG) == P_FLAG
+    }
+
+    /// Returns the tag of a value encoded in the tag itself,
+    /// e.g. `3` for `true` and `0` for a stack frame.
+    /// Reals and pointers don't have a tag,
+    /// so for them the result is meaningless.
+    #[inline]
+    pub fn tag_bits(&self) -> u8 {
+        (self.0 & T_MASK) as u8
+    }
+
+    /// Returns a reference to the `Slot` if it is stored on the heap,
+    /// i.e. if it isn't encoded in the tag itself.
+    /// Unlike `copy`, this doesn't clone the `Slot`.
+    pub fn boxed(&self) -> Option<&Slot> {
+        let Tagged(bits) = self;
+
+        if self.is_pointer() {
+            // the box is owned by this tagged pointer,
+            // so it lives at least as long as `&self`
+            Some(unsafe { &*((bits & P_MASK) as *const Slot) })
+        } else {
+            None
+        }
+    }
+
     /// Unwrapps a tagged number into the appropriate datatype,
     /// consuming the tagged number.
     pub fn slot(self) -> Slot {
@@ -126,29 +199,48 @@ impl Tagged {
     }
 
     /// Deeply copies some `Tagged` data.
+    /// If cloning the underlying `Data` panics,
+    /// `self` is left untouched.
     pub fn copy(&self) -> Slot {
         // println!("-- Copy...");
         unsafe {
             match self.extract() {
                 Ok(slot) => slot.to_owned(),
                 Err(boxed) => {
-                    let copy = boxed.clone();
-                    // println!("-- Leaking...");
                     // we took ownership to clone the pointer,
-                    // but we do not own the pointer,
-                    // so we 'leak' it - &self still holds a reference
-                    Box::leak(boxed);
-                    *copy
+                    // but we do not own the pointer - &self still holds a reference -
+                    // so we make sure it's never dropped, even if the clone panics
+                    let boxed = mem::ManuallyDrop::new(boxed);
+                    Slot::clone(&boxed)
                 },
             }
         }
     }
 }
 
+#[cfg(feature = "cycle-check")]
+impl Tagged {
+    /// Whether this is a heaped value in a reference cycle,
+    /// which won't be freed when dropped, see `Data::is_cyclic`.
+    pub fn leaks(&self) -> bool {
+        match self.boxed() {
+            Some(Slot::Data(heaped @ Data::Heaped(_))) => heaped.is_cyclic(),
+            _ => false,
+        }
+    }
+}
+
 impl Drop for Tagged {
     fn drop(&mut self) {
         // println!("-- Dropping...");
         // println!("{:#x}", self.0 & P_MASK);
+
+        // the cycle can't be printed, as printing it would never end
+        #[cfg(feature = "cycle-check")]
+        if self.leaks() {
+            eprintln!("Warning: dropped a heaped value in a reference cycle, which will leak");
+        }
+
         unsafe { mem::drop(self.extract()) };
     }
 }
@@ -168,6 +260,46 @@ impl From<Tagged> for u64 {
 mod test {
     use super::*;
 
+    #[test]
+    fn pointers() {
+        let string = Tagged::new(Slot::Data(Data::String("Banana".into())));
+        let heaped = Tagged::new(Slot::Data(Data::Heaped(
+            std::rc::Rc::new(std::cell::RefCell::new(Data::String("Banana".into())))
+        )));
+        assert!(string.is_pointer());
+        assert!(heaped.is_pointer());
+        assert!(Tagged::integer(7).is_pointer());
+
+        for tagged in [
+            Tagged::real(2.5),
+            Tagged::real(f64::NAN),
+            Tagged::real(f64::NEG_INFINITY),
+            Tagged::bool(true),
+            Tagged::bool(false),
+            Tagged::unit(),
+            Tagged::frame(),
+            Tagged::not_init(),
+        ] {
+            assert!(!tagged.is_pointer());
+        }
+
+        // inspecting doesn't consume the value
+        let bits = string.raw_bits();
+        assert_eq!(string.raw_bits(), bits);
+        assert!(matches!(string.copy(), Slot::Data(Data::String(_))));
+    }
+
+    #[test]
+    fn tags() {
+        assert_eq!(Tagged::frame().tag_bits(),     0);
+        assert_eq!(Tagged::unit().tag_bits(),      1);
+        assert_eq!(Tagged::bool(false).tag_bits(), 2);
+        assert_eq!(Tagged::bool(true).tag_bits(),  3);
+        assert_eq!(Tagged::not_init().tag_bits(),  4);
+        assert_eq!(Tagged::real(2.5).raw_bits(),   2.5f64.to_bits());
+        assert_eq!(Tagged::unit().raw_bits(),      u64::from(Tagged::unit()));
+    }
+
     #[test]
     fn reals_eq() {
         let positive = 478_329.0;
@@ -186,6 +318,24 @@ mod test {
         }
     }
 
+    #[test]
+    fn nan_outside_tag_space() {
+        // NaNs whose bits look like a pointer, a boolean, and a stack frame
+        for bits in &[P_FLAG | QNAN | 0x1234, QNAN | T_FLAG, QNAN | S_FLAG, 0xfff0_0000_0000_0001] {
+            let nan = f64::from_bits(*bits);
+            assert!(nan.is_nan());
+
+            for tagged in [Tagged::new(Slot::Data(Data::Real(nan))), Tagged::real(nan)] {
+                assert!(!tagged.is_pointer());
+                assert_eq!(tagged.raw_bits(), CANONICAL_NAN);
+                match tagged.slot() {
+                    Slot::Data(Data::Real(f)) => assert!(f.is_nan()),
+                    other => panic!("Didn't unwrap to a real, found {:?}", other),
+                }
+            }
+        }
+    }
+
     #[test]
     fn bool_and_back() {
         assert_eq!(Data::Boolean(true),  Tagged::new(Slot::Data(Data::Boolean(true) )).copy().data());
@@ -197,6 +347,27 @@ mod test {
         assert_eq!(Data::Unit, Tagged::new(Slot::Data(Data::Unit)).copy().data());
     }
 
+    #[test]
+    fn constant_constructors() {
+        assert_eq!(Tagged::unit().copy().data(),      Data::Unit);
+        assert_eq!(Tagged::bool(true).copy().data(),  Data::Boolean(true));
+        assert_eq!(Tagged::bool(false).copy().data(), Data::Boolean(false));
+        assert_eq!(Tagged::not_init().slot().data(),  Data::NotInit);
+        assert!(matches!(Tagged::frame().slot(), Slot::Frame));
+
+        let pairs = vec![
+            (Tagged::unit(),        Slot::Data(Data::Unit)),
+            (Tagged::bool(true),    Slot::Data(Data::Boolean(true))),
+            (Tagged::bool(false),   Slot::Data(Data::Boolean(false))),
+            (Tagged::not_init(),    Slot::Data(Data::NotInit)),
+            (Tagged::frame(),       Slot::Frame),
+        ];
+
+        for (constant, slot) in pairs {
+            assert_eq!(u64::from(constant), u64::from(Tagged::new(slot)));
+        }
+    }
+
     #[test]
     fn size() {
         let data_size = mem::size_of::<Data>();
@@ -218,11 +389,11 @@ mod test {
         let x =     "It's kind of a dead giveaway, isn't it?".to_string();
 
         for item in &[s, three, x] {
-            let data    = Data::String(item.clone());
+            let data    = Data::String(item.as_str().into());
             let wrapped = Tagged::new(Slot::Data(data));
             // println!("{:#b}", u64::from(wrapped));
             match wrapped.copy().data() {
-                Data::String(s) => { assert_eq!(item, &s) },
+                Data::String(s) => { assert_eq!(item.as_str(), &*s) },
                 _ => {
                     // println!("{:#b}", u64::from(wrapped));
                     panic!("Didn't unwrap to a string");
@@ -248,9 +419,9 @@ mod test {
             Data::Boolean(true),
             Data::Boolean(false),
             Data::Unit,
-            Data::String("Hello, World!".to_string()),
-            Data::String("".to_string()),
-            Data::String("Whoop 😋".to_string()),
+            Data::String("Hello, World!".into()),
+            Data::String("".into()),
+            Data::String("Whoop 😋".into()),
         ];
 
         for test in tests {
@@ -281,7 +452,7 @@ mod test {
         let location = "This is a string".to_string();
 
         // drop dereferenced data
-        let tagged = Tagged::new(Slot::Data(Data::String(location.clone())));
+        let tagged = Tagged::new(Slot::Data(Data::String(location.as_str().into())));
         let pointer = tagged.0 & P_MASK;
         let untagged = tagged.copy().data();
         // println!("-- Casting...");
@@ -292,12 +463,35 @@ mod test {
         // println!("after drop: {:?}", data);
     }
 
+    #[cfg(feature = "cycle-check")]
+    #[test]
+    fn cycle_warning() {
+        use std::{rc::Rc, cell::RefCell};
+
+        // a heaped tuple containing itself
+        let cell = Rc::new(RefCell::new(Data::Unit));
+        *cell.borrow_mut() = Data::Tuple(vec![Data::Heaped(Rc::clone(&cell))]);
+        let cyclic = Tagged::new(Slot::Data(Data::Heaped(Rc::clone(&cell))));
+        assert!(cyclic.leaks());
+
+        // warns, but the cycle keeps the cell alive
+        mem::drop(cyclic);
+        assert_eq!(Rc::strong_count(&cell), 2);
+        *cell.borrow_mut() = Data::Unit;
+        assert_eq!(Rc::strong_count(&cell), 1);
+
+        let acyclic = Tagged::new(Slot::Data(Data::Heaped(Rc::new(RefCell::new(Data::Integer(1))))));
+        assert!(!acyclic.leaks());
+        assert!(!Tagged::integer(7).leaks());
+        assert!(!Tagged::unit().leaks());
+    }
+
     #[test]
     fn no_leak_tagged() {
         let location = "This is a string".to_string();
 
         // drop tagged data
-        let tagged = Tagged::new(Slot::Data(Data::String(location.clone())));
+        let tagged = Tagged::new(Slot::Data(Data::String(location.as_str().into())));
         let pointer = tagged.0 & P_MASK;
         let data = unsafe { Box::from_raw(pointer as *mut Data) };
         // println!("-- Dropping...");
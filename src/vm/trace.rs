@@ -1,5 +1,8 @@
 use std::fmt;
-use crate::common::span::Span;
+use crate::common::{
+    span::Span,
+    source::Source,
+};
 
 /// Represents a runtime error, i.e. a traceback
 #[derive(Debug, PartialEq, Eq)]
@@ -7,6 +10,7 @@ pub struct Trace {
     kind: String, // TODO: enum?
     message: String,
     spans: Vec<Span>,
+    cause: Option<Span>,
 }
 
 impl Trace {
@@ -16,13 +20,63 @@ impl Trace {
             kind: kind.to_string(),
             message: message.to_string(),
             spans,
+            cause: None,
         }
     }
 
+    /// Points at the code that caused the error, e.g. the divisor of a division by zero.
+    /// Unlike the spans of the traceback, this isn't a frame.
+    pub fn with_cause(mut self, span: Span) -> Trace {
+        self.cause = Some(span);
+        return self;
+    }
+
+    /// Returns the code that caused the error, if known.
+    pub fn cause(&self) -> Option<&Span> {
+        self.cause.as_ref()
+    }
+
+    /// Returns the message of the error, without its kind or spans.
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+
+    /// Returns the spans of the traceback, innermost first.
+    pub fn spans(&self) -> &[Span] {
+        &self.spans
+    }
+
     /// Used to add context (i.e. function calls) while unwinding the stack.
     pub fn add_context(&mut self, span: Span) {
         self.spans.push(span);
     }
+
+    /// Renders the error followed by its backtrace,
+    /// i.e. where each frame was in the source, innermost first.
+    /// Frames in `source` are shown with the offending code,
+    /// frames elsewhere, e.g. in another file, just with their location.
+    /// Unlike `Display`, which leads up to the error like a Python traceback,
+    /// the error comes first, like a Rust backtrace.
+    pub fn report(&self, source: &Source) -> String {
+        let mut report = format!("Runtime {} Error: {}\n", self.kind, self.message);
+
+        let locate = |span: &Span| match span.source.as_deref() {
+            _ if span.is_empty() => "In an unknown location\n".to_string(),
+            Some(s) if s == source => span.to_string(),
+            _ => format!("In {}\n", span.location()),
+        };
+
+        if let Some(cause) = &self.cause {
+            report.push_str("Caused by:\n");
+            report.push_str(&locate(cause));
+        }
+
+        for span in self.spans.iter() {
+            report.push_str(&locate(span));
+        }
+
+        return report;
+    }
 }
 
 impl fmt::Display for Trace {
@@ -34,7 +88,13 @@ impl fmt::Display for Trace {
             fmt::Display::fmt(span, f)?;
         }
 
-        write!(f, "Runtime {} Error: {}", self.kind, self.message)
+        write!(f, "Runtime {} Error: {}", self.kind, self.message)?;
+
+        if let Some(cause) = &self.cause {
+            write!(f, "\nCaused by:\n{}", cause)?;
+        }
+
+        return Ok(());
     }
 }
 
@@ -92,4 +152,50 @@ forever RandomLabel
         let result = format!("{}", traceback);
         assert_eq!(result, target);
     }
+
+    #[test]
+    fn report() {
+        let source = Source::source("double = x -> x * 2\ndouble \"banana\"\n");
+        let other  = Source::new("x / 0", &std::path::PathBuf::from("./other"));
+
+        let trace = Trace::error(
+            "Type",
+            "Multiplication between unsupported datatypes",
+            vec![
+                Span::new(&source, 14, 5),
+                Span::new(&source, 20, 15),
+            ],
+        );
+
+        let target = "\
+            Runtime Type Error: Multiplication between unsupported datatypes\n\
+            In ./source:1:15\n   \
+               |\n \
+             1 | double = x -> x * 2\n   \
+               |               ^^^^^\n   \
+               |\n\
+            In ./source:2:1\n   \
+               |\n \
+             2 | double \"banana\"\n   \
+               | ^^^^^^^^^^^^^^^\n   \
+               |\n\
+        ";
+        assert_eq!(trace.report(&source), target);
+
+        // frames from elsewhere are only located
+        let mut trace = Trace::error("Value", "Division by zero", vec![Span::new(&other, 0, 5)]);
+        trace.add_context(Span::empty());
+        assert_eq!(
+            trace.report(&source),
+            "Runtime Value Error: Division by zero\nIn ./other:1:1\nIn an unknown location\n",
+        );
+
+        // the cause comes right after the error, before the frames
+        let trace = Trace::error("Value", "Division by zero", vec![])
+            .with_cause(Span::new(&other, 4, 1));
+        assert_eq!(
+            trace.report(&source),
+            "Runtime Value Error: Division by zero\nCaused by:\nIn ./other:1:5\n",
+        );
+    }
 }
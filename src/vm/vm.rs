@@ -9,6 +9,11 @@ use crate::common::{
     span::Span,
 };
 
+use crate::core::{
+    ffi::FFIFunction,
+    prelude::Prelude,
+};
+
 use crate::vm::{
     trace::Trace,
     slot::Suspend,
@@ -25,6 +30,7 @@ pub struct VM {
     pub closure: Closure,
     pub stack:   Stack,
     pub ip:      usize,
+    pub prelude: Prelude,
 }
 
 // NOTE: use Opcode::same and Opcode.to_byte() rather than actual bytes
@@ -36,10 +42,18 @@ impl VM {
     /// Initialize a new VM.
     /// To run the VM, a lambda must be passed to it through `run`.
     pub fn init(closure: Closure) -> VM {
+        return VM::init_with_prelude(closure, Prelude::new());
+    }
+
+    /// Initialize a new VM with a `Prelude` of global bindings.
+    /// This must be the same prelude the closure was hoisted with,
+    /// see `compiler::hoist_with_prelude`.
+    pub fn init_with_prelude(closure: Closure, prelude: Prelude) -> VM {
         let mut vm = VM {
             closure,
-            stack: Stack::init(),
-            ip:    0,
+            stack:   Stack::init(),
+            ip:      0,
+            prelude,
         };
         vm.stack.declare(vm.closure.lambda.decls);
         return vm;
@@ -99,6 +113,7 @@ impl VM {
             Opcode::SaveCap => self.save_cap(),
             Opcode::Load    => self.load(),
             Opcode::LoadCap => self.load_cap(),
+            Opcode::Global  => self.global(),
             Opcode::Call    => self.call(),
             Opcode::Return  => self.return_val(),
             Opcode::Closure => self.closure(),
@@ -153,6 +168,14 @@ impl VM {
         return result;
     }
 
+    /// Runs the VM like `run`, then pops and returns
+    /// the value the program evaluated to, i.e. the value of its last expression.
+    /// An empty program evaluates to `Data::Unit`.
+    pub fn evaluate(&mut self) -> Result<Data, Trace> {
+        self.run()?;
+        return Ok(self.stack.pop_data());
+    }
+
     // TODO: there are a lot of optimizations that can be made
     // I'll list a few here:
     // - searching the stack for variables
@@ -240,10 +263,21 @@ impl VM {
         self.done()
     }
 
+    /// Load a copy of a global from the prelude onto the stack.
+    #[inline]
+    pub fn global(&mut self) -> Result<(), Trace> {
+        let index = self.next_number();
+        let data = self.prelude.get(index).clone();
+        self.stack.push_data(data);
+        self.done()
+    }
+
     /// Delete the top item of the stack.
+    /// Emitted after each statement of a block but the last,
+    /// as their values are discarded.
     #[inline]
     pub fn del(&mut self) -> Result<(), Trace> {
-        mem::drop(self.stack.pop_data());
+        self.stack.pop_discard();
         self.done()
     }
 
@@ -279,13 +313,7 @@ impl VM {
     #[inline]
     pub fn tuple(&mut self) -> Result<(), Trace> {
         let index = self.next_number();
-        let mut items = vec![];
-        for _ in 0..index {
-            items.push(self.stack.pop_data())
-        }
-
-        items.reverse();
-        self.stack.push_data(Data::Tuple(items));
+        self.stack.build_tuple(index);
         self.done()
     }
 
@@ -357,6 +385,7 @@ impl VM {
         // get the function and argument to run
         let fun = match self.stack.pop_data() {
             Data::Closure(c) => *c,
+            Data::NativeFunction(n) => return self.call_native(n),
             o => return Err(Trace::error(
                 "Call",
                 &format!("The data '{}' is not a function and can not be called", o),
@@ -385,13 +414,14 @@ impl VM {
         let suspend = Suspend {
             ip: old_ip,
             closure: old_closure,
+            height: self.stack.len(),
         };
 
         // if there's a tail call, we don't bother pushing a new frame
         // the topmost frame doesn't carry any context;
         // that context is intrinsic to the VM itself.
         if !tail_call {
-            self.stack.push_frame(suspend);
+            self.stack.push_frame(suspend)?;
         }
 
         // set up the stack for the function call
@@ -407,7 +437,8 @@ impl VM {
     /// Return a value from a function.
     /// End the execution of the current lambda.
     /// Takes the number of locals on the stack
-    /// Relpaces the last frame with the value on the top of the stack.
+    /// Relpaces the last frame with the value on the top of the stack,
+    /// see `Stack::return_frame`.
     /// Expects the stack to be a `[..., Frame, Local 1, ..., Local N, Data]`
     pub fn return_val(&mut self) -> Result<(), Trace> {
         // the value to be returned
@@ -417,13 +448,13 @@ impl VM {
         let locals = self.next_number();
         for _ in 0..locals { self.del()?; }
 
-        // restore suspended callee
-        let suspend = self.stack.pop_frame(); // remove the frame
+        // restore suspended callee, leaving the return value in place of its frame
+        let suspend = self.stack.return_frame(val);
         self.ip      = suspend.ip;
         self.closure = suspend.closure;
 
-        // push return value
-        self.stack.push_data(val); // push the return value
+        // the return value is right above where the stack was when the call was made
+        self.stack.validate_after_call(suspend.height + 1);
         Ok(())
     }
 
@@ -454,6 +485,21 @@ impl VM {
         self.done()
     }
 
+    /// Call a native function, i.e. a Rust function stored as data.
+    /// Unlike closures, native functions run to completion without a new frame.
+    pub fn call_native(&mut self, fun: FFIFunction) -> Result<(), Trace> {
+        let argument = self.stack.pop_data();
+        let returned = match fun.call(argument) {
+            Ok(d) => d,
+            Err(e) => return Err(Trace::error(
+                "FFI Call", &e.message, vec![self.current_span()],
+            )),
+        };
+
+        self.stack.push_data(returned);
+        self.done()
+    }
+
     pub fn ffi_call(&mut self) -> Result<(), Trace> {
         let index = self.next_number();
         let ffi_function = &self.closure.lambda.ffi[index];
@@ -461,9 +507,19 @@ impl VM {
         let argument = self.stack.pop_data();
         let returned = match ffi_function.call(argument) {
             Ok(d) => d,
-            Err(e) => return Err(Trace::error(
-                "FFI Call", &e, vec![self.current_span()],
-            )),
+            Err(e) => {
+                let trace = Trace::error("FFI Call", &e.message, vec![self.current_span()]);
+
+                // blame the operand at fault, if the function said which one it is,
+                // e.g. the divisor of `a / b` or an assertion's condition
+                let operands = self.closure.lambda.index_operands(self.ip);
+                let cause = e.operand.and_then(|i| operands.get(i).cloned());
+
+                return Err(match cause {
+                    Some(span) if !span.is_empty() => trace.with_cause(span),
+                    _ => trace,
+                });
+            },
         };
 
         self.stack.push_data(returned);
@@ -478,10 +534,11 @@ mod test {
         lex::lex,
         parse::parse,
         desugar::desugar,
-        hoist::hoist,
-        gen::gen,
+        hoist::{hoist, hoist_with_prelude},
+        gen::{gen, gen_with_ffi, compile},
     };
     use crate::common::source::Source;
+    use crate::core::{ffi_core_with, math::Coercion};
 
     fn inspect(source: &str) -> VM {
         let lambda = lex(Source::source(source))
@@ -504,6 +561,80 @@ mod test {
         }
     }
 
+    fn evaluate(source: &str) -> Result<Data, Trace> {
+        let lambda = lex(Source::source(source))
+            .and_then(parse)
+            .and_then(compile)
+            .unwrap();
+
+        return VM::init(Closure::wrap(lambda)).evaluate();
+    }
+
+    #[test]
+    fn evaluate_arithmetic() {
+        assert_eq!(evaluate("1.0 + 2.0 * 3.0"), Ok(Data::Real(7.0)));
+
+        // integer literals evaluate to an integer, which promotes to a real
+        let result = evaluate("1 + 2 * 3").unwrap();
+        assert_eq!(result, Data::Integer(7));
+        assert_eq!(result.to_real(), Ok(Data::Real(7.0)));
+        assert_eq!(evaluate("f = x -> x * 2.0\nf 3.5"), Ok(Data::Real(7.0)));
+    }
+
+    #[test]
+    fn evaluate_discards() {
+        let source = "f = x -> x + 1\ng = y -> y * 2\n{ f 1; g 3 }";
+        assert_eq!(evaluate(source), Ok(Data::Integer(6)));
+
+        let source = "s = \"heaped\"\nf = () -> s\nx = { f (); s; 1 }\n(x, s)";
+        assert_eq!(evaluate(source), Ok(Data::Tuple(vec![Data::Integer(1), Data::String("heaped".into())])));
+    }
+
+    #[test]
+    fn evaluate_empty() {
+        assert_eq!(evaluate(""), Ok(Data::Unit));
+        assert_eq!(evaluate("\n\n"), Ok(Data::Unit));
+    }
+
+    #[test]
+    fn evaluate_failure() {
+        let trace = evaluate("1 / 0").unwrap_err();
+        assert!(trace.to_string().contains("Division by zero"), "{}", trace);
+    }
+
+    #[test]
+    fn evaluate_mixed_arithmetic() {
+        assert_eq!(evaluate("2 + 3.0"), Ok(Data::Real(5.0)));
+
+        let lambda = lex(Source::source("2 + 3.0"))
+            .and_then(parse)
+            .and_then(desugar)
+            .and_then(hoist)
+            .and_then(|sst| gen_with_ffi(sst, ffi_core_with(Coercion::Strict)))
+            .unwrap();
+
+        let trace = VM::init(Closure::wrap(lambda)).evaluate().unwrap_err();
+        assert!(trace.to_string().contains("integer and real"), "{}", trace);
+    }
+
+    #[test]
+    fn interned_strings() {
+        let source = "a = \"hi\"\nf = () -> \"hi\"\n(a, f (), a + a)";
+        let (a, b, joined) = match evaluate(source) {
+            Ok(Data::Tuple(t)) => match &t[..] {
+                [Data::String(a), Data::String(b), Data::String(j)] => (a.clone(), b.clone(), j.clone()),
+                other => panic!("{:?}", other),
+            },
+            other => panic!("{:?}", other),
+        };
+
+        // the same literal, in different functions, shares one allocation
+        assert!(std::rc::Rc::ptr_eq(&a, &b));
+        // concatenation allocates a new string
+        assert_eq!(&*joined, "hihi");
+        assert!(!std::rc::Rc::ptr_eq(&a, &joined));
+    }
+
     #[test]
     fn init_run() {
         inspect("x = 0.0");
@@ -559,6 +690,175 @@ mod test {
         ");
     }
 
+    #[test]
+    fn prelude_globals() {
+        let mut prelude = Prelude::new();
+        prelude.register_global("answer", Data::Integer(42)).unwrap();
+        prelude.register_global("double", Data::NativeFunction(FFIFunction::new(
+            Box::new(|data| match data {
+                Data::Integer(n) => Ok(Data::Integer(n * 2)),
+                _ => Err("Expected an integer".to_string()),
+            })
+        ))).unwrap();
+
+        let lambda = lex(Source::source("x = answer\ndouble x"))
+            .and_then(parse)
+            .and_then(desugar)
+            .and_then(|cst| hoist_with_prelude(cst, &prelude))
+            .and_then(gen)
+            .unwrap();
+
+        let mut vm = VM::init_with_prelude(Closure::wrap(lambda), prelude);
+        vm.run().unwrap();
+        assert_eq!(vm.stack.pop_data(), Data::Integer(84));
+    }
+
+    #[test]
+    fn prelude_shadowed_global() {
+        let mut prelude = Prelude::new();
+        prelude.register_global("answer", Data::Integer(42)).unwrap();
+        let hoisted = |source: &str| lex(Source::source(source))
+            .and_then(parse)
+            .and_then(desugar)
+            .and_then(|cst| hoist_with_prelude(cst, &prelude));
+
+        // defining a local after using the global by its name is an error,
+        // even from a function that would otherwise have captured the local
+        for source in &["x = answer\nanswer = 1", "f = () -> answer\nanswer = 1"] {
+            let error = hoisted(source).unwrap_err();
+            assert_eq!(error.message, "'answer' was used as a global before being defined here");
+            assert_eq!(error.span.contents(), "answer");
+        }
+
+        // locals that never could have been used there are fine
+        assert!(hoisted("x = answer\nf = answer -> answer").is_ok());
+        assert!(hoisted("f = () -> answer\ng = () -> { answer = 1; answer }").is_ok());
+        assert!(hoisted("answer = 1\nanswer").is_ok());
+    }
+
+    #[test]
+    fn prelude_native_error() {
+        let mut prelude = Prelude::new();
+        prelude.register_global("fail", Data::NativeFunction(FFIFunction::new(
+            Box::new(|_| Err("Failed".to_string()))
+        ))).unwrap();
+
+        let lambda = lex(Source::source("fail ()"))
+            .and_then(parse)
+            .and_then(desugar)
+            .and_then(|cst| hoist_with_prelude(cst, &prelude))
+            .and_then(gen)
+            .unwrap();
+
+        let mut vm = VM::init_with_prelude(Closure::wrap(lambda), prelude);
+        assert!(vm.run().is_err());
+    }
+
+    #[test]
+    fn division_by_zero_span() {
+        let source = Source::source("x = 7\nx / 0");
+        let lambda = lex(source.clone())
+            .and_then(parse)
+            .and_then(desugar)
+            .and_then(hoist)
+            .and_then(gen)
+            .unwrap();
+
+        let mut vm = VM::init(Closure::wrap(lambda));
+        let trace = vm.run().unwrap_err();
+
+        // the trace points at the call, and blames the zero, not the `x`
+        assert_eq!(trace.spans(), &[Span::new(&source, 6, 5)]);
+        assert_eq!(trace.cause(), Some(&Span::new(&source, 10, 1)));
+    }
+
+    #[test]
+    fn uncaused_ffi_error() {
+        use crate::core::{ffi::FFI, math::DIVISION_BY_ZERO};
+
+        // only the function decides which operand is at fault, not its message
+        let mut ffi = FFI::new();
+        ffi.add("div", FFIFunction::new(Box::new(|_| Err(DIVISION_BY_ZERO.to_string())))).unwrap();
+
+        let source = Source::source("x = 7\nx / 0");
+        let lambda = lex(source.clone())
+            .and_then(parse)
+            .and_then(desugar)
+            .and_then(hoist)
+            .and_then(|sst| gen_with_ffi(sst, ffi))
+            .unwrap();
+
+        let trace = VM::init(Closure::wrap(lambda)).evaluate().unwrap_err();
+        assert_eq!(trace.spans(), &[Span::new(&source, 6, 5)]);
+        assert_eq!(trace.cause(), None);
+    }
+
+    #[test]
+    fn assertion_failure() {
+        let source = Source::source("x = 2\nassert x == 1");
+        let lambda = lex(source.clone())
+            .and_then(parse)
+            .and_then(desugar)
+            .and_then(hoist)
+            .and_then(gen)
+            .unwrap();
+
+        let mut vm = VM::init(Closure::wrap(lambda));
+        let trace = vm.run().unwrap_err();
+
+        assert_eq!(trace.cause(), Some(&Span::new(&source, 13, 6)));
+        assert!(format!("{}", trace).contains("Assertion failed: `x == 1`"));
+    }
+
+    #[test]
+    fn closure_captures_local() {
+        let mut vm = inspect("x = 1.0\nf = y -> x\nf");
+        let closure = match vm.stack.pop_data() {
+            Data::Closure(c) => c,
+            other => panic!("Expected a closure, found {:?}", other),
+        };
+
+        assert_eq!(closure.captures.len(), 1);
+        assert_eq!(*closure.captures[0].borrow(), Data::Real(1.0));
+    }
+
+    #[test]
+    fn heapifies_captured_locals() {
+        let mut vm = inspect("x = 1.0\ny = 2.0\nf = z -> x\n()");
+        assert!(matches!(vm.stack.local_data(0), Data::Heaped(_)));
+        assert_eq!(vm.stack.local_data(1), Data::Real(2.0));
+        assert!(matches!(vm.stack.local_data(2), Data::Closure(_)));
+    }
+
+    #[test]
+    fn closures_share_captures() {
+        // each local is only moved to the heap once, however many closures capture it
+        let source = "x = 1.0\nf = y -> x\ng = y -> x\nh = y -> () -> x\nx = 3.0\n(f (), g (), h () ())";
+        assert_eq!(evaluate(source), Ok(Data::Tuple(vec![Data::Real(3.0); 3])));
+    }
+
+    #[test]
+    fn closure_shares_capture() {
+        // the closure sees the captured local after it was reassigned
+        let mut vm = inspect("x = 1.0\nf = y -> x\nx = 2.0\nf ()");
+        assert_eq!(vm.stack.pop_data(), Data::Real(2.0));
+    }
+
+    #[test]
+    fn stack_overflow() {
+        let lambda = lex(Source::source("loop = x -> { loop x; x }\nloop ()"))
+            .and_then(parse)
+            .and_then(desugar)
+            .and_then(hoist)
+            .and_then(gen)
+            .unwrap();
+
+        let mut vm = VM::init(Closure::wrap(lambda));
+        vm.stack.recursion_limit = 100;
+        let trace = vm.run().unwrap_err();
+        assert!(format!("{}", trace).contains("Stack overflow at depth 100"));
+    }
+
     // TODO: figure out how to make the following passerine code into a test
     // without entering into an infinite loop (which is the intended behaviour)
     // maybe try running it a large number of times,